@@ -0,0 +1,37 @@
+CREATE TABLE IF NOT EXISTS rank_snapshots
+(
+    id INTEGER PRIMARY KEY,
+    observed_at TIMESTAMP NOT NULL,
+    constructed_season_ordinal INTEGER,
+    constructed_class TEXT,
+    constructed_tier INTEGER,
+    constructed_step INTEGER,
+    constructed_matches_won INTEGER,
+    constructed_matches_lost INTEGER,
+    constructed_matches_drawn INTEGER,
+    constructed_percentile REAL,
+    constructed_placement INTEGER,
+    limited_season_ordinal INTEGER,
+    limited_class TEXT,
+    limited_tier INTEGER,
+    limited_step INTEGER,
+    limited_matches_won INTEGER,
+    limited_matches_lost INTEGER,
+    limited_matches_drawn INTEGER,
+    limited_percentile REAL,
+    limited_placement INTEGER
+);
+
+-- several snapshots can share a second, they are told apart by their ranks
+CREATE INDEX IF NOT EXISTS rank_snapshots_observed_at_idx ON rank_snapshots (observed_at);
+
+ALTER TABLE matches ADD COLUMN ended_at TIMESTAMP;
+
+CREATE TABLE IF NOT EXISTS match_ranks (
+    match_id TEXT NOT NULL,
+    relation TEXT NOT NULL CHECK (relation IN ('before', 'after')),
+    rank_snapshot_id INTEGER NOT NULL,
+    PRIMARY KEY (match_id, relation),
+    FOREIGN KEY (match_id) REFERENCES matches(id),
+    FOREIGN KEY (rank_snapshot_id) REFERENCES rank_snapshots(id)
+);
\ No newline at end of file
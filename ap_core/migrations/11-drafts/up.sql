@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS drafts
+(
+    id TEXT PRIMARY KEY,
+    event_id TEXT,
+    kind TEXT,
+    started_at TIMESTAMP,
+    pool TEXT
+);
+
+CREATE TABLE IF NOT EXISTS draft_picks
+(
+    draft_id TEXT,
+    pack_number INTEGER,
+    pick_number INTEGER,
+    cards_offered TEXT,
+    cards_picked TEXT,
+    picked_at TIMESTAMP,
+    PRIMARY KEY (draft_id, pack_number, pick_number),
+    FOREIGN KEY (draft_id) REFERENCES drafts(id)
+);
\ No newline at end of file
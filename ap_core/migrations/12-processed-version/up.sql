@@ -0,0 +1,6 @@
+ALTER TABLE matches ADD COLUMN processed_version TEXT;
+ALTER TABLE decks ADD COLUMN processed_version TEXT;
+ALTER TABLE mulligans ADD COLUMN processed_version TEXT;
+ALTER TABLE match_results ADD COLUMN processed_version TEXT;
+ALTER TABLE drafts ADD COLUMN processed_version TEXT;
+ALTER TABLE draft_picks ADD COLUMN processed_version TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE matches ADD COLUMN format TEXT;
+ALTER TABLE matches ADD COLUMN opponent_identity TEXT;
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS deck_versions
+(
+    fingerprint TEXT PRIMARY KEY,
+    deck_id TEXT,
+    name TEXT,
+    lineage_id TEXT NOT NULL,
+    parent_fingerprint TEXT,
+    deck_cards TEXT,
+    sideboard_cards TEXT,
+    first_seen_at TIMESTAMP,
+    processed_version TEXT
+);
+
+CREATE INDEX IF NOT EXISTS deck_versions_lineage_idx ON deck_versions (lineage_id);
+
+ALTER TABLE decks ADD COLUMN fingerprint TEXT;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS deck_diffs (
+    match_id TEXT,
+    game_number INTEGER,
+    cards_in TEXT,
+    cards_out TEXT,
+    processed_version TEXT,
+    PRIMARY KEY (match_id, game_number),
+    FOREIGN KEY (match_id) REFERENCES matches(id)
+);
@@ -0,0 +1,269 @@
+use std::iter::FusedIterator;
+use std::result::Result as StdResult;
+
+use anyhow::Result;
+use tracing::warn;
+
+use crate::processor::{ArenaEventSource, LogEvent, ParseError, ParseOutput};
+use crate::replay::{MatchReplay, MatchReplayBuilder};
+
+//
+// Iterator (and with the `stream` feature, `futures::Stream`) adapters over `ArenaEventSource`,
+// so events can be consumed with the standard combinators instead of hand written loops, e.g.
+//
+// `PlayerLogProcessor::from_reader(log_text.as_bytes()).events().filter_map(Result::ok)`
+//
+
+/// Yields the events of a source until it runs out.
+/// Json that isn't shaped like any known event is skipped, events that fail to decode
+/// are yielded as errors, and iteration ends after an IO error
+pub struct LogEvents<S> {
+    source: S,
+    finished: bool,
+}
+
+/// Like `LogEvents`, without the log header metadata
+pub struct Events<S>(LogEvents<S>);
+
+impl<S> LogEvents<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self {
+            source,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S> Events<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self(LogEvents::new(source))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0.into_inner()
+    }
+}
+
+impl<S: ArenaEventSource> Iterator for LogEvents<S> {
+    type Item = StdResult<LogEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        loop {
+            match self.source.get_next_log_event() {
+                Err(ParseError::EndOfInput) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(ParseError::UnknownShape(_)) => {}
+                Err(e @ ParseError::Io(_)) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+                result => return Some(result),
+            }
+        }
+    }
+}
+
+impl<S: ArenaEventSource> FusedIterator for LogEvents<S> {}
+
+impl<S: ArenaEventSource> Iterator for Events<S> {
+    type Item = StdResult<ParseOutput, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0
+            .next()
+            .map(|result| result.map(|log_event| log_event.output))
+    }
+}
+
+impl<S: ArenaEventSource> FusedIterator for Events<S> {}
+
+/// Yields each match in a source once its end message has been read.
+/// Events that fail to decode are logged and skipped, an IO error is yielded and ends iteration
+pub struct MatchReplays<S> {
+    log_events: LogEvents<S>,
+    match_replay_builder: MatchReplayBuilder,
+}
+
+impl<S> MatchReplays<S> {
+    pub(crate) fn new(source: S) -> Self {
+        Self {
+            log_events: LogEvents::new(source),
+            match_replay_builder: MatchReplayBuilder::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.log_events.into_inner()
+    }
+}
+
+impl<S: ArenaEventSource> Iterator for MatchReplays<S> {
+    type Item = Result<MatchReplay>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.log_events.next()? {
+                Ok(log_event) => {
+                    if self.match_replay_builder.ingest_log_event(log_event) {
+                        return Some(self.match_replay_builder.build());
+                    }
+                }
+                Err(e @ ParseError::Io(_)) => return Some(Err(e.into())),
+                Err(e) => warn!("{e}"),
+            }
+        }
+    }
+}
+
+impl<S: ArenaEventSource> FusedIterator for MatchReplays<S> {}
+
+#[cfg(feature = "stream")]
+pub use stream::FollowingEventStream;
+
+#[cfg(feature = "stream")]
+mod stream {
+    use futures::channel::mpsc::{unbounded, UnboundedReceiver};
+    use futures::{Stream, StreamExt};
+    use std::pin::Pin;
+    use std::result::Result as StdResult;
+    use std::task::{Context, Poll};
+
+    use crate::processor::{
+        ArenaEventSource, FollowingEventSource, FollowingStopHandle, LogEvent, ParseError,
+    };
+
+    /// Events from a `FollowingEventSource` as a `futures::Stream`.
+    /// The source is read on its own thread, which is stopped when the stream is dropped
+    pub struct FollowingEventStream {
+        receiver: UnboundedReceiver<StdResult<LogEvent, ParseError>>,
+        stop_handle: FollowingStopHandle,
+    }
+
+    impl FollowingEventSource {
+        pub fn into_stream(self) -> FollowingEventStream {
+            let (sender, receiver) = unbounded();
+            let stop_handle = self.stop_handle();
+            std::thread::spawn(move || {
+                for result in self.log_events() {
+                    if sender.unbounded_send(result).is_err() {
+                        break;
+                    }
+                }
+            });
+            FollowingEventStream {
+                receiver,
+                stop_handle,
+            }
+        }
+    }
+
+    impl FollowingEventStream {
+        pub fn stop_handle(&self) -> FollowingStopHandle {
+            self.stop_handle.clone()
+        }
+    }
+
+    impl Stream for FollowingEventStream {
+        type Item = StdResult<LogEvent, ParseError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.receiver.poll_next_unpin(cx)
+        }
+    }
+
+    impl Drop for FollowingEventStream {
+        fn drop(&mut self) {
+            self.stop_handle.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processor::{ArenaEventSource, ParseError, ParseOutput, PlayerLogProcessor};
+
+    const LOG: &str = concat!(
+        "{\"greToClientEvent\":{\"greToClientMessages\":\"oops\"}}\n",
+        "{\"unrelated\": true}\n",
+        "[UnityCrossThreadLogger]==> EventJoin {\"id\":\"abc\",\"request\":\"{\\\"EventName\\\":\\\"Ladder\\\"}\"}\n",
+    );
+
+    #[test]
+    fn test_events_from_str() {
+        let events: Vec<_> = PlayerLogProcessor::from_reader(LOG.as_bytes())
+            .events()
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[0], Err(ParseError::Deserialize(..))));
+        assert!(matches!(events[1], Ok(ParseOutput::FrontDoor(_))));
+    }
+
+    #[test]
+    fn test_log_events_by_ref() {
+        let mut processor = PlayerLogProcessor::from_reader(LOG.as_bytes());
+        let line_numbers: Vec<_> = (&mut processor)
+            .log_events()
+            .filter_map(Result::ok)
+            .map(|log_event| log_event.envelope.line_number)
+            .collect();
+        assert_eq!(line_numbers, vec![3]);
+        assert!(matches!(
+            processor.get_next_log_event(),
+            Err(ParseError::EndOfInput)
+        ));
+    }
+
+    #[test]
+    fn test_match_replays() -> anyhow::Result<()> {
+        let mgrsc = |state_type: &str| {
+            format!(
+                "{{\"matchGameRoomStateChangedEvent\":{{\"gameRoomInfo\":{{\"gameRoomConfig\":{{\"matchId\":\"m1\"}},\"stateType\":\"MatchGameRoomStateType_{state_type}\"}}}},\"timestamp\":\"1\",\"transactionId\":\"t\"}}\n"
+            )
+        };
+        let log = format!("{}{LOG}{}", mgrsc("Playing"), mgrsc("MatchCompleted"));
+        let match_replays = PlayerLogProcessor::from_reader(std::io::Cursor::new(log))
+            .match_replays()
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(match_replays.len(), 1);
+        assert_eq!(match_replays[0].match_id, "m1");
+        Ok(())
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn test_following_event_stream() -> anyhow::Result<()> {
+        use crate::processor::FollowingEventSource;
+        use futures::StreamExt;
+        use std::io::Write;
+        use std::time::Duration;
+
+        let dir = std::env::temp_dir().join(format!("ap_core_{}_stream", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(&log_path, "")?;
+        let mut stream = FollowingEventSource::new(PlayerLogProcessor::try_new(log_path.clone())?)
+            .with_poll_interval(Duration::from_millis(50))
+            .into_stream();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_path)?
+            .write_all(LOG.as_bytes())?;
+        let first = futures::executor::block_on(stream.next());
+        assert!(matches!(first, Some(Err(ParseError::Deserialize(..)))));
+        let second = futures::executor::block_on(stream.next());
+        assert!(matches!(second, Some(Ok(_))));
+        stream.stop_handle().stop();
+        assert!(futures::executor::block_on(stream.next()).is_none());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -34,6 +34,26 @@ pub struct CardDbEntry {
     pub colors: Option<Vec<String>>,
     pub color_identity: Vec<String>,
     pub card_faces: Option<Vec<CardFace>>,
+    #[serde(default)]
+    pub collector_number: Option<String>,
+}
+
+impl CardDbEntry {
+    /// The name MTGA uses in deck lists: the front face of double faced and adventure cards,
+    /// and both halves of split cards
+    pub fn arena_name(&self) -> &str {
+        match &self.card_faces {
+            Some(card_faces) if !matches!(self.layout.as_str(), "split" | "aftermath") => {
+                card_faces.first().map_or(&self.name, |face| &face.name)
+            }
+            _ => &self.name,
+        }
+    }
+
+    /// Whether `name` is the card's full name or its name in MTGA deck lists, ignoring case
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.arena_name().eq_ignore_ascii_case(name)
+    }
 }
 
 impl CardsDatabase {
@@ -78,6 +98,60 @@ impl CardsDatabase {
         let grp_id = grp_id.to_string();
         self.db.get(&grp_id)
     }
+
+    /// A printing of the card called `name`: the one from `set` with `collector_number` if
+    /// there is one, else the newest from `set`, else the newest of all
+    pub fn find_by_name(
+        &self,
+        name: &str,
+        set: Option<&str>,
+        collector_number: Option<&str>,
+    ) -> Option<&CardDbEntry> {
+        let printings: Vec<&CardDbEntry> = self
+            .db
+            .values()
+            .filter(|card| card.is_named(name))
+            .collect();
+        let in_set =
+            |card: &&&CardDbEntry| set.is_some_and(|set| card.set.eq_ignore_ascii_case(set));
+        printings
+            .iter()
+            .filter(in_set)
+            .find(|card| {
+                collector_number.is_some() && card.collector_number.as_deref() == collector_number
+            })
+            .or_else(|| printings.iter().filter(in_set).max_by_key(|card| card.id))
+            .or_else(|| printings.iter().max_by_key(|card| card.id))
+            .copied()
+    }
+}
+
+/// Legality of each card per format, kept in its own file next to the cards database,
+/// e.g. `{"7000": {"standard": "not_legal", "historic": "legal"}}` with scryfall's format names
+#[derive(Debug, Default)]
+pub struct LegalityTable {
+    pub legalities: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl LegalityTable {
+    /// # Errors
+    ///
+    /// Will return an error if the file cannot be opened or is not valid JSON
+    pub fn new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(Self {
+            legalities: serde_json::from_reader(reader)?,
+        })
+    }
+
+    /// Whether a card may be played in `format`, None if the table does not say
+    pub fn is_legal<T>(&self, grp_id: &T, format: &str) -> Option<bool>
+    where
+        T: Display + ?Sized,
+    {
+        let legality = self.legalities.get(&grp_id.to_string())?.get(format)?;
+        Some(matches!(legality.as_str(), "legal" | "restricted"))
+    }
 }
 
 impl Default for CardsDatabase {
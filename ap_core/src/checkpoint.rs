@@ -0,0 +1,261 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, Metadata};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::info;
+
+use crate::draft::DraftLobby;
+use crate::log_envelope::LogEnvelope;
+
+// how many bytes in front of the checkpoint offset are kept to recognize the same log later
+const FINGERPRINT_LEN: u64 = 64;
+
+/// A place in Player.log that processing can restart from:
+/// the start of a line that is not inside a json event
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogPosition {
+    pub byte_offset: u64,
+    /// number of lines before `byte_offset`
+    pub line_number: usize,
+    /// header information read before `byte_offset` that applies to the next event
+    pub envelope: LogEnvelope,
+    /// which of the files a `PlayerLogProcessor` has read the position is in, counting up
+    /// each time it switches files, so positions from before a switch can be told apart.
+    /// Only meaningful to the processor that produced it, so never stored
+    #[serde(skip)]
+    pub generation: u32,
+}
+
+/// What the filesystem can tell us about which file a path pointed to
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogFileIdentity {
+    pub created: Option<SystemTime>,
+    /// inode number, only available on unix
+    pub inode: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogCheckpoint {
+    pub log_path: PathBuf,
+    pub identity: LogFileIdentity,
+    /// size of the log when the checkpoint was taken
+    pub file_size: u64,
+    /// everything before this position has been fully processed
+    pub position: LogPosition,
+    /// the bytes of the log just before `position.byte_offset`
+    pub fingerprint: Vec<u8>,
+    /// lobby state read before `position` that the next draft is named from
+    #[serde(default)]
+    pub draft_lobby: DraftLobby,
+}
+
+pub trait CheckpointStore {
+    /// # Errors
+    ///
+    /// Will return an error if a stored checkpoint exists but cannot be read
+    fn load(&mut self) -> Result<Option<LogCheckpoint>>;
+
+    /// # Errors
+    ///
+    /// Will return an error if the checkpoint cannot be written to the store
+    fn save(&mut self, checkpoint: &LogCheckpoint) -> Result<()>;
+}
+
+/// Keeps a single checkpoint as a json file
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&mut self) -> Result<Option<LogCheckpoint>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+    }
+
+    fn save(&mut self, checkpoint: &LogCheckpoint) -> Result<()> {
+        // write then rename, so an interrupted save never leaves a half written checkpoint
+        let tmp_path = self.path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, checkpoint)?;
+        writer
+            .into_inner()
+            .map_err(std::io::IntoInnerError::into_error)?;
+        std::fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl LogFileIdentity {
+    pub(crate) fn new(metadata: &Metadata) -> Self {
+        Self {
+            created: metadata.created().ok(),
+            inode: inode(metadata),
+        }
+    }
+
+    /// Fields that either side doesn't know about are not compared
+    pub(crate) fn is_same_file(&self, other: &Self) -> bool {
+        matches_if_known(self.created.as_ref(), other.created.as_ref())
+            && matches_if_known(self.inode.as_ref(), other.inode.as_ref())
+    }
+}
+
+fn matches_if_known<T: PartialEq>(a: Option<&T>, b: Option<&T>) -> bool {
+    a.is_none() || b.is_none() || a == b
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn inode(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+fn read_fingerprint(file: &mut File, byte_offset: u64) -> Result<Vec<u8>> {
+    let start = byte_offset.saturating_sub(FINGERPRINT_LEN);
+    file.seek(SeekFrom::Start(start))?;
+    let mut fingerprint = Vec::new();
+    file.take(byte_offset - start)
+        .read_to_end(&mut fingerprint)?;
+    Ok(fingerprint)
+}
+
+impl LogCheckpoint {
+    /// # Errors
+    ///
+    /// Will return an error if the log file cannot be read
+    pub fn capture(log_path: &Path, position: LogPosition) -> Result<Self> {
+        let mut file = File::open(log_path)?;
+        let metadata = file.metadata()?;
+        let fingerprint = read_fingerprint(&mut file, position.byte_offset)?;
+        Ok(Self {
+            log_path: log_path.to_path_buf(),
+            identity: LogFileIdentity::new(&metadata),
+            file_size: metadata.len(),
+            position,
+            fingerprint,
+            draft_lobby: DraftLobby::default(),
+        })
+    }
+
+    /// Checks that the log at `log_path` is still the file this checkpoint was taken from,
+    /// and that it hasn't been truncated or rewritten since
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the log file cannot be read
+    pub fn is_valid_for(&self, log_path: &Path) -> Result<bool> {
+        let mut file = File::open(log_path)?;
+        let metadata = file.metadata()?;
+        if !self.identity.is_same_file(&LogFileIdentity::new(&metadata)) {
+            info!(
+                "{} has been replaced since the last checkpoint",
+                log_path.display()
+            );
+            return Ok(false);
+        }
+        if metadata.len() < self.position.byte_offset {
+            info!(
+                "{} has been truncated since the last checkpoint",
+                log_path.display()
+            );
+            return Ok(false);
+        }
+        if read_fingerprint(&mut file, self.position.byte_offset)? != self.fingerprint {
+            info!(
+                "{} has been rewritten since the last checkpoint",
+                log_path.display()
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn scratch_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ap_core_{}_{name}", std::process::id()))
+    }
+
+    fn position(byte_offset: u64) -> LogPosition {
+        LogPosition {
+            byte_offset,
+            ..LogPosition::default()
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_survives_appends() -> Result<()> {
+        let log_path = scratch_path("appended.log");
+        std::fs::write(&log_path, "first line\nsecond line\n")?;
+        let checkpoint = LogCheckpoint::capture(&log_path, position(11))?;
+        assert_eq!(checkpoint.fingerprint, b"first line\n");
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log_path)?;
+        file.write_all(b"third line\n")?;
+        assert!(checkpoint.is_valid_for(&log_path)?);
+        std::fs::remove_file(log_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_detects_truncation() -> Result<()> {
+        let log_path = scratch_path("truncated.log");
+        std::fs::write(&log_path, "first line\nsecond line\n")?;
+        let checkpoint = LogCheckpoint::capture(&log_path, position(23))?;
+
+        File::create(&log_path)?.write_all(b"new\n")?;
+        assert!(!checkpoint.is_valid_for(&log_path)?);
+        std::fs::remove_file(log_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_detects_rewrite() -> Result<()> {
+        let log_path = scratch_path("rewritten.log");
+        std::fs::write(&log_path, "first line\nsecond line\n")?;
+        let checkpoint = LogCheckpoint::capture(&log_path, position(11))?;
+
+        std::fs::write(&log_path, "other line\nsecond line\nthird line\n")?;
+        assert!(!checkpoint.is_valid_for(&log_path)?);
+        std::fs::remove_file(log_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_checkpoint_store() -> Result<()> {
+        let log_path = scratch_path("stored.log");
+        let store_path = scratch_path("checkpoint.json");
+        std::fs::write(&log_path, "first line\n")?;
+        let mut store = FileCheckpointStore::new(store_path.clone());
+        assert_eq!(store.load()?, None);
+
+        let checkpoint = LogCheckpoint::capture(&log_path, position(11))?;
+        store.save(&checkpoint)?;
+        assert_eq!(store.load()?, Some(checkpoint));
+        std::fs::remove_file(log_path)?;
+        std::fs::remove_file(store_path)?;
+        Ok(())
+    }
+}
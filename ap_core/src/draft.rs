@@ -0,0 +1,577 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::mtga_events::frontdoor::{
+    BotDraftStatus, Course, FrontDoorEvent, FrontDoorNotificationPayload, FrontDoorRequestPayload,
+    FrontDoorResponsePayload,
+};
+use crate::processor::{LogEvent, ParseOutput};
+
+const BOT_DRAFT_COMPLETED: &str = "Completed";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DraftKind {
+    /// drafting against bots, e.g. Quick Draft
+    #[default]
+    BotDraft,
+    /// drafting against other players, e.g. Premier and Traditional Draft
+    PlayerDraft,
+    /// no picks, the pool is granted when joining the event
+    Sealed,
+}
+
+impl Display for DraftKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl FromStr for DraftKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "BotDraft" => Ok(Self::BotDraft),
+            "PlayerDraft" => Ok(Self::PlayerDraft),
+            "Sealed" => Ok(Self::Sealed),
+            _ => Err(anyhow!("Unknown draft kind: {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DraftPick {
+    /// 1-based
+    pub pack_number: i32,
+    /// 1-based
+    pub pick_number: i32,
+    pub cards_offered: Vec<i32>,
+    /// usually a single card, more for "pick two" formats
+    pub cards_picked: Vec<i32>,
+    pub picked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DraftReplay {
+    pub draft_id: String,
+    /// e.g. `QuickDraft_MKM_20240301`
+    pub event_id: String,
+    pub kind: DraftKind,
+    pub started_at: Option<DateTime<Utc>>,
+    pub picks: Vec<DraftPick>,
+    /// every card the player ended up with
+    pub pool: Vec<i32>,
+}
+
+/// What the lobby tells us ahead of a draft that is needed to name it. It is kept from one
+/// draft to the next, and saved with checkpoints so a resumed run names drafts the same
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DraftLobby {
+    /// player drafts don't name their event, so remember the last one joined
+    pub last_joined_event: Option<String>,
+    /// course ids by event name, from joining or listing events
+    pub course_ids: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct DraftReplayBuilder {
+    pub draft_id: Option<String>,
+    pub event_id: Option<String>,
+    pub kind: DraftKind,
+    pub started_at: Option<DateTime<Utc>>,
+    pub picks: BTreeMap<(i32, i32), DraftPick>,
+    pub pool: Option<Vec<i32>>,
+    lobby: DraftLobby,
+}
+
+impl DraftReplayBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Picks up from the lobby state saved with a checkpoint
+    pub fn with_lobby(lobby: DraftLobby) -> Self {
+        Self {
+            lobby,
+            ..Self::default()
+        }
+    }
+
+    pub fn lobby(&self) -> &DraftLobby {
+        &self.lobby
+    }
+
+    /// True if no draft is in progress, so no picks would be lost by restarting
+    /// processing from this point with the current `lobby`
+    pub fn is_idle(&self) -> bool {
+        self.draft_id.is_none()
+    }
+
+    /// Returns true once the draft is complete and ready to `build`
+    pub fn ingest_log_event(&mut self, log_event: &LogEvent) -> bool {
+        let ParseOutput::FrontDoor(front_door_event) = &log_event.output else {
+            return false;
+        };
+        let observed_at = log_event.envelope.timestamp_utc();
+        self.ingest_front_door_event(front_door_event, observed_at)
+    }
+
+    /// Returns true once the draft is complete and ready to `build`.
+    /// `observed_at` is the log header time, if the event had one
+    pub fn ingest_front_door_event(
+        &mut self,
+        front_door_event: &FrontDoorEvent,
+        observed_at: Option<DateTime<Utc>>,
+    ) -> bool {
+        match front_door_event {
+            FrontDoorEvent::Request(request) => match &request.payload {
+                FrontDoorRequestPayload::EventJoin(join) => {
+                    self.lobby.last_joined_event = Some(join.event_name.clone());
+                }
+                FrontDoorRequestPayload::BotDraftDraftPick(pick) => {
+                    let pick_info = &pick.pick_info;
+                    self.record_pick(
+                        (pick_info.pack_number + 1, pick_info.pick_number + 1),
+                        pick_info.picked(),
+                        observed_at,
+                    );
+                }
+                FrontDoorRequestPayload::EventPlayerDraftMakePick(pick) => {
+                    if self.draft_id.as_deref() != Some(pick.draft_id.as_str()) {
+                        self.start(pick.draft_id.clone(), DraftKind::PlayerDraft, observed_at);
+                    }
+                    self.record_pick((pick.pack, pick.pick), pick.picked(), observed_at);
+                }
+                // submitting a deck for the event means picking is over
+                FrontDoorRequestPayload::EventSetDeck(set_deck) => {
+                    return self.draft_id.is_some()
+                        && self.event_id.as_deref() == Some(set_deck.event_name.as_str());
+                }
+                _ => {}
+            },
+            FrontDoorEvent::Response(response) => match &response.payload {
+                FrontDoorResponsePayload::BotDraftDraftStatus(bot_draft)
+                | FrontDoorResponsePayload::BotDraftDraftPick(bot_draft) => {
+                    return self.ingest_bot_draft_status(&bot_draft.payload, observed_at);
+                }
+                FrontDoorResponsePayload::EventPlayerDraftMakePick(pick) => {
+                    return self.draft_id.is_some() && pick.is_picking_completed == Some(true);
+                }
+                FrontDoorResponsePayload::EventJoin(course_response) => {
+                    if let Some(course) = &course_response.course {
+                        self.lobby.last_joined_event = Some(course.internal_event_name.clone());
+                        self.record_course_id(course);
+                        return self.ingest_sealed_pool(course, observed_at);
+                    }
+                }
+                FrontDoorResponsePayload::EventGetCourses(courses_response) => {
+                    for course in &courses_response.courses {
+                        self.record_course_id(course);
+                    }
+                }
+                _ => {}
+            },
+            FrontDoorEvent::Notification(notification) => {
+                if let FrontDoorNotificationPayload::DraftNotify(draft_notify) =
+                    &notification.payload
+                {
+                    if self.draft_id.as_deref() != Some(draft_notify.draft_id.as_str()) {
+                        self.start(
+                            draft_notify.draft_id.clone(),
+                            DraftKind::PlayerDraft,
+                            observed_at,
+                        );
+                    }
+                    self.record_pack(
+                        (draft_notify.self_pack, draft_notify.self_pick),
+                        &draft_notify.pack_cards,
+                    );
+                }
+            }
+        }
+        false
+    }
+
+    fn start(&mut self, draft_id: String, kind: DraftKind, started_at: Option<DateTime<Utc>>) {
+        if let Some(previous) = &self.draft_id {
+            warn!("Draft {previous} was not completed before draft {draft_id} started");
+        }
+        info!("found draft: {draft_id}");
+        let event_id = match kind {
+            DraftKind::PlayerDraft => self.lobby.last_joined_event.clone(),
+            DraftKind::BotDraft | DraftKind::Sealed => None,
+        };
+        self.take_draft();
+        self.draft_id = Some(draft_id);
+        self.event_id = event_id;
+        self.kind = kind;
+        self.started_at = started_at;
+    }
+
+    /// Leaves only the lobby state behind, and returns everything else
+    fn take_draft(&mut self) -> Self {
+        let lobby = self.lobby.clone();
+        std::mem::replace(self, Self::with_lobby(lobby))
+    }
+
+    fn record_course_id(&mut self, course: &Course) {
+        if let Some(course_id) = &course.course_id {
+            self.lobby
+                .course_ids
+                .insert(course.internal_event_name.clone(), course_id.clone());
+        }
+    }
+
+    /// Drafts without an id of their own are named after their event and course, or else
+    /// after the log time they were first seen at, so reading the same log twice names
+    /// them the same
+    fn event_draft_id(
+        &self,
+        event_name: &str,
+        observed_at: Option<DateTime<Utc>>,
+    ) -> Option<String> {
+        let suffix = self
+            .lobby
+            .course_ids
+            .get(event_name)
+            .cloned()
+            .or_else(|| observed_at.map(|observed_at| observed_at.timestamp().to_string()));
+        if suffix.is_none() {
+            warn!("Skipping {event_name} draft, it has neither a course id nor a log timestamp");
+        }
+        suffix.map(|suffix| format!("{event_name}_{suffix}"))
+    }
+
+    fn ingest_bot_draft_status(
+        &mut self,
+        status: &BotDraftStatus,
+        observed_at: Option<DateTime<Utc>>,
+    ) -> bool {
+        if self.event_id.as_deref() != Some(status.event_name.as_str()) {
+            if status.draft_status == BOT_DRAFT_COMPLETED {
+                // revisiting an event whose draft we already saw (or missed) finish
+                return false;
+            }
+            let Some(draft_id) = self.event_draft_id(&status.event_name, observed_at) else {
+                return false;
+            };
+            self.start(draft_id, DraftKind::BotDraft, observed_at);
+            self.event_id = Some(status.event_name.clone());
+        }
+        if !status.draft_pack.is_empty() {
+            self.record_pack(
+                (status.pack_number + 1, status.pick_number + 1),
+                &status.draft_pack,
+            );
+        }
+        if status.draft_status == BOT_DRAFT_COMPLETED {
+            self.pool = Some(status.picked_cards.clone());
+            return true;
+        }
+        false
+    }
+
+    fn ingest_sealed_pool(&mut self, course: &Course, observed_at: Option<DateTime<Utc>>) -> bool {
+        if course.card_pool.is_empty() || !course.internal_event_name.contains("Sealed") {
+            return false;
+        }
+        let Some(draft_id) = course
+            .course_id
+            .clone()
+            .or_else(|| self.event_draft_id(&course.internal_event_name, observed_at))
+        else {
+            return false;
+        };
+        self.start(draft_id, DraftKind::Sealed, observed_at);
+        self.event_id = Some(course.internal_event_name.clone());
+        self.pool = Some(course.card_pool.clone());
+        true
+    }
+
+    /// `key` is the 1-based (pack, pick) pair
+    fn record_pack(&mut self, key: (i32, i32), cards_offered: &[i32]) {
+        let pick = self.picks.entry(key).or_default();
+        (pick.pack_number, pick.pick_number) = key;
+        pick.cards_offered = cards_offered.to_vec();
+    }
+
+    fn record_pick(
+        &mut self,
+        key: (i32, i32),
+        cards_picked: Vec<i32>,
+        picked_at: Option<DateTime<Utc>>,
+    ) {
+        let pick = self.picks.entry(key).or_default();
+        (pick.pack_number, pick.pick_number) = key;
+        pick.cards_picked = cards_picked;
+        pick.picked_at = picked_at;
+    }
+
+    /// Builds the draft and starts over, keeping the lobby state for the next one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no draft was found
+    pub fn build(&mut self) -> Result<DraftReplay> {
+        let draft = self.take_draft();
+        let draft_id = draft.draft_id.ok_or(anyhow!("Missing Draft Id"))?;
+        let picks: Vec<DraftPick> = draft.picks.into_values().collect();
+        let pool = draft.pool.unwrap_or_else(|| {
+            picks
+                .iter()
+                .flat_map(|pick| pick.cards_picked.iter().copied())
+                .collect()
+        });
+        Ok(DraftReplay {
+            draft_id,
+            event_id: draft.event_id.unwrap_or_default(),
+            kind: draft.kind,
+            started_at: draft.started_at,
+            picks,
+            pool,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::frontdoor::{
+        BotDraftPickInfo, BotDraftPickRequest, BotDraftResponse, CourseResponse, DraftNotification,
+        EventGetCoursesResponse, EventJoinRequest, FrontDoorNotification, FrontDoorRequest,
+        FrontDoorResponse, PlayerDraftMakePickRequest, PlayerDraftMakePickResponse,
+    };
+
+    fn request(payload: FrontDoorRequestPayload) -> FrontDoorEvent {
+        FrontDoorEvent::Request(FrontDoorRequest {
+            id: String::new(),
+            method: String::new(),
+            payload,
+        })
+    }
+
+    fn response(payload: FrontDoorResponsePayload) -> FrontDoorEvent {
+        FrontDoorEvent::Response(FrontDoorResponse {
+            request_id: None,
+            method: String::new(),
+            payload,
+        })
+    }
+
+    fn bot_status(
+        pick_number: i32,
+        pack: Vec<i32>,
+        picked: Vec<i32>,
+        status: &str,
+    ) -> FrontDoorEvent {
+        response(FrontDoorResponsePayload::BotDraftDraftPick(
+            BotDraftResponse {
+                current_module: Some("BotDraft".to_string()),
+                payload: BotDraftStatus {
+                    event_name: "QuickDraft_MKM".to_string(),
+                    draft_status: status.to_string(),
+                    pick_number,
+                    draft_pack: pack,
+                    picked_cards: picked,
+                    ..BotDraftStatus::default()
+                },
+            },
+        ))
+    }
+
+    fn bot_pick(pick_number: i32, card: i32) -> FrontDoorEvent {
+        request(FrontDoorRequestPayload::BotDraftDraftPick(
+            BotDraftPickRequest {
+                event_name: "QuickDraft_MKM".to_string(),
+                pick_info: BotDraftPickInfo {
+                    card_ids: vec![card],
+                    pick_number,
+                    ..BotDraftPickInfo::default()
+                },
+            },
+        ))
+    }
+
+    #[test]
+    fn test_bot_draft() -> Result<()> {
+        let now = Some(Utc::now());
+        let mut builder = DraftReplayBuilder::new();
+        let events = [
+            bot_status(0, vec![1, 2, 3], vec![], "PickNext"),
+            bot_pick(0, 2),
+            bot_status(1, vec![4, 5], vec![2], "PickNext"),
+            bot_pick(1, 5),
+        ];
+        for event in &events {
+            assert!(!builder.ingest_front_door_event(event, now));
+        }
+        assert!(
+            builder.ingest_front_door_event(&bot_status(2, vec![], vec![2, 5], "Completed"), now)
+        );
+
+        let draft = builder.build()?;
+        assert_eq!(draft.kind, DraftKind::BotDraft);
+        assert_eq!(draft.event_id, "QuickDraft_MKM");
+        assert_eq!(draft.picks.len(), 2);
+        assert_eq!(draft.picks[0].pack_number, 1);
+        assert_eq!(draft.picks[0].pick_number, 1);
+        assert_eq!(draft.picks[0].cards_offered, vec![1, 2, 3]);
+        assert_eq!(draft.picks[0].cards_picked, vec![2]);
+        assert_eq!(draft.picks[1].cards_offered, vec![4, 5]);
+        assert_eq!(draft.picks[1].cards_picked, vec![5]);
+        assert_eq!(draft.pool, vec![2, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_draft() -> Result<()> {
+        let now = Some(Utc::now());
+        let mut builder = DraftReplayBuilder::new();
+        let join = request(FrontDoorRequestPayload::EventJoin(EventJoinRequest {
+            event_name: "PremierDraft_MKM".to_string(),
+            ..EventJoinRequest::default()
+        }));
+        let notify = FrontDoorEvent::Notification(FrontDoorNotification {
+            method: "Draft.Notify".to_string(),
+            payload: FrontDoorNotificationPayload::DraftNotify(DraftNotification {
+                draft_id: "d-1".to_string(),
+                self_pack: 1,
+                self_pick: 1,
+                pack_cards: vec![7, 8, 9],
+            }),
+        });
+        let make_pick = request(FrontDoorRequestPayload::EventPlayerDraftMakePick(
+            PlayerDraftMakePickRequest {
+                draft_id: "d-1".to_string(),
+                grp_ids: vec![8],
+                pack: 1,
+                pick: 1,
+                ..PlayerDraftMakePickRequest::default()
+            },
+        ));
+        let completed = response(FrontDoorResponsePayload::EventPlayerDraftMakePick(
+            PlayerDraftMakePickResponse {
+                is_picking_completed: Some(true),
+                ..PlayerDraftMakePickResponse::default()
+            },
+        ));
+        assert!(!builder.ingest_front_door_event(&join, now));
+        assert!(!builder.ingest_front_door_event(&notify, now));
+        assert!(!builder.ingest_front_door_event(&make_pick, now));
+        assert!(builder.ingest_front_door_event(&completed, now));
+
+        let draft = builder.build()?;
+        assert_eq!(draft.draft_id, "d-1");
+        assert_eq!(draft.event_id, "PremierDraft_MKM");
+        assert_eq!(draft.kind, DraftKind::PlayerDraft);
+        assert_eq!(draft.picks[0].cards_offered, vec![7, 8, 9]);
+        assert_eq!(draft.pool, vec![8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sealed_pool() -> Result<()> {
+        let mut builder = DraftReplayBuilder::new();
+        let join = response(FrontDoorResponsePayload::EventJoin(CourseResponse {
+            course: Some(Course {
+                course_id: Some("c-1".to_string()),
+                internal_event_name: "Sealed_MKM".to_string(),
+                card_pool: vec![1, 2, 3],
+                ..Course::default()
+            }),
+            ..CourseResponse::default()
+        }));
+        assert!(builder.ingest_front_door_event(&join, None));
+        let draft = builder.build()?;
+        assert_eq!(draft.kind, DraftKind::Sealed);
+        assert_eq!(draft.draft_id, "c-1");
+        assert!(draft.picks.is_empty());
+        assert_eq!(draft.pool, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_completed_bot_draft_status_without_draft() {
+        let mut builder = DraftReplayBuilder::new();
+        let status = bot_status(0, vec![], vec![1, 2], "Completed");
+        assert!(!builder.ingest_front_door_event(&status, Some(Utc::now())));
+        assert!(builder.is_idle());
+    }
+
+    #[test]
+    fn test_bot_draft_id() {
+        let courses = response(FrontDoorResponsePayload::EventGetCourses(
+            EventGetCoursesResponse {
+                courses: vec![Course {
+                    course_id: Some("c-1".to_string()),
+                    internal_event_name: "QuickDraft_MKM".to_string(),
+                    ..Course::default()
+                }],
+            },
+        ));
+        let first_pick = bot_status(0, vec![1, 2, 3], vec![], "PickNext");
+        let draft_id = |events: &[&FrontDoorEvent], observed_at| {
+            let mut builder = DraftReplayBuilder::new();
+            for event in events {
+                builder.ingest_front_door_event(event, observed_at);
+            }
+            builder.draft_id
+        };
+        let observed_at = DateTime::from_timestamp(1_714_564_800, 0);
+        assert_eq!(
+            draft_id(&[&courses, &first_pick], observed_at),
+            Some("QuickDraft_MKM_c-1".to_string())
+        );
+        assert_eq!(
+            draft_id(&[&courses, &first_pick], None),
+            Some("QuickDraft_MKM_c-1".to_string())
+        );
+        assert_eq!(
+            draft_id(&[&first_pick], observed_at),
+            Some("QuickDraft_MKM_1714564800".to_string())
+        );
+        assert_eq!(draft_id(&[&first_pick], None), None);
+    }
+
+    #[test]
+    fn test_lobby_kept_across_drafts() -> Result<()> {
+        let courses = response(FrontDoorResponsePayload::EventGetCourses(
+            EventGetCoursesResponse {
+                courses: vec![Course {
+                    course_id: Some("c-1".to_string()),
+                    internal_event_name: "QuickDraft_MKM".to_string(),
+                    ..Course::default()
+                }],
+            },
+        ));
+        let observed_at = DateTime::from_timestamp(1_714_564_800, 0);
+        let mut builder = DraftReplayBuilder::new();
+        builder.ingest_front_door_event(&courses, observed_at);
+        builder
+            .ingest_front_door_event(&bot_status(0, vec![1, 2], vec![], "PickNext"), observed_at);
+        assert!(builder
+            .ingest_front_door_event(&bot_status(1, vec![], vec![1], "Completed"), observed_at));
+        assert_eq!(builder.build()?.draft_id, "QuickDraft_MKM_c-1");
+        assert!(builder.is_idle());
+
+        // e.g. a resumed run, starting from a checkpoint taken between the drafts
+        let mut resumed = DraftReplayBuilder::with_lobby(builder.lobby().clone());
+        let first_pick = bot_status(0, vec![3, 4], vec![], "PickNext");
+        for builder in [&mut builder, &mut resumed] {
+            builder.ingest_front_door_event(&first_pick, observed_at);
+            assert_eq!(builder.draft_id.as_deref(), Some("QuickDraft_MKM_c-1"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_without_draft() {
+        assert!(DraftReplayBuilder::new().build().is_err());
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+
+use tracing::warn;
+
+use crate::models::deck::Deck;
+use crate::mtga_events::client::{ClientMessage, MulliganOption};
+use crate::mtga_events::gre::{GREToClientMessage, GameObjectType, GameStateMessage};
+use crate::mtga_events::primitives::{PlayerDieRoll, ResultListEntry, ZoneType};
+use crate::replay::{MatchReplay, MatchReplayEvent};
+
+const DEFAULT_HAND_SIZE: i32 = 7;
+const PENDING_MULLIGAN: &str = "ClientMessageType_MulliganResp";
+
+/// One game of a match, with the events from its start up to the `IntermissionReq` that ends it
+#[derive(Debug, Clone)]
+pub struct GameReplay<'a> {
+    pub game_number: i32,
+    /// seat that takes the first turn
+    pub starting_player: Option<i32>,
+    pub die_rolls: Vec<PlayerDieRoll>,
+    /// the deck submitted for this game, `ConnectResp` for the first one and `SubmitDeckResp` after
+    pub deck: Option<Deck>,
+    /// the controller's hands, in the order they were offered
+    pub mulligans: Vec<Mulligan>,
+    pub events: Vec<&'a MatchReplayEvent>,
+    /// winner and reason, from the `IntermissionReq` or else the final match result
+    pub result: Option<ResultListEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mulligan {
+    /// grp ids
+    pub hand: Vec<i32>,
+    pub number_to_keep: i32,
+    /// None if the match ended before a decision was made
+    pub decision: Option<MulliganOption>,
+}
+
+impl GameReplay<'_> {
+    fn gre_messages_iter(&self) -> impl Iterator<Item = &GREToClientMessage> {
+        self.events
+            .iter()
+            .filter_map(|mre| match mre {
+                MatchReplayEvent::GRE(message) => Some(message),
+                _ => None,
+            })
+            .flat_map(|gre| &gre.gre_to_client_event.gre_to_client_messages)
+    }
+
+    pub(crate) fn game_state_messages_iter(&self) -> impl Iterator<Item = &GameStateMessage> {
+        self.gre_messages_iter()
+            .filter_map(|gre_message| match gre_message {
+                GREToClientMessage::GameStateMessage(wrapper) => Some(&wrapper.game_state_message),
+                _ => None,
+            })
+    }
+
+    fn find_starting_player(&self) -> Option<i32> {
+        // while both players are choosing whether to mulligan, the decision player is the one on the play
+        self.game_state_messages_iter()
+            .find(|gsm| {
+                gsm.players.len() == 2
+                    && gsm.players.iter().all(|player| {
+                        player.pending_message_type.as_deref() == Some(PENDING_MULLIGAN)
+                    })
+                    && gsm
+                        .turn_info
+                        .as_ref()
+                        .is_some_and(|turn_info| turn_info.decision_player.is_some())
+            })
+            .and_then(|gsm| gsm.turn_info.as_ref()?.decision_player)
+            .or_else(|| {
+                self.game_state_messages_iter()
+                    .filter_map(|gsm| gsm.turn_info.as_ref())
+                    .find(|turn_info| turn_info.turn_number == Some(1))
+                    .and_then(|turn_info| turn_info.active_player)
+            })
+    }
+
+    fn find_die_rolls(&self) -> Vec<PlayerDieRoll> {
+        self.gre_messages_iter()
+            .find_map(|gre_message| match gre_message {
+                GREToClientMessage::DieRollResults(wrapper) => {
+                    Some(wrapper.die_roll_results_resp.player_die_rolls.clone())
+                }
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn find_deck(&self) -> Option<Deck> {
+        let submitted_deck = self.events.iter().rev().find_map(|mre| match mre {
+            MatchReplayEvent::Client(message) => match &message.payload {
+                ClientMessage::SubmitDeckResp(wrapper) => Some(&wrapper.submit_deck_resp.deck),
+                _ => None,
+            },
+            _ => None,
+        });
+        let deck_message = submitted_deck.or_else(|| {
+            self.gre_messages_iter()
+                .find_map(|gre_message| match gre_message {
+                    GREToClientMessage::ConnectResp(wrapper) => {
+                        Some(&wrapper.connect_resp.deck_message)
+                    }
+                    _ => None,
+                })
+        })?;
+        let mut deck = Deck::from(deck_message);
+        deck.game_number = self.game_number;
+        Some(deck)
+    }
+
+    fn find_mulligans(&self, controller_seat_id: i32) -> Vec<Mulligan> {
+        let hands: Vec<Vec<i32>> = self
+            .game_state_messages_iter()
+            .filter(|gsm| {
+                gsm.players.iter().any(|player| {
+                    player.controller_seat_id == controller_seat_id
+                        && player.pending_message_type.as_deref() == Some(PENDING_MULLIGAN)
+                })
+            })
+            .filter_map(|gsm| {
+                let Some(hand_zone) = gsm.zones.iter().find(|zone| {
+                    zone.type_field == ZoneType::Hand
+                        && zone.owner_seat_id == Some(controller_seat_id)
+                }) else {
+                    warn!(
+                        "Controller hand zone not found in game {}",
+                        self.game_number
+                    );
+                    return None;
+                };
+                Some(
+                    gsm.game_objects
+                        .iter()
+                        .filter(|go| {
+                            go.zone_id == Some(hand_zone.zone_id)
+                                && go.type_field == GameObjectType::Card
+                        })
+                        .map(|go| go.grp_id)
+                        .collect(),
+                )
+            })
+            .collect();
+        let mulligan_requests: Vec<_> = self
+            .gre_messages_iter()
+            .filter_map(|gre_message| match gre_message {
+                GREToClientMessage::MulliganReq(wrapper) => Some(wrapper),
+                _ => None,
+            })
+            .collect();
+        let decisions: BTreeMap<i32, MulliganOption> = self
+            .events
+            .iter()
+            .filter_map(|mre| match mre {
+                MatchReplayEvent::Client(message) => match &message.payload {
+                    ClientMessage::MulliganResp(wrapper) => {
+                        Some((wrapper.meta.game_state_id?, wrapper.mulligan_resp.decision))
+                    }
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        // each hand is offered with a mulligan request, anything else means lost data
+        if hands.len() != mulligan_requests.len() {
+            warn!(
+                "{} hands but {} mulligan requests in game {}",
+                hands.len(),
+                mulligan_requests.len(),
+                self.game_number
+            );
+        }
+
+        hands
+            .into_iter()
+            .zip(mulligan_requests)
+            .map(|(hand, mulligan_request)| Mulligan {
+                hand,
+                number_to_keep: DEFAULT_HAND_SIZE - mulligan_request.mulligan_req.mulligan_count,
+                decision: mulligan_request
+                    .meta
+                    .game_state_id
+                    .and_then(|game_state_id| decisions.get(&game_state_id).copied()),
+            })
+            .collect()
+    }
+
+    fn find_result(&self) -> Option<ResultListEntry> {
+        self.gre_messages_iter()
+            .find_map(|gre_message| match gre_message {
+                GREToClientMessage::IntermissionReq(wrapper) => {
+                    Some(wrapper.intermission_req.result.clone())
+                }
+                _ => None,
+            })
+    }
+}
+
+impl MatchReplay {
+    /// The match split into games at each `IntermissionReq`
+    pub fn games(&self) -> Vec<GameReplay<'_>> {
+        let controller_seat_id = self.get_controller_seat_id().ok();
+        let final_game_results: Vec<_> = self
+            .get_final_match_result()
+            .map(|final_match_result| {
+                final_match_result
+                    .result_list
+                    .into_iter()
+                    .filter(|result| result.scope == "MatchScope_Game")
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut segments = vec![Vec::new()];
+        for mre in &self.client_server_messages {
+            if let Some(segment) = segments.last_mut() {
+                segment.push(mre);
+            }
+            let ends_game = matches!(mre, MatchReplayEvent::GRE(gre) if gre
+                .gre_to_client_event
+                .gre_to_client_messages
+                .iter()
+                .any(|gre_message| matches!(gre_message, GREToClientMessage::IntermissionReq(_))));
+            if ends_game {
+                segments.push(Vec::new());
+            }
+        }
+
+        let mut games = Vec::new();
+        for events in segments {
+            let mut game = GameReplay {
+                game_number: 0,
+                starting_player: None,
+                die_rolls: Vec::new(),
+                deck: None,
+                mulligans: Vec::new(),
+                events,
+                result: None,
+            };
+            // events after the last game, or a segment with nothing but lobby chatter
+            let Some(first_state) = game.game_state_messages_iter().next() else {
+                continue;
+            };
+            let previous_game_number = games.last().map_or(0, |game: &GameReplay| game.game_number);
+            game.game_number = first_state
+                .game_info
+                .as_ref()
+                .map_or(previous_game_number + 1, |game_info| game_info.game_number);
+            game.starting_player = game.find_starting_player();
+            game.die_rolls = game.find_die_rolls();
+            game.deck = game.find_deck();
+            if let Some(controller_seat_id) = controller_seat_id {
+                game.mulligans = game.find_mulligans(controller_seat_id);
+            }
+            game.result = game.find_result().or_else(|| {
+                usize::try_from(game.game_number - 1)
+                    .ok()
+                    .and_then(|i| final_game_results.get(i).cloned())
+            });
+            games.push(game);
+        }
+        games
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::gre::{
+        ConnectResp, ConnectRespWrapper, DeckMessage, GREToClientEvent, GameInfo,
+        GameStateMessageWrapper, IntermissionReq, IntermissionReqWrapper,
+        RequestTypeGREToClientEvent,
+    };
+    use crate::mtga_events::primitives::TurnInfo;
+
+    fn gre_event(gre_to_client_messages: Vec<GREToClientMessage>) -> MatchReplayEvent {
+        MatchReplayEvent::GRE(RequestTypeGREToClientEvent {
+            gre_to_client_event: GREToClientEvent {
+                gre_to_client_messages,
+            },
+            ..RequestTypeGREToClientEvent::default()
+        })
+    }
+
+    fn first_turn(game_number: i32, active_player: i32) -> GREToClientMessage {
+        GREToClientMessage::GameStateMessage(GameStateMessageWrapper {
+            game_state_message: GameStateMessage {
+                game_info: Some(GameInfo {
+                    game_number,
+                    ..GameInfo::default()
+                }),
+                turn_info: Some(TurnInfo {
+                    active_player: Some(active_player),
+                    turn_number: Some(1),
+                    ..TurnInfo::default()
+                }),
+                ..GameStateMessage::default()
+            },
+            ..GameStateMessageWrapper::default()
+        })
+    }
+
+    fn game_over(winning_team_id: i32) -> GREToClientMessage {
+        GREToClientMessage::IntermissionReq(IntermissionReqWrapper {
+            intermission_req: IntermissionReq {
+                result: ResultListEntry {
+                    scope: "MatchScope_Game".to_string(),
+                    winning_team_id,
+                    reason: Some("ResultReason_Concede".to_string()),
+                    result: None,
+                },
+                ..IntermissionReq::default()
+            },
+            ..IntermissionReqWrapper::default()
+        })
+    }
+
+    #[test]
+    fn test_games() {
+        let connect_resp = GREToClientMessage::ConnectResp(ConnectRespWrapper {
+            connect_resp: ConnectResp {
+                deck_message: DeckMessage {
+                    deck_cards: vec![1, 2, 3],
+                    sideboard_cards: vec![4],
+                },
+                ..ConnectResp::default()
+            },
+            ..ConnectRespWrapper::default()
+        });
+        let match_replay = MatchReplay {
+            client_server_messages: vec![
+                gre_event(vec![connect_resp]),
+                gre_event(vec![first_turn(1, 1)]),
+                gre_event(vec![game_over(2)]),
+                gre_event(vec![first_turn(2, 1)]),
+                gre_event(vec![game_over(1)]),
+            ],
+            ..MatchReplay::default()
+        };
+
+        let games = match_replay.games();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].game_number, 1);
+        assert_eq!(games[0].events.len(), 3);
+        assert_eq!(games[0].starting_player, Some(1));
+        assert_eq!(
+            games[0].deck.as_ref().map(|deck| deck.mainboard.clone()),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            games[0]
+                .result
+                .as_ref()
+                .map(|result| result.winning_team_id),
+            Some(2)
+        );
+        assert_eq!(games[1].game_number, 2);
+        assert!(games[1].deck.is_none());
+        assert_eq!(
+            games[1]
+                .result
+                .as_ref()
+                .map(|result| result.winning_team_id),
+            Some(1)
+        );
+    }
+}
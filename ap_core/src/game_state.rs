@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::mtga_events::gre::{GameInfo, GameObject, GameStateMessage};
+use crate::mtga_events::primitives::{Annotation, Player, TurnInfo, Zone, ZoneType};
+
+//
+// The GRE only sends the whole board once in a while (`GameStateType_Full`), every other
+// `GameStateMessage` is a diff against an earlier state (`prev_game_state_id`) that carries
+// the zones, objects and players that changed, plus the ids of objects and persistent
+// annotations that went away
+//
+
+const FULL_GAME_STATE: &str = "GameStateType_Full";
+
+/// The complete board as of one `game_state_id`
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct GameState {
+    pub game_state_id: i32,
+    pub game_info: Option<GameInfo>,
+    pub turn_info: Option<TurnInfo>,
+    /// keyed by `system_seat_number`
+    pub players: BTreeMap<i32, Player>,
+    /// keyed by `zone_id`
+    pub zones: BTreeMap<i32, Zone>,
+    /// keyed by `instance_id`
+    pub game_objects: BTreeMap<i32, GameObject>,
+    /// keyed by annotation id
+    pub persistent_annotations: BTreeMap<i32, Annotation>,
+}
+
+impl GameState {
+    fn apply(&mut self, gsm: &GameStateMessage) {
+        self.game_state_id = gsm.game_state_id;
+        if let Some(game_info) = &gsm.game_info {
+            self.game_info = Some(game_info.clone());
+        }
+        if let Some(turn_info) = &gsm.turn_info {
+            self.turn_info = Some(turn_info.clone());
+        }
+        for player in &gsm.players {
+            self.players
+                .insert(player.system_seat_number, player.clone());
+        }
+        for zone in &gsm.zones {
+            self.zones.insert(zone.zone_id, zone.clone());
+        }
+        for instance_id in &gsm.diff_deleted_instance_ids {
+            self.game_objects.remove(instance_id);
+        }
+        for game_object in &gsm.game_objects {
+            self.game_objects
+                .insert(game_object.instance_id, game_object.clone());
+        }
+        for annotation_id in &gsm.diff_deleted_persistent_annotation_ids {
+            self.persistent_annotations.remove(annotation_id);
+        }
+        for annotation in &gsm.persistent_annotations {
+            self.persistent_annotations
+                .insert(annotation.id, annotation.clone());
+        }
+    }
+
+    pub fn game_number(&self) -> Option<i32> {
+        self.game_info
+            .as_ref()
+            .map(|game_info| game_info.game_number)
+    }
+
+    pub fn turn_number(&self) -> Option<i32> {
+        self.turn_info
+            .as_ref()
+            .and_then(|turn_info| turn_info.turn_number)
+    }
+
+    pub fn life_total(&self, seat_id: i32) -> Option<i32> {
+        self.players.get(&seat_id).map(|player| player.life_total)
+    }
+
+    pub fn zone(&self, zone_type: ZoneType, owner_seat_id: Option<i32>) -> Option<&Zone> {
+        self.zones
+            .values()
+            .find(|zone| zone.type_field == zone_type && zone.owner_seat_id == owner_seat_id)
+    }
+
+    /// Objects in a zone, in the order the zone lists them.
+    /// Hidden cards (e.g. the opponent's hand or any library) usually have no object
+    pub fn zone_objects(&self, zone: &Zone) -> Vec<&GameObject> {
+        zone.object_instance_ids
+            .iter()
+            .filter_map(|instance_id| self.game_objects.get(instance_id))
+            .collect()
+    }
+
+    /// Objects on the battlefield controlled by `seat_id`
+    pub fn battlefield(&self, seat_id: i32) -> Vec<&GameObject> {
+        self.zone(ZoneType::Battlefield, None)
+            .map(|zone| self.zone_objects(zone))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|game_object| {
+                game_object
+                    .controller_seat_id
+                    .unwrap_or(game_object.owner_seat_id)
+                    == seat_id
+            })
+            .collect()
+    }
+}
+
+/// Full copies of the board are kept this many states apart, any other earlier state is
+/// rebuilt from the closest copy and the diffs applied since
+const SNAPSHOT_INTERVAL: usize = 32;
+
+/// A message as it was applied, `base` is the state it was applied on (none for full states)
+#[derive(Debug)]
+struct AppliedMessage {
+    gsm: GameStateMessage,
+    base: Option<i32>,
+}
+
+/// Materializes the board from a game's `GameStateMessage`s. Only the latest state is kept
+/// whole, earlier ones can be looked up by `game_state_id` and are rebuilt from the diffs.
+/// A full state for a different game number starts the history over
+#[derive(Debug, Default)]
+pub struct GameStateTracker {
+    current: Option<GameState>,
+    /// keyed by `game_state_id`
+    applied: BTreeMap<i32, AppliedMessage>,
+    /// every `SNAPSHOT_INTERVAL`th state, keyed by `game_state_id`
+    snapshots: BTreeMap<i32, GameState>,
+    applied_since_snapshot: usize,
+}
+
+impl GameStateTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a message on top of the state it is a diff against, and returns the new state
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the message is a diff and no earlier state has been seen
+    pub fn apply(&mut self, gsm: &GameStateMessage) -> Result<&GameState> {
+        let (mut state, base) = if gsm.type_field == FULL_GAME_STATE {
+            let game_number = gsm
+                .game_info
+                .as_ref()
+                .map(|game_info| game_info.game_number);
+            if game_number.is_some()
+                && self.current().and_then(GameState::game_number) != game_number
+            {
+                *self = Self::default();
+            }
+            (GameState::default(), None)
+        } else {
+            let base = self.diff_base(gsm)?;
+            let state = if self.current().map(|state| state.game_state_id) == Some(base) {
+                self.current.take().unwrap_or_default()
+            } else {
+                self.get(base)
+                    .ok_or(anyhow!("could not rebuild game state {base}"))?
+            };
+            (state, Some(base))
+        };
+        state.apply(gsm);
+        self.applied.insert(
+            gsm.game_state_id,
+            AppliedMessage {
+                gsm: gsm.clone(),
+                base,
+            },
+        );
+        self.applied_since_snapshot += 1;
+        if self.applied_since_snapshot == SNAPSHOT_INTERVAL {
+            self.applied_since_snapshot = 0;
+            self.snapshots.insert(gsm.game_state_id, state.clone());
+        }
+        Ok(self.current.insert(state))
+    }
+
+    fn diff_base(&self, gsm: &GameStateMessage) -> Result<i32> {
+        if let Some(prev_game_state_id) = gsm.prev_game_state_id {
+            if self.contains(prev_game_state_id) {
+                return Ok(prev_game_state_id);
+            }
+            warn!(
+                "game state {} is a diff against unknown state {}, applying it to the latest one",
+                gsm.game_state_id, prev_game_state_id
+            );
+        }
+        self.current()
+            .map(|state| state.game_state_id)
+            .ok_or(anyhow!(
+                "no game state to apply diff {} to",
+                gsm.game_state_id
+            ))
+    }
+
+    /// The most recently applied state
+    pub fn current(&self) -> Option<&GameState> {
+        self.current.as_ref()
+    }
+
+    /// Whether a state of the current game has been applied
+    pub fn contains(&self, game_state_id: i32) -> bool {
+        self.applied.contains_key(&game_state_id)
+    }
+
+    /// Rebuilds an earlier state of the current game from the closest snapshot
+    pub fn get(&self, game_state_id: i32) -> Option<GameState> {
+        let mut diffs = Vec::new();
+        let mut id = game_state_id;
+        let mut state = loop {
+            if let Some(state) = self.current().filter(|state| state.game_state_id == id) {
+                break state.clone();
+            }
+            if let Some(state) = self.snapshots.get(&id) {
+                break state.clone();
+            }
+            // a state sent again as a diff against a later one would loop forever
+            if diffs.len() > self.applied.len() {
+                return None;
+            }
+            let applied = self.applied.get(&id)?;
+            diffs.push(&applied.gsm);
+            match applied.base {
+                Some(base) => id = base,
+                None => break GameState::default(),
+            }
+        };
+        for gsm in diffs.into_iter().rev() {
+            state.apply(gsm);
+        }
+        Some(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::primitives::Visibility;
+
+    fn zone(zone_id: i32, type_field: ZoneType, object_instance_ids: Vec<i32>) -> Zone {
+        Zone {
+            zone_id,
+            type_field,
+            object_instance_ids,
+            visibility: Visibility::Public,
+            ..Zone::default()
+        }
+    }
+
+    fn card(instance_id: i32, grp_id: i32, zone_id: i32) -> GameObject {
+        GameObject {
+            instance_id,
+            grp_id,
+            zone_id: Some(zone_id),
+            owner_seat_id: 1,
+            controller_seat_id: Some(1),
+            ..GameObject::default()
+        }
+    }
+
+    fn player(system_seat_number: i32, life_total: i32) -> Player {
+        Player {
+            system_seat_number,
+            controller_seat_id: system_seat_number,
+            life_total,
+            ..Player::default()
+        }
+    }
+
+    fn full_state() -> GameStateMessage {
+        GameStateMessage {
+            game_state_id: 1,
+            type_field: FULL_GAME_STATE.to_string(),
+            game_info: Some(GameInfo {
+                game_number: 1,
+                ..GameInfo::default()
+            }),
+            players: vec![player(1, 20), player(2, 20)],
+            zones: vec![
+                zone(28, ZoneType::Battlefield, vec![]),
+                zone(31, ZoneType::Hand, vec![100, 101]),
+            ],
+            game_objects: vec![card(100, 5000, 31), card(101, 5001, 31)],
+            ..GameStateMessage::default()
+        }
+    }
+
+    fn play_land() -> GameStateMessage {
+        GameStateMessage {
+            game_state_id: 2,
+            prev_game_state_id: Some(1),
+            type_field: "GameStateType_Diff".to_string(),
+            players: vec![player(2, 17)],
+            zones: vec![
+                zone(28, ZoneType::Battlefield, vec![102]),
+                zone(31, ZoneType::Hand, vec![101]),
+            ],
+            game_objects: vec![card(102, 5000, 28)],
+            diff_deleted_instance_ids: vec![100],
+            turn_info: Some(TurnInfo {
+                turn_number: Some(1),
+                ..TurnInfo::default()
+            }),
+            ..GameStateMessage::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_diff() -> Result<()> {
+        let mut tracker = GameStateTracker::new();
+        tracker.apply(&full_state())?;
+        let state = tracker.apply(&play_land())?;
+
+        assert_eq!(state.game_state_id, 2);
+        assert_eq!(state.turn_number(), Some(1));
+        assert_eq!(state.life_total(1), Some(20));
+        assert_eq!(state.life_total(2), Some(17));
+        let battlefield: Vec<_> = state.battlefield(1).iter().map(|go| go.grp_id).collect();
+        assert_eq!(battlefield, vec![5000]);
+        assert!(!state.game_objects.contains_key(&100));
+
+        // earlier states are left untouched
+        let first = tracker.get(1).ok_or(anyhow!("state 1 missing"))?;
+        assert_eq!(first.game_state_id, 1);
+        assert_eq!(first.life_total(2), Some(20));
+        assert_eq!(first.game_objects.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_without_base() {
+        let mut tracker = GameStateTracker::new();
+        assert!(tracker.apply(&play_land()).is_err());
+    }
+
+    #[test]
+    fn test_new_game_resets_history() -> Result<()> {
+        let mut tracker = GameStateTracker::new();
+        tracker.apply(&full_state())?;
+        tracker.apply(&play_land())?;
+        let mut next_game = full_state();
+        if let Some(game_info) = &mut next_game.game_info {
+            game_info.game_number = 2;
+        }
+        tracker.apply(&next_game)?;
+        assert!(tracker.contains(1));
+        assert!(!tracker.contains(2));
+        assert_eq!(tracker.current().and_then(GameState::game_number), Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_game() -> Result<()> {
+        // a long best-of-one sends a few thousand states
+        const STATES: i32 = 5000;
+        let mut tracker = GameStateTracker::new();
+        tracker.apply(&full_state())?;
+        for game_state_id in 2..=STATES {
+            let gsm = GameStateMessage {
+                game_state_id,
+                prev_game_state_id: Some(game_state_id - 1),
+                type_field: "GameStateType_Diff".to_string(),
+                players: vec![player(2, 20 - game_state_id % 20)],
+                game_objects: vec![card(1000 + game_state_id, game_state_id, 28)],
+                diff_deleted_instance_ids: vec![999 + game_state_id],
+                turn_info: Some(TurnInfo {
+                    turn_number: Some(game_state_id / 100),
+                    ..TurnInfo::default()
+                }),
+                ..GameStateMessage::default()
+            };
+            let state = tracker.apply(&gsm)?;
+            assert_eq!(state.game_state_id, game_state_id);
+        }
+
+        // only the latest state and one copy every few states are kept whole
+        assert_eq!(tracker.snapshots.len(), STATES as usize / SNAPSHOT_INTERVAL);
+        let current = tracker.current().ok_or(anyhow!("no current state"))?;
+        assert_eq!(current.turn_number(), Some(STATES / 100));
+        assert_eq!(current.life_total(2), Some(20));
+        assert_eq!(current.game_objects.len(), 3);
+        assert!(current.game_objects.contains_key(&(1000 + STATES)));
+
+        let earlier = tracker.get(1234).ok_or(anyhow!("state 1234 missing"))?;
+        assert_eq!(earlier.game_state_id, 1234);
+        assert_eq!(earlier.life_total(2), Some(6));
+        assert_eq!(earlier.turn_number(), Some(12));
+        assert!(earlier.game_objects.contains_key(&2234));
+        assert!(!earlier.game_objects.contains_key(&2233));
+
+        // a diff against an earlier state is applied to that state
+        let replay = GameStateMessage {
+            game_state_id: STATES + 1,
+            prev_game_state_id: Some(1234),
+            type_field: "GameStateType_Diff".to_string(),
+            ..GameStateMessage::default()
+        };
+        let state = tracker.apply(&replay)?;
+        assert_eq!(state.life_total(2), Some(6));
+        assert!(state.game_objects.contains_key(&2234));
+        Ok(())
+    }
+}
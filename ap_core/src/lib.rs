@@ -3,10 +3,17 @@
 #![deny(clippy::unwrap_used)]
 #![allow(clippy::module_name_repetitions)]
 #![allow(clippy::must_use_candidate)]
+pub mod adapters;
 pub mod cards;
+pub mod checkpoint;
+pub mod draft;
+pub mod game_replay;
+pub mod game_state;
+pub mod log_envelope;
 pub mod match_insights;
 pub mod models;
 pub mod mtga_events;
+pub mod narration;
 pub mod processor;
 pub mod replay;
 pub mod storage_backends;
@@ -0,0 +1,218 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+//
+// Player.log prefixes most json blobs with a header line (or two), e.g.
+//
+// `[UnityCrossThreadLogger]10/17/2026 8:15:02 PM: Match to XYZ: GreToClientEvent`
+// `[UnityCrossThreadLogger]==> EventJoin {"id":"...","request":"..."}`
+// `<== EventJoin(0f3c...)`
+//
+// `LogEnvelope` keeps what those headers tell us about the event that follows
+//
+
+// MTGA writes timestamps in the local machine's locale, so try the common ones
+const TIMESTAMP_FORMATS: [&str; 6] = [
+    "%m/%d/%Y %I:%M:%S %p",
+    "%m/%d/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y/%m/%d %H:%M:%S",
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// `==>` lines, the client calling an API method
+    Request,
+    /// `<==` lines, the server answering an API method
+    Response,
+    /// messages the server pushes without a request, e.g. `Draft.Notify`
+    Notification,
+    #[default]
+    None,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEnvelope {
+    /// e.g. `UnityCrossThreadLogger`, `Client GRE`
+    pub logger: Option<String>,
+    /// wall-clock time of the latest timestamp header seen before this event, in local time
+    pub timestamp: Option<NaiveDateTime>,
+    pub direction: Direction,
+    /// API method name for request/response events, e.g. `EventJoin`
+    pub method: Option<String>,
+    /// id in parentheses on response headers, matches the `id` of the request json
+    pub request_id: Option<String>,
+    /// byte offset into the log of the opening brace of the event
+    pub byte_offset: u64,
+    /// 1-based line number the event starts on
+    pub line_number: usize,
+}
+
+impl LogEnvelope {
+    /// Folds any header information found in `header` (the part of a log line that is not json)
+    /// into this envelope
+    pub fn merge_header(&mut self, header: &str) {
+        let mut rest = header.trim();
+        if let Some(tagged) = rest.strip_prefix('[') {
+            if let Some((logger, after)) = tagged.split_once(']') {
+                self.logger = Some(logger.to_string());
+                rest = after.trim();
+            }
+        }
+
+        if let Some(method) = rest.strip_prefix("==>") {
+            self.direction = Direction::Request;
+            self.method = method_name(method);
+            self.request_id = None;
+        } else if let Some(method) = rest.strip_prefix("<==") {
+            self.direction = Direction::Response;
+            self.method = method_name(method);
+            self.request_id = method
+                .split_once('(')
+                .and_then(|(_, id)| id.split_once(')'))
+                .map(|(id, _)| id.trim().to_string())
+                .filter(|id| !id.is_empty());
+        } else if let Some(timestamp) = parse_timestamp(rest) {
+            self.timestamp = Some(timestamp);
+        } else if is_notification_name(rest) {
+            self.direction = Direction::Notification;
+            self.method = Some(rest.to_string());
+            self.request_id = None;
+        }
+    }
+
+    /// Header state that only applies to a single event is dropped,
+    /// the timestamp is carried forward as the best guess for later events
+    #[must_use]
+    pub fn carry_forward(&self) -> Self {
+        Self {
+            timestamp: self.timestamp,
+            ..Self::default()
+        }
+    }
+
+    /// the header timestamp converted from the local time MTGA logs in
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+            .and_then(|timestamp| Local.from_local_datetime(&timestamp).earliest())
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+
+    pub fn is_request(&self) -> bool {
+        self.direction == Direction::Request
+    }
+
+    pub fn is_response(&self) -> bool {
+        self.direction == Direction::Response
+    }
+}
+
+fn method_name(text: &str) -> Option<String> {
+    let name: String = text
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// notifications are logged as a bare dotted name, e.g. `[UnityCrossThreadLogger]Draft.Notify {..}`
+fn is_notification_name(text: &str) -> bool {
+    text.contains('.')
+        && !text.starts_with('.')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_')
+}
+
+fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    // "10/17/2026 8:15:02 PM: Match to XYZ: GreToClientEvent", time parts never have ": "
+    let candidate = text.split(": ").next().unwrap_or(text).trim();
+    TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(candidate, format).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    fn datetime(h: u32, m: u32, s: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2026, 10, 17).and_then(|d| d.and_hms_opt(h, m, s))
+    }
+
+    #[test]
+    fn test_timestamp_header() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("[UnityCrossThreadLogger]10/17/2026 8:15:02 PM\n");
+        assert_eq!(envelope.logger.as_deref(), Some("UnityCrossThreadLogger"));
+        assert_eq!(envelope.timestamp, datetime(20, 15, 2));
+        assert_eq!(envelope.direction, Direction::None);
+    }
+
+    #[test]
+    fn test_timestamp_header_with_trailer() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header(
+            "[UnityCrossThreadLogger]17/10/2026 20:15:02: Match to ABC: GreToClientEvent",
+        );
+        assert_eq!(envelope.timestamp, datetime(20, 15, 2));
+    }
+
+    #[test]
+    fn test_request_header() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("[UnityCrossThreadLogger]==> EventJoin ");
+        assert_eq!(envelope.direction, Direction::Request);
+        assert_eq!(envelope.method.as_deref(), Some("EventJoin"));
+        assert!(envelope.is_request());
+    }
+
+    #[test]
+    fn test_response_header() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("<== EventJoin(4a0b21f3-7a52-4ef5-a0e3-2d55f4a3c1a1)");
+        assert_eq!(envelope.logger, None);
+        assert_eq!(envelope.direction, Direction::Response);
+        assert_eq!(envelope.method.as_deref(), Some("EventJoin"));
+        assert_eq!(
+            envelope.request_id.as_deref(),
+            Some("4a0b21f3-7a52-4ef5-a0e3-2d55f4a3c1a1")
+        );
+    }
+
+    #[test]
+    fn test_notification_header() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("[UnityCrossThreadLogger]Draft.Notify ");
+        assert_eq!(envelope.direction, Direction::Notification);
+        assert_eq!(envelope.method.as_deref(), Some("Draft.Notify"));
+    }
+
+    #[test]
+    fn test_carry_forward_keeps_timestamp_only() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("[UnityCrossThreadLogger]10/17/2026 8:15:02 PM");
+        envelope.merge_header("<== EventJoin(abc)");
+        envelope.byte_offset = 10;
+        let next = envelope.carry_forward();
+        assert_eq!(next.timestamp, datetime(20, 15, 2));
+        assert_eq!(next.method, None);
+        assert_eq!(next.direction, Direction::None);
+        assert_eq!(next.byte_offset, 0);
+    }
+
+    #[test]
+    fn test_noise_is_ignored() {
+        let mut envelope = LogEnvelope::default();
+        envelope.merge_header("Initialize engine version: 2022.3.42f1 (ab12)");
+        assert_eq!(envelope, LogEnvelope::default());
+    }
+}
@@ -1,25 +1,49 @@
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use include_dir::{include_dir, Dir};
-use lazy_static::lazy_static;
-use rusqlite::{Connection, Params as RusqliteParams, Result as RusqliteResult, Transaction};
+use rusqlite::{
+    params, Connection, Params as RusqliteParams, Result as RusqliteResult, Row, Transaction,
+};
 use rusqlite_migration::Migrations;
+use std::sync::LazyLock;
 use tracing::{debug, info};
 
 use crate::cards::CardsDatabase;
-use crate::models::deck::Deck;
-use crate::models::match_result::{MatchResult, MatchResultBuilder};
+use crate::draft::{DraftPick, DraftReplay};
+use crate::models::deck::{Deck, UNNAMED_DECK};
+use crate::models::deck_diff::{DeckDiff, SideboardPlan};
+use crate::models::deck_version::{DeckLineage, DeckVersion};
+use crate::models::match_result::{MatchResult, NO_WINNING_TEAM};
 use crate::models::mtga_match::{MTGAMatch, MTGAMatchBuilder};
 use crate::models::mulligan::MulliganInfo;
+use crate::models::rank::{MatchRanks, Rank, RankSnapshot};
+use crate::models::win_rate::{Streaks, WinRate, WinRateFilter};
 use crate::replay::MatchReplay;
-use crate::storage_backends::ArenaMatchStorageBackend;
+use crate::storage_backends::{ArenaDraftStorageBackend, ArenaMatchStorageBackend};
+
+//
+// Conflict semantics, per table:
+// - matches and drafts are upserted, a rewrite overwrites every column
+// - decks, deck_diffs, mulligans and match_results belong to a match, and draft_picks to a draft,
+//   so they are deleted and rewritten together with their parent in one transaction;
+//   a reprocessed match that yields fewer games or hands leaves no stale rows behind
+// - rank_snapshots are observations that never change, so a snapshot already stored with the
+//   same time and ranks is kept as is
+// - match_ranks link a match to the closest snapshots before it started and after it ended;
+//   they are relinked whenever the match, or a snapshot that could be closer, is written
+// - deck_versions are shared by every match played with the same decklist, so they are
+//   never deleted; a rewrite only fills in a name or deck id that was missing
+//
 
 static MIGRATIONS_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/migrations");
 
-lazy_static! {
-    static ref MIGRATIONS: Migrations<'static> =
-        Migrations::from_directory(&MIGRATIONS_DIR).unwrap_or(Migrations::new(Vec::new()));
-}
+/// Stored alongside every extracted row so rows written by an older extractor can be found
+/// and reprocessed
+pub const PROCESSED_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+static MIGRATIONS: LazyLock<Migrations<'static>> = LazyLock::new(|| {
+    Migrations::from_directory(&MIGRATIONS_DIR).unwrap_or(Migrations::new(Vec::new()))
+});
 
 #[derive(Debug)]
 pub struct MatchInsightDB {
@@ -61,11 +85,20 @@ impl MatchInsightDB {
             &mtga_match.controller_player_name,
             &mtga_match.opponent_player_name,
             &mtga_match.created_at,
+            &mtga_match.format,
+            &mtga_match.opponent_identity,
+            PROCESSED_VERSION,
+            &mtga_match.ended_at,
         );
 
         let sql = "INSERT INTO matches \
-            (id, controller_seat_id, controller_player_name, opponent_player_name, created_at)\
-            VALUES (?1, ?2, ?3, ?4, ?5) ON CONFLICT(id) DO NOTHING";
+            (id, controller_seat_id, controller_player_name, opponent_player_name, created_at, format, opponent_identity, processed_version, ended_at) \
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+            ON CONFLICT (id) \
+            DO UPDATE SET controller_seat_id = excluded.controller_seat_id, controller_player_name = excluded.controller_player_name, \
+                opponent_player_name = excluded.opponent_player_name, created_at = excluded.created_at, format = excluded.format, \
+                opponent_identity = excluded.opponent_identity, processed_version = excluded.processed_version, \
+                ended_at = excluded.ended_at";
         tx.execute(sql, params)?;
         Ok(())
     }
@@ -79,11 +112,85 @@ impl MatchInsightDB {
 
         tx.execute(
             "INSERT INTO decks
-                    (match_id, game_number, deck_cards, sideboard_cards)
-                    VALUES (?1, ?2, ?3, ?4)
+                    (match_id, game_number, deck_cards, sideboard_cards, fingerprint, processed_version)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                    ON CONFLICT (match_id, game_number)
+                    DO UPDATE SET deck_cards = excluded.deck_cards, sideboard_cards = excluded.sideboard_cards, fingerprint = excluded.fingerprint, processed_version = excluded.processed_version",
+            (match_id, deck.game_number, deck_string, sideboard_string, deck.fingerprint(), PROCESSED_VERSION)
+        )?;
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn insert_deck_diff(
+        match_id: &str,
+        game_number: i32,
+        deck_diff: &DeckDiff,
+        tx: &Transaction,
+    ) -> Result<()> {
+        tx.execute(
+            "INSERT INTO deck_diffs (match_id, game_number, cards_in, cards_out, processed_version)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
                     ON CONFLICT (match_id, game_number)
-                    DO UPDATE SET deck_cards = excluded.deck_cards, sideboard_cards = excluded.sideboard_cards",
-            (match_id, deck.game_number, deck_string, sideboard_string)
+                    DO UPDATE SET cards_in = excluded.cards_in, cards_out = excluded.cards_out, processed_version = excluded.processed_version",
+            (
+                match_id,
+                game_number,
+                serde_json::to_string(&deck_diff.cards_in)?,
+                serde_json::to_string(&deck_diff.cards_out)?,
+                PROCESSED_VERSION,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Records a decklist as a version of the deck it was most likely edited from,
+    /// or as the first version of a new deck
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn register_deck_version(
+        deck: &Deck,
+        deck_id: Option<&str>,
+        name: Option<&str>,
+        seen_at: DateTime<Utc>,
+        tx: &Transaction,
+    ) -> Result<()> {
+        let fingerprint = deck.fingerprint();
+        let versions = query_deck_versions(tx)?;
+        let (lineage_id, parent_fingerprint) = if versions
+            .iter()
+            .any(|version| version.fingerprint == fingerprint)
+        {
+            // only deck_id and name are updated below
+            (fingerprint.clone(), None)
+        } else {
+            match DeckVersion::find_parent(deck, deck_id, &versions) {
+                Some(parent) => (parent.lineage_id.clone(), Some(parent.fingerprint.clone())),
+                None => (fingerprint.clone(), None),
+            }
+        };
+
+        tx.execute(
+            "INSERT INTO deck_versions
+                    (fingerprint, deck_id, name, lineage_id, parent_fingerprint, deck_cards, sideboard_cards, first_seen_at, processed_version)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                    ON CONFLICT (fingerprint)
+                    DO UPDATE SET deck_id = COALESCE(excluded.deck_id, deck_id), name = COALESCE(excluded.name, name), processed_version = excluded.processed_version",
+            params![
+                fingerprint,
+                deck_id,
+                name,
+                lineage_id,
+                parent_fingerprint,
+                serde_json::to_string(&deck.mainboard)?,
+                serde_json::to_string(&deck.sideboard)?,
+                seen_at,
+                PROCESSED_VERSION
+            ],
         )?;
         Ok(())
     }
@@ -93,10 +200,11 @@ impl MatchInsightDB {
     /// will return an error if the database cannot be contacted for some reason
     fn insert_mulligan_info(mulligan_info: MulliganInfo, tx: &Transaction) -> Result<()> {
         tx.execute(
-            "INSERT INTO mulligans (match_id, game_number, number_to_keep, hand, play_draw, opponent_identity, decision)\
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)\
+            "INSERT INTO mulligans (match_id, game_number, number_to_keep, hand, play_draw, opponent_identity, decision, processed_version)\
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)\
              ON CONFLICT (match_id, game_number, number_to_keep) \
-             DO UPDATE SET hand = excluded.hand, play_draw = excluded.play_draw, opponent_identity = excluded.opponent_identity, decision = excluded.decision",
+             DO UPDATE SET hand = excluded.hand, play_draw = excluded.play_draw, opponent_identity = excluded.opponent_identity, \
+                decision = excluded.decision, processed_version = excluded.processed_version",
             (
                 mulligan_info.match_id,
                 mulligan_info.game_number,
@@ -105,6 +213,7 @@ impl MatchInsightDB {
                 mulligan_info.play_draw,
                 mulligan_info.opponent_identity,
                 mulligan_info.decision,
+                PROCESSED_VERSION,
             ),
         )?;
         Ok(())
@@ -119,16 +228,293 @@ impl MatchInsightDB {
             &match_result.game_number,
             &match_result.winning_team_id,
             &match_result.result_scope,
+            PROCESSED_VERSION,
         );
 
-        let sql = "INSERT INTO match_results (match_id, game_number, winning_team_id, result_scope)\
-             VALUES (?1, ?2, ?3, ?4)\
+        let sql = "INSERT INTO match_results (match_id, game_number, winning_team_id, result_scope, processed_version)\
+             VALUES (?1, ?2, ?3, ?4, ?5)\
              ON CONFLICT (match_id, game_number)\
-             DO UPDATE SET winning_team_id = excluded.winning_team_id, result_scope = excluded.result_scope";
+             DO UPDATE SET winning_team_id = excluded.winning_team_id, result_scope = excluded.result_scope, processed_version = excluded.processed_version";
         tx.execute(sql, params)?;
         Ok(())
     }
 
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn insert_rank_snapshot(rank_snapshot: &RankSnapshot, tx: &Transaction) -> Result<()> {
+        let constructed = &rank_snapshot.constructed;
+        let limited = &rank_snapshot.limited;
+        tx.execute(
+            "INSERT INTO rank_snapshots (observed_at, \
+                constructed_season_ordinal, constructed_class, constructed_tier, constructed_step, \
+                constructed_matches_won, constructed_matches_lost, constructed_matches_drawn, \
+                constructed_percentile, constructed_placement, \
+                limited_season_ordinal, limited_class, limited_tier, limited_step, \
+                limited_matches_won, limited_matches_lost, limited_matches_drawn, \
+                limited_percentile, limited_placement) \
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19 \
+             WHERE NOT EXISTS (SELECT 1 FROM rank_snapshots WHERE observed_at = ?1 \
+                AND constructed_season_ordinal IS ?2 AND constructed_class IS ?3 \
+                AND constructed_tier IS ?4 AND constructed_step IS ?5 \
+                AND constructed_matches_won IS ?6 AND constructed_matches_lost IS ?7 \
+                AND constructed_matches_drawn IS ?8 AND constructed_percentile IS ?9 \
+                AND constructed_placement IS ?10 \
+                AND limited_season_ordinal IS ?11 AND limited_class IS ?12 \
+                AND limited_tier IS ?13 AND limited_step IS ?14 \
+                AND limited_matches_won IS ?15 AND limited_matches_lost IS ?16 \
+                AND limited_matches_drawn IS ?17 AND limited_percentile IS ?18 \
+                AND limited_placement IS ?19)",
+            params![
+                rank_snapshot.observed_at,
+                constructed.season_ordinal,
+                constructed.class,
+                constructed.tier,
+                constructed.step,
+                constructed.matches_won,
+                constructed.matches_lost,
+                constructed.matches_drawn,
+                constructed.mythic_percentile,
+                constructed.mythic_placement,
+                limited.season_ordinal,
+                limited.class,
+                limited.tier,
+                limited.step,
+                limited.matches_won,
+                limited.matches_lost,
+                limited.matches_drawn,
+                limited.mythic_percentile,
+                limited.mythic_placement,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Links `match_id`, and every match ended since `since`, to the latest snapshot at or
+    /// before the match started and the earliest one after it ended
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn link_match_ranks(
+        match_id: &str,
+        since: Option<DateTime<Utc>>,
+        tx: &Transaction,
+    ) -> Result<()> {
+        for (relation, closest_snapshot) in [
+            (
+                "before",
+                "SELECT s.id FROM rank_snapshots s WHERE s.observed_at <= m.created_at \
+                 ORDER BY s.observed_at DESC, s.id DESC LIMIT 1",
+            ),
+            (
+                "after",
+                "SELECT s.id FROM rank_snapshots s \
+                 WHERE s.observed_at > COALESCE(m.ended_at, m.created_at) \
+                 ORDER BY s.observed_at, s.id LIMIT 1",
+            ),
+        ] {
+            tx.execute(
+                &format!(
+                    "INSERT INTO match_ranks (match_id, relation, rank_snapshot_id) \
+                     SELECT id, ?1, snapshot_id FROM ( \
+                        SELECT m.id, ({closest_snapshot}) AS snapshot_id FROM matches m \
+                        WHERE m.id = ?2 OR COALESCE(m.ended_at, m.created_at) >= ?3 \
+                     ) WHERE snapshot_id IS NOT NULL \
+                     ON CONFLICT (match_id, relation) \
+                     DO UPDATE SET rank_snapshot_id = excluded.rank_snapshot_id"
+                ),
+                params![relation, match_id, since],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn insert_draft(draft_replay: &DraftReplay, tx: &Transaction) -> Result<()> {
+        let pool_string = serde_json::to_string(&draft_replay.pool)?;
+        tx.execute(
+            "INSERT INTO drafts (id, event_id, kind, started_at, pool, processed_version) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6) \
+             ON CONFLICT (id) \
+             DO UPDATE SET event_id = excluded.event_id, kind = excluded.kind, started_at = excluded.started_at, pool = excluded.pool, \
+                processed_version = excluded.processed_version",
+            (
+                &draft_replay.draft_id,
+                &draft_replay.event_id,
+                draft_replay.kind.to_string(),
+                &draft_replay.started_at,
+                pool_string,
+                PROCESSED_VERSION,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn insert_draft_pick(draft_id: &str, draft_pick: &DraftPick, tx: &Transaction) -> Result<()> {
+        let cards_offered = serde_json::to_string(&draft_pick.cards_offered)?;
+        let cards_picked = serde_json::to_string(&draft_pick.cards_picked)?;
+        tx.execute(
+            "INSERT INTO draft_picks (draft_id, pack_number, pick_number, cards_offered, cards_picked, picked_at, processed_version) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7) \
+             ON CONFLICT (draft_id, pack_number, pick_number) \
+             DO UPDATE SET cards_offered = excluded.cards_offered, cards_picked = excluded.cards_picked, picked_at = excluded.picked_at, \
+                processed_version = excluded.processed_version",
+            (
+                draft_id,
+                draft_pick.pack_number,
+                draft_pick.pick_number,
+                cards_offered,
+                cards_picked,
+                &draft_pick.picked_at,
+                PROCESSED_VERSION,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn delete_match_children(match_id: &str, tx: &Transaction) -> Result<()> {
+        for table in [
+            "decks",
+            "deck_diffs",
+            "mulligans",
+            "match_results",
+            "match_ranks",
+        ] {
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE match_id = ?1"),
+                [match_id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    fn delete_draft_picks(draft_id: &str, tx: &Transaction) -> Result<()> {
+        tx.execute("DELETE FROM draft_picks WHERE draft_id = ?1", [draft_id])?;
+        Ok(())
+    }
+
+    /// Writes a match, replacing everything previously extracted for the same match id.
+    /// Either all rows of the match are replaced or none are
+    ///
+    /// # Errors
+    ///
+    /// will return an error if a `controller_seat_id` cannot be found
+    /// or if the match replay cannot be written to the database due to missing data
+    /// or connection error
+    pub fn replace_match(&mut self, match_replay: &MatchReplay) -> Result<()> {
+        let controller_seat_id = match_replay.get_controller_seat_id()?;
+        let match_id = &match_replay.match_id;
+        let (controller_name, opponent_name) = match_replay.get_player_names(controller_seat_id)?;
+        let event_start = match_replay.match_start_time().unwrap_or(Utc::now());
+
+        let mtga_match = MTGAMatchBuilder::default()
+            .id(match_id.clone())
+            .controller_seat_id(controller_seat_id)
+            .controller_player_name(controller_name)
+            .opponent_player_name(opponent_name)
+            .created_at(event_start)
+            .ended_at(match_replay.match_end_time())
+            .format(match_replay.match_format())
+            .opponent_identity(Some(
+                match_replay.get_opponent_color_identity(&self.cards_database)?,
+            ))
+            .build()?;
+        // extract everything up front so a bad replay fails before anything is deleted
+        let decklists = match_replay.get_decklists()?;
+        let deck_diffs = match_replay.get_deck_diffs()?;
+        let mulligan_infos = match_replay.get_mulligan_infos(&self.cards_database)?;
+        let match_results = match_replay.get_match_results()?;
+        debug!("{:?}", match_results);
+
+        let tx = self.conn.transaction()?;
+
+        Self::insert_match(&mtga_match, &tx)?;
+        Self::delete_match_children(match_id, &tx)?;
+
+        // named decks first, so the deck played is linked to the deck it was saved as
+        match_replay.named_decks.iter().try_for_each(|named_deck| {
+            Self::register_deck_version(
+                &named_deck.deck(),
+                Some(&named_deck.deck_id),
+                Some(&named_deck.name),
+                named_deck.observed_at,
+                &tx,
+            )
+        })?;
+        if let Some(deck) = decklists.first() {
+            let name = Some(deck.name.as_str()).filter(|name| *name != UNNAMED_DECK);
+            Self::register_deck_version(deck, None, name, event_start, &tx)?;
+        }
+
+        decklists
+            .iter()
+            .try_for_each(|deck| Self::insert_deck(match_id, deck, &tx))?;
+
+        deck_diffs.iter().try_for_each(|(game_number, deck_diff)| {
+            Self::insert_deck_diff(match_id, *game_number, deck_diff, &tx)
+        })?;
+
+        mulligan_infos
+            .into_iter()
+            .try_for_each(|mulligan_info| Self::insert_mulligan_info(mulligan_info, &tx))?;
+
+        // matches that ended after the snapshot preceding the new ones may now have a closer one
+        let since = match match_replay
+            .rank_snapshots
+            .iter()
+            .map(|rank_snapshot| rank_snapshot.observed_at)
+            .min()
+        {
+            Some(earliest) => Some(
+                tx.query_row(
+                    "SELECT MAX(observed_at) FROM rank_snapshots WHERE observed_at < ?1",
+                    [earliest],
+                    |row| row.get::<_, Option<DateTime<Utc>>>(0),
+                )?
+                .unwrap_or(DateTime::<Utc>::UNIX_EPOCH),
+            ),
+            None => None,
+        };
+        match_replay
+            .rank_snapshots
+            .iter()
+            .try_for_each(|rank_snapshot| Self::insert_rank_snapshot(rank_snapshot, &tx))?;
+        Self::link_match_ranks(match_id, since, &tx)?;
+
+        match_results
+            .iter()
+            .try_for_each(|match_result| Self::insert_match_result(match_result, &tx))?;
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The extractor version that last wrote a match, None if the match is unknown
+    /// or was written before versions were recorded
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_processed_version(&mut self, match_id: &str) -> Result<Option<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT processed_version FROM matches WHERE id = ?1")?;
+        let mut versions = stmt.query_map([match_id], |row| row.get(0))?;
+        Ok(versions.next().transpose()?.flatten())
+    }
+
     /// # Errors
     ///
     /// will return an error if the database cannot be contacted for some reason
@@ -157,17 +543,22 @@ impl MatchInsightDB {
     ///
     /// will return an error if the database cannot be contacted for some reason
     pub fn get_decklists(&mut self, match_id: &str) -> Result<Vec<Deck>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT game_number, deck_cards, sideboard_cards FROM decks WHERE match_id = ?1",
-        )?;
+        let sql = format!(
+            "SELECT d.game_number, d.deck_cards, d.sideboard_cards, {LINEAGE_NAME} FROM decks d \
+             LEFT JOIN decks first ON first.match_id = d.match_id AND first.game_number = 1 \
+             LEFT JOIN deck_versions v ON v.fingerprint = first.fingerprint \
+             WHERE d.match_id = ?1"
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
         let deck = stmt
             .query_map([match_id], |row| {
                 let game_number: i32 = row.get(0)?;
                 let deck_cards: String = row.get(1)?;
                 let sideboard_cards: String = row.get(2)?;
+                let name: Option<String> = row.get(3)?;
 
                 Ok(Deck::from_raw_decklist(
-                    "Found Deck".to_string(),
+                    name.unwrap_or(UNNAMED_DECK.to_string()),
                     game_number,
                     &deck_cards,
                     &sideboard_cards,
@@ -177,6 +568,90 @@ impl MatchInsightDB {
         Ok(deck)
     }
 
+    /// Sideboarding done before each game after the first, with the game it was done for
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_deck_diffs(&mut self, match_id: &str) -> Result<Vec<(i32, DeckDiff)>> {
+        let mut statement = self.conn.prepare(
+            "SELECT game_number, cards_in, cards_out FROM deck_diffs \
+             WHERE match_id = ?1 ORDER BY game_number",
+        )?;
+        let rows = statement
+            .query_map([match_id], |row| {
+                Ok((
+                    row.get::<_, i32>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<RusqliteResult<Vec<_>>>()?;
+        rows.into_iter()
+            .map(|(game_number, cards_in, cards_out)| {
+                Ok((game_number, deck_diff_from_json(&cards_in, &cards_out)?))
+            })
+            .collect()
+    }
+
+    /// How games 2 and 3 were sideboarded in the matches selected by `filter` that started
+    /// with the deck `fingerprint`, per opponent color identity, most common plan first
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_sideboard_plans(
+        &mut self,
+        fingerprint: &str,
+        filter: &WinRateFilter,
+    ) -> Result<Vec<SideboardPlan>> {
+        let sql = format!(
+            "SELECT {OPPONENT_IDENTITY} AS identity, \
+                s.cards_in, s.cards_out, COUNT(*), \
+                COALESCE(SUM(r.winning_team_id = m.controller_seat_id), 0), \
+                COALESCE(SUM(r.winning_team_id NOT IN ({NO_WINNING_TEAM}, m.controller_seat_id)), 0) \
+             FROM deck_diffs s \
+             JOIN matches m ON m.id = s.match_id \
+             JOIN decks d ON d.match_id = s.match_id AND d.game_number = 1 \
+             LEFT JOIN match_results r ON r.match_id = s.match_id AND r.game_number = s.game_number \
+             WHERE (?1 IS NULL OR m.created_at >= ?1) \
+             AND (?2 IS NULL OR m.created_at < ?2) \
+             AND (?3 IS NULL OR m.format = ?3) \
+             AND d.fingerprint = ?4 \
+             GROUP BY identity, s.cards_in, s.cards_out \
+             ORDER BY identity, COUNT(*) DESC, s.cards_in, s.cards_out"
+        );
+        let mut statement = self.conn.prepare(&sql)?;
+        let rows = statement
+            .query_map(
+                params![filter.since, filter.until, filter.format, fingerprint],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, u32>(3)?,
+                        row.get::<_, u32>(4)?,
+                        row.get::<_, u32>(5)?,
+                    ))
+                },
+            )?
+            .collect::<RusqliteResult<Vec<_>>>()?;
+        rows.into_iter()
+            .map(
+                |(opponent_identity, cards_in, cards_out, games, wins, losses)| {
+                    Ok(SideboardPlan {
+                        opponent_identity,
+                        diff: deck_diff_from_json(&cards_in, &cards_out)?,
+                        games,
+                        wins,
+                        losses,
+                    })
+                },
+            )
+            .collect()
+    }
+
     /// # Errors
     ///
     /// will return an error if the database cannot be contacted for some reason
@@ -210,27 +685,416 @@ impl MatchInsightDB {
     /// # Errors
     ///
     /// will return an error if the database cannot be contacted for some reason
-    pub fn get_matches(&mut self) -> Result<Vec<MTGAMatch>> {
-        let mut statement = self.conn.prepare("SELECT id, controller_seat_id, controller_player_name, opponent_player_name, created_at FROM matches")?;
-        let matches = statement
+    pub fn get_rank_snapshots(&mut self) -> Result<Vec<RankSnapshot>> {
+        let mut statement = self.conn.prepare(
+            "SELECT observed_at, \
+                constructed_season_ordinal, constructed_class, constructed_tier, constructed_step, \
+                constructed_matches_won, constructed_matches_lost, constructed_matches_drawn, \
+                constructed_percentile, constructed_placement, \
+                limited_season_ordinal, limited_class, limited_tier, limited_step, \
+                limited_matches_won, limited_matches_lost, limited_matches_drawn, \
+                limited_percentile, limited_placement \
+             FROM rank_snapshots ORDER BY observed_at",
+        )?;
+        let rank_snapshots = statement
             .query_map([], |row| {
-                let id: String = row.get(0)?;
-                let controller_seat_id: i32 = row.get(1)?;
-                let controller_player_name: String = row.get(2)?;
-                let opponent_player_name: String = row.get(3)?;
-                let created_at: Option<DateTime<Utc>> = row.get(4)?;
-                Ok(MTGAMatch {
-                    id,
-                    controller_seat_id,
-                    controller_player_name,
-                    opponent_player_name,
-                    created_at: created_at.unwrap_or_default(),
+                Ok(RankSnapshot {
+                    observed_at: row.get(0)?,
+                    constructed: rank_from_row(row, 1)?,
+                    limited: rank_from_row(row, 10)?,
                 })
             })?
+            .collect::<RusqliteResult<Vec<RankSnapshot>>>()?;
+        Ok(rank_snapshots)
+    }
+
+    /// Closest rank snapshots taken before the match started and after it ended
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_match_ranks(&mut self, match_id: &str) -> Result<MatchRanks> {
+        let mut statement = self.conn.prepare(
+            "SELECT r.relation, s.observed_at, \
+                s.constructed_season_ordinal, s.constructed_class, s.constructed_tier, s.constructed_step, \
+                s.constructed_matches_won, s.constructed_matches_lost, s.constructed_matches_drawn, \
+                s.constructed_percentile, s.constructed_placement, \
+                s.limited_season_ordinal, s.limited_class, s.limited_tier, s.limited_step, \
+                s.limited_matches_won, s.limited_matches_lost, s.limited_matches_drawn, \
+                s.limited_percentile, s.limited_placement \
+             FROM match_ranks r JOIN rank_snapshots s ON s.id = r.rank_snapshot_id \
+             WHERE r.match_id = ?1",
+        )?;
+        let mut match_ranks = MatchRanks::default();
+        let linked = statement.query_map([match_id], |row| {
+            let rank_snapshot = RankSnapshot {
+                observed_at: row.get(1)?,
+                constructed: rank_from_row(row, 2)?,
+                limited: rank_from_row(row, 11)?,
+            };
+            Ok((row.get::<_, String>(0)?, rank_snapshot))
+        })?;
+        for link in linked {
+            let (relation, rank_snapshot) = link?;
+            if relation == "before" {
+                match_ranks.before = Some(rank_snapshot);
+            } else {
+                match_ranks.after = Some(rank_snapshot);
+            }
+        }
+        Ok(match_ranks)
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_draft_picks(&mut self, draft_id: &str) -> Result<Vec<DraftPick>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT pack_number, pick_number, cards_offered, cards_picked, picked_at FROM draft_picks \
+             WHERE draft_id = ?1 ORDER BY pack_number, pick_number",
+        )?;
+        let picks = stmt
+            .query_map([draft_id], |row| {
+                let cards_offered: String = row.get(2)?;
+                let cards_picked: String = row.get(3)?;
+                Ok(DraftPick {
+                    pack_number: row.get(0)?,
+                    pick_number: row.get(1)?,
+                    cards_offered: serde_json::from_str(&cards_offered).unwrap_or_default(),
+                    cards_picked: serde_json::from_str(&cards_picked).unwrap_or_default(),
+                    picked_at: row.get(4)?,
+                })
+            })?
+            .collect::<RusqliteResult<Vec<DraftPick>>>()?;
+        Ok(picks)
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_matches(&mut self) -> Result<Vec<MTGAMatch>> {
+        let mut statement = self
+            .conn
+            .prepare(&format!("{MATCH_QUERY} ORDER BY created_at, id"))?;
+        let matches = statement
+            .query_map([], match_from_row)?
             .collect::<RusqliteResult<Vec<MTGAMatch>>>()?;
 
         Ok(matches)
     }
+
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_match(&mut self, match_id: &str) -> Result<Option<MTGAMatch>> {
+        let mut statement = self.conn.prepare(&format!("{MATCH_QUERY} WHERE id = ?1"))?;
+        let mut matches = statement.query_map([match_id], match_from_row)?;
+        Ok(matches.next().transpose()?)
+    }
+
+    /// Team that won the match, None if the match is unknown or has no result
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_match_winner(&mut self, match_id: &str) -> Result<Option<i32>> {
+        let mut statement = self.conn.prepare(
+            "SELECT winning_team_id FROM match_results WHERE match_id = ?1 AND game_number = 0",
+        )?;
+        let mut winners = statement.query_map([match_id], |row| row.get(0))?;
+        Ok(winners.next().transpose()?)
+    }
+
+    /// Wins and losses of the matches or games selected by `filter`, grouped by `label`,
+    /// a SQL expression over the columns of `WIN_RATE_QUERY`
+    fn get_win_rates(
+        &mut self,
+        label: &str,
+        unit: WinRateUnit,
+        filter: &WinRateFilter,
+    ) -> Result<Vec<WinRate>> {
+        self.get_grouped_win_rates(label, label, unit, filter)
+    }
+
+    /// Like `get_win_rates`, for groups that are not told apart by their label,
+    /// e.g. two decks with the same name
+    fn get_grouped_win_rates(
+        &mut self,
+        group: &str,
+        label: &str,
+        unit: WinRateUnit,
+        filter: &WinRateFilter,
+    ) -> Result<Vec<WinRate>> {
+        let game_number = match unit {
+            WinRateUnit::Match => "r.game_number = 0",
+            WinRateUnit::Game => "r.game_number > 0",
+        };
+        let sql = format!(
+            "SELECT {label} AS label, \
+                SUM(r.winning_team_id = m.controller_seat_id), \
+                SUM(r.winning_team_id NOT IN ({NO_WINNING_TEAM}, m.controller_seat_id)), \
+                SUM(r.winning_team_id = {NO_WINNING_TEAM}) \
+             {WIN_RATE_QUERY} AND {game_number} \
+             GROUP BY {group} ORDER BY COUNT(*) DESC, label"
+        );
+        let mut statement = self.conn.prepare(&sql)?;
+        let win_rates = statement
+            .query_map(params![filter.since, filter.until, filter.format], |row| {
+                Ok(WinRate::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                ))
+            })?
+            .collect::<RusqliteResult<Vec<WinRate>>>()?;
+        Ok(win_rates)
+    }
+
+    /// Match win rate over every match selected by `filter`, e.g. a date range
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rate(&mut self, filter: &WinRateFilter) -> Result<WinRate> {
+        let win_rate = self
+            .get_win_rates("'All'", WinRateUnit::Match, filter)?
+            .pop()
+            .unwrap_or(WinRate::new("All".to_string(), 0, 0, 0));
+        Ok(win_rate)
+    }
+
+    /// Match win rate per format
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_format(&mut self, filter: &WinRateFilter) -> Result<Vec<WinRate>> {
+        self.get_win_rates("COALESCE(m.format, 'Unknown')", WinRateUnit::Match, filter)
+    }
+
+    /// Match win rate per deck played in game 1, counting every version of a deck together.
+    /// Labeled with the deck's latest name, or its lineage id for decks never seen by name
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_deck(&mut self, filter: &WinRateFilter) -> Result<Vec<WinRate>> {
+        self.get_grouped_win_rates(
+            "v.lineage_id",
+            &format!("COALESCE({LINEAGE_NAME}, 'Deck ' || substr(v.lineage_id, 1, 8), 'Unknown')"),
+            WinRateUnit::Match,
+            filter,
+        )
+    }
+
+    /// Every decklist seen, oldest first
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_deck_versions(&mut self) -> Result<Vec<DeckVersion>> {
+        query_deck_versions(&self.conn)
+    }
+
+    /// Every deck played in the matches selected by `filter`, with all its versions,
+    /// most played first
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_deck_lineages(&mut self, filter: &WinRateFilter) -> Result<Vec<DeckLineage>> {
+        let win_rates =
+            self.get_win_rates("COALESCE(v.lineage_id, '')", WinRateUnit::Match, filter)?;
+        let versions = self.get_deck_versions()?;
+        let lineages = win_rates
+            .into_iter()
+            // matches written before deck versions were tracked
+            .filter(|win_rate| !win_rate.label.is_empty())
+            .map(|win_rate| {
+                let lineage_versions: Vec<DeckVersion> = versions
+                    .iter()
+                    .filter(|version| version.lineage_id == win_rate.label)
+                    .cloned()
+                    .collect();
+                let name = lineage_versions
+                    .iter()
+                    .rev()
+                    .find_map(|version| version.name.clone());
+                DeckLineage {
+                    lineage_id: win_rate.label.clone(),
+                    name,
+                    versions: lineage_versions,
+                    win_rate,
+                }
+            })
+            .collect();
+        Ok(lineages)
+    }
+
+    /// Match win rate per color identity of the opponent's cards seen during the match
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_opponent_identity(
+        &mut self,
+        filter: &WinRateFilter,
+    ) -> Result<Vec<WinRate>> {
+        self.get_win_rates(OPPONENT_IDENTITY, WinRateUnit::Match, filter)
+    }
+
+    /// Match win rate per week the match started in, e.g. "2024-W18"
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_week(&mut self, filter: &WinRateFilter) -> Result<Vec<WinRate>> {
+        self.get_win_rates(
+            "COALESCE(strftime('%Y-W%W', m.created_at), 'Unknown')",
+            WinRateUnit::Match,
+            filter,
+        )
+    }
+
+    /// Game win rate on the play and on the draw
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_play_draw(&mut self, filter: &WinRateFilter) -> Result<Vec<WinRate>> {
+        self.get_win_rates(
+            "COALESCE(g.play_draw, 'Unknown')",
+            WinRateUnit::Game,
+            filter,
+        )
+    }
+
+    /// Game win rate per number of mulligans taken before keeping
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_win_rates_by_mulligans(&mut self, filter: &WinRateFilter) -> Result<Vec<WinRate>> {
+        self.get_win_rates(
+            "CAST(COALESCE(g.mulligans, 0) AS TEXT)",
+            WinRateUnit::Game,
+            filter,
+        )
+    }
+
+    /// Winning and losing streaks over the matches selected by `filter`, in the order they started
+    ///
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be contacted for some reason
+    pub fn get_streaks(&mut self, filter: &WinRateFilter) -> Result<Streaks> {
+        let sql = format!(
+            "SELECT r.winning_team_id = m.controller_seat_id {WIN_RATE_QUERY} AND r.game_number = 0 \
+             AND r.winning_team_id != {NO_WINNING_TEAM} \
+             ORDER BY m.created_at, m.id"
+        );
+        let mut statement = self.conn.prepare(&sql)?;
+        let outcomes = statement
+            .query_map(params![filter.since, filter.until, filter.format], |row| {
+                row.get(0)
+            })?
+            .collect::<RusqliteResult<Vec<bool>>>()?;
+        Ok(Streaks::new(outcomes))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum WinRateUnit {
+    Match,
+    Game,
+}
+
+/// Every match or game result with what it can be grouped by. Result rows of game 0 are for
+/// the whole match. Team ids are the same as seat ids in two player matches
+const WIN_RATE_QUERY: &str = "FROM match_results r \
+    JOIN matches m ON m.id = r.match_id \
+    LEFT JOIN decks d ON d.match_id = r.match_id AND d.game_number = 1 \
+    LEFT JOIN deck_versions v ON v.fingerprint = d.fingerprint \
+    LEFT JOIN ( \
+        SELECT match_id, game_number, MAX(play_draw) AS play_draw, SUM(decision = 'Mulligan') AS mulligans \
+        FROM mulligans GROUP BY match_id, game_number \
+    ) g ON g.match_id = r.match_id AND g.game_number = r.game_number \
+    WHERE (?1 IS NULL OR m.created_at >= ?1) \
+    AND (?2 IS NULL OR m.created_at < ?2) \
+    AND (?3 IS NULL OR m.format = ?3)";
+
+/// The opponent's color identity in `m`, with no colors seen being colorless
+const OPPONENT_IDENTITY: &str = "CASE m.opponent_identity WHEN '' THEN 'Colorless' ELSE COALESCE(m.opponent_identity, 'Unknown') END";
+
+/// The latest name given to any version in the lineage of deck version `v`
+const LINEAGE_NAME: &str = "(SELECT n.name FROM deck_versions n \
+    WHERE n.lineage_id = v.lineage_id AND n.name IS NOT NULL \
+    ORDER BY n.first_seen_at DESC LIMIT 1)";
+
+fn query_deck_versions(conn: &Connection) -> Result<Vec<DeckVersion>> {
+    let mut statement = conn.prepare(
+        "SELECT fingerprint, deck_id, name, lineage_id, parent_fingerprint, deck_cards, \
+         sideboard_cards, first_seen_at FROM deck_versions ORDER BY first_seen_at, fingerprint",
+    )?;
+    let versions = statement
+        .query_map([], |row| {
+            let deck_cards: String = row.get(5)?;
+            let sideboard_cards: String = row.get(6)?;
+            let first_seen_at: Option<DateTime<Utc>> = row.get(7)?;
+            let deck = Deck::from_raw_decklist(String::new(), 0, &deck_cards, &sideboard_cards);
+            Ok(DeckVersion {
+                fingerprint: row.get(0)?,
+                deck_id: row.get(1)?,
+                name: row.get(2)?,
+                lineage_id: row.get(3)?,
+                parent_fingerprint: row.get(4)?,
+                mainboard: deck.mainboard,
+                sideboard: deck.sideboard,
+                first_seen_at: first_seen_at.unwrap_or_default(),
+            })
+        })?
+        .collect::<RusqliteResult<Vec<DeckVersion>>>()?;
+    Ok(versions)
+}
+
+fn deck_diff_from_json(cards_in: &str, cards_out: &str) -> Result<DeckDiff> {
+    Ok(DeckDiff {
+        cards_in: serde_json::from_str(cards_in)?,
+        cards_out: serde_json::from_str(cards_out)?,
+    })
+}
+
+const MATCH_QUERY: &str =
+    "SELECT id, controller_seat_id, controller_player_name, opponent_player_name, \
+    created_at, format, opponent_identity, ended_at FROM matches";
+
+fn match_from_row(row: &Row) -> RusqliteResult<MTGAMatch> {
+    let created_at: Option<DateTime<Utc>> = row.get(4)?;
+    Ok(MTGAMatch {
+        id: row.get(0)?,
+        controller_seat_id: row.get(1)?,
+        controller_player_name: row.get(2)?,
+        opponent_player_name: row.get(3)?,
+        created_at: created_at.unwrap_or_default(),
+        format: row.get(5)?,
+        opponent_identity: row.get(6)?,
+        ended_at: row.get(7)?,
+    })
+}
+
+fn rank_from_row(row: &Row, offset: usize) -> RusqliteResult<Rank> {
+    Ok(Rank {
+        season_ordinal: row.get(offset)?,
+        class: row.get(offset + 1)?,
+        tier: row.get(offset + 2)?,
+        step: row.get(offset + 3)?,
+        matches_won: row.get::<_, Option<i32>>(offset + 4)?.unwrap_or_default(),
+        matches_lost: row.get::<_, Option<i32>>(offset + 5)?.unwrap_or_default(),
+        matches_drawn: row.get::<_, Option<i32>>(offset + 6)?.unwrap_or_default(),
+        mythic_percentile: row.get(offset + 7)?,
+        mythic_placement: row.get(offset + 8)?,
+    })
 }
 
 impl ArenaMatchStorageBackend for MatchInsightDB {
@@ -241,54 +1105,502 @@ impl ArenaMatchStorageBackend for MatchInsightDB {
     /// or connection error
     fn write(&mut self, match_replay: &MatchReplay) -> Result<()> {
         info!("Writing match replay to database");
-        let controller_seat_id = match_replay.get_controller_seat_id()?;
-        let match_id = &match_replay.match_id;
-        let (controller_name, opponent_name) = match_replay.get_player_names(controller_seat_id)?;
-        let event_start = match_replay.match_start_time().unwrap_or(Utc::now());
-
-        let mtga_match = MTGAMatchBuilder::default()
-            .id(match_id.to_string())
-            .controller_seat_id(controller_seat_id)
-            .controller_player_name(controller_name)
-            .opponent_player_name(opponent_name)
-            .created_at(event_start)
-            .build()?;
+        self.replace_match(match_replay)
+    }
+}
 
+impl ArenaDraftStorageBackend for MatchInsightDB {
+    fn write_draft(&mut self, draft_replay: &DraftReplay) -> Result<()> {
+        info!("Writing draft replay to database");
         let tx = self.conn.transaction()?;
+        Self::insert_draft(draft_replay, &tx)?;
+        Self::delete_draft_picks(&draft_replay.draft_id, &tx)?;
+        draft_replay
+            .picks
+            .iter()
+            .try_for_each(|pick| Self::insert_draft_pick(&draft_replay.draft_id, pick, &tx))?;
+        tx.commit()?;
+        Ok(())
+    }
+}
 
-        Self::insert_match(&mtga_match, &tx)?;
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
 
-        match_replay
-            .get_decklists()?
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::models::deck::NamedDeck;
+    use crate::models::win_rate::Streak;
+    use crate::mtga_events::client::{
+        ClientMessage, RequestTypeClientToMatchServiceMessage, SubmitDeckResp,
+        SubmitDeckRespWrapper,
+    };
+    use crate::mtga_events::gre::{
+        ConnectResp, ConnectRespWrapper, DeckMessage, GREToClientEvent, GREToClientMessage,
+        GameInfo, GameStateMessage, GameStateMessageWrapper, GreMeta, IntermissionReq,
+        IntermissionReqWrapper, RequestTypeGREToClientEvent,
+    };
+    use crate::mtga_events::mgrsc::{FinalMatchResult, MatchPlayer, RequestTypeMGRSCEvent};
+    use crate::mtga_events::primitives::ResultListEntry;
+    use crate::replay::MatchReplayEvent;
+
+    fn insight_db() -> Result<MatchInsightDB> {
+        let mut db = MatchInsightDB::new(
+            Connection::open_in_memory()?,
+            CardsDatabase {
+                db: BTreeMap::new(),
+            },
+        );
+        db.init()?;
+        Ok(db)
+    }
+
+    fn gre_event(gre_to_client_message: GREToClientMessage) -> MatchReplayEvent {
+        MatchReplayEvent::GRE(RequestTypeGREToClientEvent {
+            gre_to_client_event: GREToClientEvent {
+                gre_to_client_messages: vec![gre_to_client_message],
+            },
+            ..RequestTypeGREToClientEvent::default()
+        })
+    }
+
+    fn submit_deck(deck_cards: Vec<i32>, sideboard_cards: Vec<i32>) -> MatchReplayEvent {
+        MatchReplayEvent::Client(RequestTypeClientToMatchServiceMessage {
+            client_to_match_service_message_type: String::new(),
+            request_id: 0,
+            payload: ClientMessage::SubmitDeckResp(SubmitDeckRespWrapper {
+                submit_deck_resp: SubmitDeckResp {
+                    deck: DeckMessage {
+                        deck_cards,
+                        sideboard_cards,
+                    },
+                },
+                ..SubmitDeckRespWrapper::default()
+            }),
+            timestamp: None,
+            transaction_id: None,
+        })
+    }
+
+    fn result(scope: &str, winning_team_id: i32) -> ResultListEntry {
+        ResultListEntry {
+            scope: scope.to_string(),
+            winning_team_id,
+            reason: None,
+            result: None,
+        }
+    }
+
+    /// A match in `format` where seat 1 is the controller, and the winner of the last game
+    /// wins the match
+    fn match_replay(match_id: &str, format: &str, game_winners: &[i32]) -> Result<MatchReplay> {
+        deck_match_replay(match_id, format, game_winners, vec![1, 2, 3])
+    }
+
+    fn deck_match_replay(
+        match_id: &str,
+        format: &str,
+        game_winners: &[i32],
+        deck_cards: Vec<i32>,
+    ) -> Result<MatchReplay> {
+        let mut client_server_messages = vec![gre_event(GREToClientMessage::ConnectResp(
+            ConnectRespWrapper {
+                meta: GreMeta {
+                    system_seat_ids: vec![1],
+                    ..GreMeta::default()
+                },
+                connect_resp: ConnectResp {
+                    deck_message: DeckMessage {
+                        deck_cards,
+                        sideboard_cards: vec![4],
+                    },
+                    ..ConnectResp::default()
+                },
+            },
+        ))];
+        for (game_number, winning_team_id) in (1..).zip(game_winners) {
+            client_server_messages.push(gre_event(GREToClientMessage::GameStateMessage(
+                GameStateMessageWrapper {
+                    game_state_message: GameStateMessage {
+                        game_info: Some(GameInfo {
+                            game_number,
+                            ..GameInfo::default()
+                        }),
+                        ..GameStateMessage::default()
+                    },
+                    ..GameStateMessageWrapper::default()
+                },
+            )));
+            client_server_messages.push(gre_event(GREToClientMessage::IntermissionReq(
+                IntermissionReqWrapper {
+                    intermission_req: IntermissionReq {
+                        result: result("MatchScope_Game", *winning_team_id),
+                        ..IntermissionReq::default()
+                    },
+                    ..IntermissionReqWrapper::default()
+                },
+            )));
+        }
+
+        let mut match_start_message = RequestTypeMGRSCEvent::default();
+        match_start_message.mgrsc_event.game_room_info.players = Some(vec![
+            MatchPlayer {
+                player_name: "me".to_string(),
+                system_seat_id: 1,
+                team_id: 1,
+                ..MatchPlayer::default()
+            },
+            MatchPlayer {
+                player_name: "them".to_string(),
+                system_seat_id: 2,
+                team_id: 2,
+                ..MatchPlayer::default()
+            },
+        ]);
+        let mut match_end_message = match_start_message.clone();
+        match_end_message
+            .mgrsc_event
+            .game_room_info
+            .final_match_result = Some(FinalMatchResult {
+            match_id: match_id.to_string(),
+            result_list: vec![result(
+                "MatchScope_Match",
+                game_winners.last().copied().unwrap_or_default(),
+            )],
+        });
+
+        Ok(MatchReplay {
+            match_id: match_id.to_string(),
+            match_start_message,
+            match_end_message,
+            client_server_messages,
+            business_messages: vec![serde_json::from_value(serde_json::json!({
+                "EventId": format,
+                "EventTime": "2024-05-01T12:00:00Z",
+                "MatchId": match_id,
+            }))?],
+            ..MatchReplay::default()
+        })
+    }
+
+    fn count(db: &MatchInsightDB, table: &str) -> Result<i64> {
+        Ok(db
+            .conn
+            .query_row(&format!("SELECT count(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })?)
+    }
+
+    #[test]
+    fn test_rewrite_is_idempotent() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[1, 1])?)?;
+        db.write(&match_replay("match-1", "Ladder", &[1, 1])?)?;
+
+        assert_eq!(count(&db, "matches")?, 1);
+        assert_eq!(count(&db, "decks")?, 1);
+        assert_eq!(count(&db, "match_results")?, 3);
+        assert_eq!(
+            db.get_processed_version("match-1")?.as_deref(),
+            Some(PROCESSED_VERSION)
+        );
+        assert_eq!(db.get_processed_version("match-2")?, None);
+        assert_eq!(db.get_match_winner("match-1")?, Some(1));
+        let mtga_match = db.get_match("match-1")?.ok_or(anyhow!("match-1 missing"))?;
+        assert_eq!(mtga_match.opponent_player_name, "them");
+        assert_eq!(mtga_match.format.as_deref(), Some("Ladder"));
+        assert!(db.get_match("match-2")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_match_drops_stale_rows() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[1, 1, 1])?)?;
+        db.replace_match(&match_replay("match-1", "Ladder", &[1, 1])?)?;
+
+        let game_numbers: Vec<i32> = db
+            .get_match_results("match-1")?
             .iter()
-            .try_for_each(|deck| Self::insert_deck(&match_replay.match_id, deck, &tx))?;
+            .map(|match_result| match_result.game_number)
+            .collect();
+        assert_eq!(game_numbers, vec![1, 2]);
+        Ok(())
+    }
 
-        let mulligan_infos = match_replay.get_mulligan_infos(&self.cards_database)?;
-        mulligan_infos
+    #[test]
+    fn test_failed_replace_keeps_rows() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[1, 1])?)?;
+        let mut unfinished = match_replay("match-1", "Ladder", &[1])?;
+        unfinished
+            .match_end_message
+            .mgrsc_event
+            .game_room_info
+            .final_match_result = None;
+
+        assert!(db.replace_match(&unfinished).is_err());
+        assert_eq!(count(&db, "match_results")?, 3);
+        Ok(())
+    }
+
+    fn rank_snapshot(observed_at: &str, class: &str) -> Result<RankSnapshot> {
+        Ok(RankSnapshot {
+            observed_at: observed_at.parse()?,
+            constructed: Rank {
+                class: Some(class.to_string()),
+                ..Rank::default()
+            },
+            limited: Rank::default(),
+        })
+    }
+
+    /// A match played from `start` to `end`, seeing `rank_snapshots` on the way
+    fn timed_match_replay(
+        match_id: &str,
+        start: &str,
+        end: &str,
+        rank_snapshots: Vec<RankSnapshot>,
+    ) -> Result<MatchReplay> {
+        let mut match_replay = match_replay(match_id, "Ladder", &[1, 1])?;
+        let start: DateTime<Utc> = start.parse()?;
+        let end: DateTime<Utc> = end.parse()?;
+        for business_message in &mut match_replay.business_messages {
+            business_message.event_time = Some(start);
+        }
+        match_replay.match_end_message.timestamp = end.timestamp_millis().to_string();
+        match_replay.rank_snapshots = rank_snapshots;
+        Ok(match_replay)
+    }
+
+    fn linked_classes(db: &mut MatchInsightDB, match_id: &str) -> Result<[Option<String>; 2]> {
+        let match_ranks = db.get_match_ranks(match_id)?;
+        Ok([match_ranks.before, match_ranks.after]
+            .map(|rank_snapshot| rank_snapshot.and_then(|snapshot| snapshot.constructed.class)))
+    }
+
+    #[test]
+    fn test_match_ranks() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&timed_match_replay(
+            "match-1",
+            "2024-05-01T12:00:00Z",
+            "2024-05-01T12:30:00Z",
+            vec![
+                rank_snapshot("2024-05-01T11:00:00Z", "Silver")?,
+                // mid match, neither before nor after it
+                rank_snapshot("2024-05-01T12:10:00Z", "Bronze")?,
+            ],
+        )?)?;
+        assert_eq!(
+            linked_classes(&mut db, "match-1")?,
+            [Some("Silver".to_string()), None]
+        );
+
+        // the rank reported back in the lobby comes in with the next match, twice in a second
+        let match_2 = timed_match_replay(
+            "match-2",
+            "2024-05-01T14:00:00Z",
+            "2024-05-01T14:30:00Z",
+            vec![
+                rank_snapshot("2024-05-01T13:00:00Z", "Gold")?,
+                rank_snapshot("2024-05-01T13:00:00Z", "Platinum")?,
+            ],
+        )?;
+        db.write(&match_2)?;
+        db.write(&match_2)?;
+        assert_eq!(count(&db, "rank_snapshots")?, 4);
+        assert_eq!(
+            linked_classes(&mut db, "match-1")?,
+            [Some("Silver".to_string()), Some("Gold".to_string())]
+        );
+        assert_eq!(
+            linked_classes(&mut db, "match-2")?,
+            [Some("Platinum".to_string()), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_win_rates() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[1, 1])?)?;
+        db.write(&match_replay("match-2", "Ladder", &[2, 1, 2])?)?;
+        db.write(&match_replay("match-3", "Traditional_Ladder", &[2, 1, 1])?)?;
+
+        let overall = db.get_win_rate(&WinRateFilter::default())?;
+        assert_eq!((overall.wins, overall.losses), (2, 1));
+
+        let by_format = db.get_win_rates_by_format(&WinRateFilter::default())?;
+        let by_format: Vec<_> = by_format
             .iter()
-            .try_for_each(|mulligan_info| Self::insert_mulligan_info(mulligan_info.clone(), &tx))?;
+            .map(|win_rate| (win_rate.label.as_str(), win_rate.wins, win_rate.losses))
+            .collect();
+        assert_eq!(
+            by_format,
+            vec![("Ladder", 1, 1), ("Traditional_Ladder", 1, 0)]
+        );
 
-        // not too keen on this data model
-        let match_results = match_replay.get_match_results()?;
-        debug!("{:?}", match_results);
-        for (i, result) in match_results.result_list.iter().enumerate() {
-            let game_number = if result.scope == "MatchScope_Game" {
-                i32::try_from(i + 1).unwrap_or(0)
-            } else {
-                0
-            };
+        let ladder = WinRateFilter {
+            format: Some("Ladder".to_string()),
+            ..WinRateFilter::default()
+        };
+        let by_opponent = db.get_win_rates_by_opponent_identity(&ladder)?;
+        assert_eq!(by_opponent.len(), 1);
+        assert_eq!(by_opponent[0].label, "Colorless");
+        assert_eq!(by_opponent[0].total(), 2);
+        let by_deck = db.get_win_rates_by_deck(&ladder)?;
+        let fingerprint = Deck::new(String::new(), 0, vec![1, 2, 3], vec![4]).fingerprint();
+        assert_eq!(by_deck[0].label, format!("Deck {}", &fingerprint[..8]));
+
+        // game results of the ladder matches, without mulligan rows to group by
+        let by_mulligans = db.get_win_rates_by_mulligans(&ladder)?;
+        assert_eq!(by_mulligans.len(), 1);
+        assert_eq!(by_mulligans[0].label, "0");
+        assert_eq!((by_mulligans[0].wins, by_mulligans[0].losses), (3, 2));
+        Ok(())
+    }
 
-            let match_result = MatchResultBuilder::default()
-                .match_id(match_id.to_string())
-                .game_number(game_number)
-                .winning_team_id(result.winning_team_id)
-                .result_scope(result.scope.clone())
-                .build()?;
+    #[test]
+    fn test_draws() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[2, 2])?)?;
+        db.write(&match_replay("match-2", "Ladder", &[1, 1])?)?;
+        // a drawn match ends in a drawn game, with no winning team
+        db.write(&match_replay("match-3", "Ladder", &[1, 2, 0])?)?;
 
-            Self::insert_match_result(&match_result, &tx)?;
+        let overall = db.get_win_rate(&WinRateFilter::default())?;
+        assert_eq!((overall.wins, overall.losses, overall.draws), (1, 1, 1));
+        assert!((overall.win_rate - 0.5).abs() < f64::EPSILON);
+        let by_mulligans = db.get_win_rates_by_mulligans(&WinRateFilter::default())?;
+        assert_eq!(
+            (
+                by_mulligans[0].wins,
+                by_mulligans[0].losses,
+                by_mulligans[0].draws
+            ),
+            (3, 3, 1)
+        );
+        // the draw neither extends nor breaks the win streak
+        let streaks = db.get_streaks(&WinRateFilter::default())?;
+        assert_eq!(
+            streaks.current,
+            Some(Streak {
+                won: true,
+                length: 1
+            })
+        );
+        assert_eq!(streaks.longest_loss, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_win_rate_date_range() -> Result<()> {
+        let mut db = insight_db()?;
+        db.write(&match_replay("match-1", "Ladder", &[1])?)?;
+        let started_at: DateTime<Utc> = "2024-05-01T12:00:00Z".parse()?;
+
+        let since = WinRateFilter {
+            since: Some(started_at),
+            ..WinRateFilter::default()
+        };
+        assert_eq!(db.get_win_rate(&since)?.total(), 1);
+        let until = WinRateFilter {
+            until: Some(started_at),
+            ..WinRateFilter::default()
+        };
+        assert_eq!(db.get_win_rate(&until)?.total(), 0);
+
+        let streaks = db.get_streaks(&since)?;
+        assert_eq!(streaks.longest_win, 1);
+        assert_eq!(streaks.longest_loss, 0);
+
+        let by_week = db.get_win_rates_by_week(&WinRateFilter::default())?;
+        assert_eq!(by_week[0].label, "2024-W18");
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_lineages() -> Result<()> {
+        let mut db = insight_db()?;
+        let mono_red = [vec![1; 4], vec![2; 4], vec![3; 4]].concat();
+        let mut match_1 = deck_match_replay("match-1", "Ladder", &[1], mono_red.clone())?;
+        match_1.named_decks.push(NamedDeck {
+            deck_id: "deck-1".to_string(),
+            name: "Mono Red".to_string(),
+            mainboard: mono_red.clone(),
+            sideboard: vec![4],
+            observed_at: "2024-05-01T11:55:00Z".parse()?,
+        });
+        db.write(&match_1)?;
+        db.write(&match_1)?;
+        // a one card swap, played without going through the deck builder
+        let swapped = [vec![1; 4], vec![2; 4], vec![3; 3], vec![5]].concat();
+        db.write(&deck_match_replay("match-2", "Ladder", &[2], swapped)?)?;
+        db.write(&deck_match_replay("match-3", "Ladder", &[1], vec![9; 12])?)?;
+
+        assert_eq!(count(&db, "deck_versions")?, 3);
+        assert_eq!(db.get_decklists("match-2")?[0].name, "Mono Red");
+        assert_eq!(db.get_decklists("match-3")?[0].name, UNNAMED_DECK);
+
+        let lineages = db.get_deck_lineages(&WinRateFilter::default())?;
+        assert_eq!(lineages.len(), 2);
+        assert_eq!(lineages[0].name.as_deref(), Some("Mono Red"));
+        assert_eq!(lineages[0].versions.len(), 2);
+        assert_eq!(
+            lineages[0].versions[1].parent_fingerprint,
+            Some(lineages[0].versions[0].fingerprint.clone())
+        );
+        assert_eq!(
+            (lineages[0].win_rate.wins, lineages[0].win_rate.losses),
+            (1, 1)
+        );
+        assert_eq!(lineages[1].name, None);
+        assert_eq!(
+            lineages[1].display_name(),
+            format!("Deck {}", &lineages[1].lineage_id[..8])
+        );
+
+        let by_deck = db.get_win_rates_by_deck(&WinRateFilter::default())?;
+        assert_eq!(by_deck[0].label, "Mono Red");
+        assert_eq!(by_deck[0].total(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sideboard_plans() -> Result<()> {
+        let mut db = insight_db()?;
+        for (match_id, game_winners) in [("match-1", [2, 1, 1]), ("match-2", [1, 2, 2])] {
+            let mut match_replay = match_replay(match_id, "Ladder", &game_winners)?;
+            // after the ConnectResp and game 1, bring in the sideboard card for a 3
+            match_replay
+                .client_server_messages
+                .insert(3, submit_deck(vec![1, 2, 4], vec![3]));
+            db.write(&match_replay)?;
         }
+        db.write(&match_replay("match-3", "Ladder", &[1])?)?;
 
-        tx.commit()?;
+        let deck_diff = DeckDiff {
+            cards_in: [(4, 1)].into(),
+            cards_out: [(3, 1)].into(),
+        };
+        assert_eq!(db.get_deck_diffs("match-1")?, vec![(2, deck_diff.clone())]);
+        assert!(db.get_deck_diffs("match-3")?.is_empty());
+
+        let fingerprint = Deck::new(String::new(), 0, vec![1, 2, 3], vec![4]).fingerprint();
+        let plans = db.get_sideboard_plans(&fingerprint, &WinRateFilter::default())?;
+        assert_eq!(
+            plans,
+            vec![SideboardPlan {
+                opponent_identity: "Colorless".to_string(),
+                diff: deck_diff,
+                games: 2,
+                wins: 1,
+                losses: 1,
+            }]
+        );
         Ok(())
     }
 }
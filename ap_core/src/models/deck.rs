@@ -1,11 +1,21 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 
+use crate::cards::CardsDatabase;
+use crate::mtga_events::frontdoor::{DeckSummary, FrontDoorDeck};
 use crate::mtga_events::gre::DeckMessage;
 
+/// Name of decks only seen in game, where MTGA does not send the deck name
+pub const UNNAMED_DECK: &str = "Found Deck";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Deck {
     pub name: String,
@@ -17,7 +27,7 @@ pub struct Deck {
 impl From<DeckMessage> for Deck {
     fn from(deck_message: DeckMessage) -> Self {
         Self::new(
-            "Found Deck".to_string(),
+            UNNAMED_DECK.to_string(),
             0,
             deck_message.deck_cards,
             deck_message.sideboard_cards,
@@ -79,6 +89,230 @@ impl Deck {
     pub fn sideboard_quantities(&self) -> HashMap<i32, u16> {
         quantities(&self.sideboard)
     }
+
+    /// Identifies the decklist regardless of card order or name: a hash of the sorted
+    /// mainboard and sideboard quantities. Stable across runs and versions
+    pub fn fingerprint(&self) -> String {
+        let canonical = [&self.mainboard, &self.sideboard]
+            .iter()
+            .map(|cards| {
+                sorted_quantities(cards)
+                    .iter()
+                    .map(|(grp_id, quantity)| format!("{grp_id}:{quantity}"))
+                    .join(",")
+            })
+            .join(";");
+        // FNV-1a, std's hashers are not guaranteed to be stable
+        let hash = canonical.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+        });
+        format!("{hash:016x}")
+    }
+
+    /// How many mainboard cards have to be swapped to turn one deck into the other,
+    /// e.g. 1 for a one-card swap. Extra or missing cards count as swaps too
+    pub fn distance(&self, other: &Deck) -> u32 {
+        let ours = sorted_quantities(&self.mainboard);
+        let theirs = sorted_quantities(&other.mainboard);
+        let missing_from = |from: &BTreeMap<i32, u32>, to: &BTreeMap<i32, u32>| -> u32 {
+            from.iter()
+                .map(|(grp_id, quantity)| {
+                    quantity.saturating_sub(to.get(grp_id).copied().unwrap_or(0))
+                })
+                .sum()
+        };
+        missing_from(&ours, &theirs).max(missing_from(&theirs, &ours))
+    }
+}
+
+impl Deck {
+    /// The deck in the format MTGA copies to and imports from the clipboard:
+    /// "4 Lightning Strike (DMU) 146" lines under "Deck" and "Sideboard" headers.
+    /// Cards missing from `cards_db` are written by grp id
+    pub fn to_arena_text(&self, cards_db: &CardsDatabase) -> String {
+        let mut sections = vec![arena_section("Deck", &self.mainboard, cards_db)];
+        if !self.sideboard.is_empty() {
+            sections.push(arena_section("Sideboard", &self.sideboard, cards_db));
+        }
+        sections.join("\n")
+    }
+
+    /// Reads a deck in MTGA's clipboard format, resolving card names and set codes against
+    /// `cards_db`. The name comes from an "About" section, and cards after a blank line
+    /// without a header go to the sideboard, as in older exports
+    ///
+    /// # Errors
+    ///
+    /// will return an error if a line is not a quantity and a card, or if a card
+    /// cannot be found in `cards_db`
+    pub fn from_arena_text(text: &str, cards_db: &CardsDatabase) -> Result<Self> {
+        let mut name = None;
+        let mut mainboard = Vec::new();
+        let mut sideboard = Vec::new();
+        let mut section = ArenaSection::Deck;
+        let mut unknown_cards = Vec::new();
+
+        for (line_number, line) in (1..).zip(text.lines()) {
+            let line = line.trim();
+            match line.to_ascii_lowercase().as_str() {
+                "about" => section = ArenaSection::About,
+                "deck" | "commander" => section = ArenaSection::Deck,
+                "sideboard" | "companion" => section = ArenaSection::Sideboard,
+                "" => {
+                    if section == ArenaSection::Deck && !mainboard.is_empty() {
+                        section = ArenaSection::Sideboard;
+                    }
+                }
+                _ if section == ArenaSection::About => {
+                    if let Some(deck_name) = line.strip_prefix("Name ") {
+                        name = Some(deck_name.trim().to_string());
+                    }
+                }
+                _ => {
+                    let arena_line = ArenaLine::parse(line).ok_or_else(|| {
+                        anyhow!("line {line_number}: expected a card, got {line}")
+                    })?;
+                    let Some(grp_id) = arena_line.resolve(cards_db) else {
+                        unknown_cards.push(arena_line.name.to_string());
+                        continue;
+                    };
+                    let cards = match section {
+                        ArenaSection::Sideboard => &mut sideboard,
+                        _ => &mut mainboard,
+                    };
+                    cards.extend(std::iter::repeat_n(grp_id, arena_line.quantity));
+                }
+            }
+        }
+
+        if !unknown_cards.is_empty() {
+            return Err(anyhow!("cards not found: {}", unknown_cards.join(", ")));
+        }
+        Ok(Self::new(
+            name.unwrap_or(UNNAMED_DECK.to_string()),
+            0,
+            mainboard,
+            sideboard,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArenaSection {
+    About,
+    Deck,
+    Sideboard,
+}
+
+/// "4 Lightning Strike (DMU) 146", where the set and collector number are optional
+#[derive(Debug, PartialEq, Eq)]
+struct ArenaLine<'a> {
+    quantity: usize,
+    name: &'a str,
+    set: Option<&'a str>,
+    collector_number: Option<&'a str>,
+}
+
+impl<'a> ArenaLine<'a> {
+    fn parse(line: &'a str) -> Option<Self> {
+        let (quantity, card) = line.split_once(' ')?;
+        let quantity = quantity
+            .strip_suffix('x')
+            .unwrap_or(quantity)
+            .parse()
+            .ok()?;
+        let card = card.trim();
+        let printing = card.rsplit_once(" (").and_then(|(name, printing)| {
+            let (set, collector_number) = printing.split_once(')')?;
+            let collector_number = collector_number.trim();
+            (!set.is_empty() && !set.contains(' ')).then_some((
+                name.trim(),
+                set,
+                Some(collector_number).filter(|number| !number.is_empty()),
+            ))
+        });
+        let (name, set, collector_number) = match printing {
+            Some((name, set, collector_number)) => (name, Some(set), collector_number),
+            None => (card, None, None),
+        };
+        (!name.is_empty()).then_some(Self {
+            quantity,
+            name,
+            set,
+            collector_number,
+        })
+    }
+
+    /// The grp id of the card, a bare number being taken as a grp id already
+    fn resolve(&self, cards_db: &CardsDatabase) -> Option<i32> {
+        cards_db
+            .find_by_name(self.name, self.set, self.collector_number)
+            .map(|card| card.id)
+            .or_else(|| self.name.parse().ok())
+    }
+}
+
+/// A header and one line per card, in the order the cards first appear
+fn arena_section(header: &str, cards: &[i32], cards_db: &CardsDatabase) -> String {
+    let quantities = quantities(cards);
+    let lines = cards.iter().unique().map(|grp_id| {
+        let quantity = quantities.get(grp_id).copied().unwrap_or_default();
+        let card = match cards_db.get(grp_id) {
+            Some(card) => match &card.collector_number {
+                Some(collector_number) => format!(
+                    "{} ({}) {collector_number}",
+                    card.arena_name(),
+                    card.set.to_uppercase()
+                ),
+                None => card.arena_name().to_string(),
+            },
+            None => grp_id.to_string(),
+        };
+        format!("{quantity} {card}\n")
+    });
+    std::iter::once(format!("{header}\n"))
+        .chain(lines)
+        .collect()
+}
+
+pub(crate) fn sorted_quantities(cards: &[i32]) -> BTreeMap<i32, u32> {
+    let mut quantities = BTreeMap::new();
+    for grp_id in cards {
+        *quantities.entry(*grp_id).or_insert(0) += 1;
+    }
+    quantities
+}
+
+/// A deck as saved in the deck builder (`DeckUpsertDeck`) or submitted to an event
+/// (`EventSetDeck`), the only places MTGA logs deck names and ids
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedDeck {
+    pub deck_id: String,
+    pub name: String,
+    pub mainboard: Vec<i32>,
+    pub sideboard: Vec<i32>,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl NamedDeck {
+    pub fn new(summary: &DeckSummary, deck: &FrontDoorDeck, observed_at: DateTime<Utc>) -> Self {
+        Self {
+            deck_id: summary.deck_id.clone(),
+            name: summary.name.clone(),
+            mainboard: deck.mainboard(),
+            sideboard: deck.sideboard(),
+            observed_at,
+        }
+    }
+
+    pub fn deck(&self) -> Deck {
+        Deck::new(
+            self.name.clone(),
+            0,
+            self.mainboard.clone(),
+            self.sideboard.clone(),
+        )
+    }
 }
 
 pub fn quantities(deck: &[i32]) -> HashMap<i32, u16> {
@@ -107,6 +341,37 @@ fn process_raw_decklist(raw_decklist: &str) -> Vec<i32> {
 
 #[cfg(test)]
 mod tests {
+    use crate::cards::CardsDatabase;
+
+    fn cards_db() -> anyhow::Result<CardsDatabase> {
+        let card = |id: i32, set: &str, name: &str, collector_number: &str| {
+            serde_json::json!({
+                "id": id, "set": set, "name": name, "lang": "en", "cmc": 1.0,
+                "type_line": "Instant", "layout": "normal", "color_identity": ["R"],
+                "collector_number": collector_number,
+            })
+        };
+        let mut mdfc = card(
+            4,
+            "znr",
+            "Shatterskull Smashing // Shatterskull, the Hammer Pass",
+            "161",
+        );
+        mdfc["layout"] = "modal_dfc".into();
+        mdfc["card_faces"] = serde_json::json!([
+            {"name": "Shatterskull Smashing", "type_line": "Sorcery"},
+            {"name": "Shatterskull, the Hammer Pass", "type_line": "Land"},
+        ]);
+        Ok(CardsDatabase {
+            db: serde_json::from_value(serde_json::json!({
+                "1": card(1, "dmu", "Lightning Strike", "146"),
+                "2": card(2, "m19", "Lightning Strike", "152"),
+                "3": card(3, "m21", "Shock", "159"),
+                "4": mdfc,
+            }))?,
+        })
+    }
+
     #[test]
     fn test_quantities() {
         let deck = vec![1, 2, 3, 1, 2, 3, 1, 2, 3, 4];
@@ -198,4 +463,58 @@ mod tests {
         assert_eq!(quantities.get(&5), Some(&1));
         assert_eq!(quantities.get(&6), Some(&1));
     }
+
+    #[test]
+    fn test_deck_fingerprint() {
+        let deck = super::Deck::new("Test Deck".to_string(), 1, vec![1, 2, 1], vec![4]);
+        let shuffled = super::Deck::new("Other Name".to_string(), 2, vec![2, 1, 1], vec![4]);
+        assert_eq!(deck.fingerprint(), shuffled.fingerprint());
+        assert_eq!(deck.fingerprint().len(), 16);
+
+        let sideboarded = super::Deck::new("Test Deck".to_string(), 1, vec![1, 2, 1], vec![5]);
+        assert_ne!(deck.fingerprint(), sideboarded.fingerprint());
+    }
+
+    #[test]
+    fn test_deck_distance() {
+        let deck = super::Deck::new(String::new(), 0, vec![1, 1, 2, 2], vec![]);
+        let swapped = super::Deck::new(String::new(), 0, vec![1, 1, 2, 3], vec![]);
+        let extra = super::Deck::new(String::new(), 0, vec![1, 1, 2, 2, 3, 3], vec![]);
+        assert_eq!(deck.distance(&deck), 0);
+        assert_eq!(deck.distance(&swapped), 1);
+        assert_eq!(swapped.distance(&deck), 1);
+        assert_eq!(deck.distance(&extra), 2);
+    }
+
+    #[test]
+    fn test_to_arena_text() -> anyhow::Result<()> {
+        let deck = super::Deck::new("Burn".to_string(), 0, vec![1, 4, 1, 1, 1], vec![3, 99]);
+        assert_eq!(
+            deck.to_arena_text(&cards_db()?),
+            "Deck\n4 Lightning Strike (DMU) 146\n1 Shatterskull Smashing (ZNR) 161\n\n\
+             Sideboard\n1 Shock (M21) 159\n1 99\n"
+        );
+        let read = super::Deck::from_arena_text(&deck.to_arena_text(&cards_db()?), &cards_db()?)?;
+        assert_eq!(read.fingerprint(), deck.fingerprint());
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_arena_text() -> anyhow::Result<()> {
+        let text = "About\nName Burn\n\nDeck\n2 Lightning Strike (M19) 152\n2x lightning strike\n\n1 Shock\n";
+        let deck = super::Deck::from_arena_text(text, &cards_db()?)?;
+        assert_eq!(deck.name, "Burn");
+        // the newest printing without a set
+        assert_eq!(deck.mainboard, vec![2, 2, 2, 2]);
+        assert_eq!(deck.sideboard, vec![3]);
+
+        let deck = super::Deck::from_arena_text("Lightning Strike (DMU) 146\n", &cards_db()?);
+        assert!(deck.is_err());
+        let deck = super::Deck::from_arena_text("4 Lava Spike\n1 Shock\n1 Fireblast", &cards_db()?);
+        assert_eq!(
+            deck.err().map(|e| e.to_string()),
+            Some("cards not found: Lava Spike, Fireblast".to_string())
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,67 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::deck::{sorted_quantities, Deck};
+
+/// Mainboard changes between two games of a match, as grp id to number of copies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeckDiff {
+    pub cards_in: BTreeMap<i32, u32>,
+    pub cards_out: BTreeMap<i32, u32>,
+}
+
+impl DeckDiff {
+    /// What changed going from `before` to `after`
+    pub fn new(before: &Deck, after: &Deck) -> Self {
+        let before = sorted_quantities(&before.mainboard);
+        let after = sorted_quantities(&after.mainboard);
+        Self {
+            cards_in: difference(&after, &before),
+            cards_out: difference(&before, &after),
+        }
+    }
+
+    /// True when the deck was not sideboarded
+    pub fn is_empty(&self) -> bool {
+        self.cards_in.is_empty() && self.cards_out.is_empty()
+    }
+}
+
+/// How often a deck was sideboarded one way against opponents of one color identity,
+/// and how those games went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SideboardPlan {
+    pub opponent_identity: String,
+    pub diff: DeckDiff,
+    pub games: u32,
+    pub wins: u32,
+    /// games that neither side won, or that have no result, are in neither `wins` nor `losses`
+    pub losses: u32,
+}
+
+/// Copies in `from` beyond those in `to`
+fn difference(from: &BTreeMap<i32, u32>, to: &BTreeMap<i32, u32>) -> BTreeMap<i32, u32> {
+    from.iter()
+        .filter_map(|(grp_id, quantity)| {
+            let extra = quantity.saturating_sub(to.get(grp_id).copied().unwrap_or(0));
+            (extra > 0).then_some((*grp_id, extra))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deck_diff() {
+        let game_1 = Deck::new(String::new(), 1, vec![1, 1, 2, 2, 3], vec![4, 4, 5]);
+        let game_2 = Deck::new(String::new(), 2, vec![1, 1, 2, 4, 4], vec![2, 3, 5]);
+        let diff = DeckDiff::new(&game_1, &game_2);
+        assert_eq!(diff.cards_in, BTreeMap::from([(4, 2)]));
+        assert_eq!(diff.cards_out, BTreeMap::from([(2, 1), (3, 1)]));
+        assert!(!diff.is_empty());
+        assert!(DeckDiff::new(&game_1, &game_1).is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cards::{CardDbEntry, CardsDatabase};
+use crate::models::deck::Deck;
+
+/// Mana values from this one up share the last bucket of the curve
+pub const MAX_CURVE_BUCKET: u16 = 7;
+
+const COLORS: [char; 5] = ['W', 'U', 'B', 'R', 'G'];
+
+/// What a deck's mainboard asks of its mana. Double faced and adventure cards count as
+/// their front face, except that spells with a land on the back also count as color sources
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeckProfile {
+    pub cards: u32,
+    pub lands: u32,
+    /// spells with a land on the back, e.g. Shatterskull Smashing
+    pub modal_lands: u32,
+    pub creatures: u32,
+    pub noncreature_spells: u32,
+    /// nonland cards per mana value, from 0 to `MAX_CURVE_BUCKET` and up
+    pub mana_curve: BTreeMap<u16, u32>,
+    /// over nonland cards
+    pub average_mana_value: f64,
+    /// in WUBRG order, only colors the deck needs or can make
+    pub colors: Vec<ColorRequirement>,
+    /// cards missing from the cards database, left out of everything else
+    pub unknown_cards: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorRequirement {
+    pub color: char,
+    /// colored mana symbols in the costs of nonland cards, hybrid symbols counting for each color
+    pub pips: u32,
+    /// lands and modal lands whose color identity includes the color
+    pub sources: u32,
+}
+
+impl DeckProfile {
+    pub fn new(deck: &Deck, cards_db: &CardsDatabase) -> Self {
+        let mut profile = Self {
+            mana_curve: (0..=MAX_CURVE_BUCKET).map(|bucket| (bucket, 0)).collect(),
+            ..Self::default()
+        };
+        let mut pips = [0; COLORS.len()];
+        let mut sources = [0; COLORS.len()];
+        let mut total_mana_value = 0.0;
+
+        for grp_id in &deck.mainboard {
+            profile.cards += 1;
+            let Some(card) = cards_db.get(grp_id) else {
+                profile.unknown_cards += 1;
+                continue;
+            };
+            let type_line = front_type_line(card);
+            let is_land = type_line.contains("Land");
+            if is_land || has_land_back(card) {
+                for (i, color) in COLORS.iter().enumerate() {
+                    if card.color_identity.iter().any(|c| c.starts_with(*color)) {
+                        sources[i] += 1;
+                    }
+                }
+            }
+            if is_land {
+                profile.lands += 1;
+                continue;
+            }
+
+            if has_land_back(card) {
+                profile.modal_lands += 1;
+            }
+            if type_line.contains("Creature") {
+                profile.creatures += 1;
+            } else {
+                profile.noncreature_spells += 1;
+            }
+            let bucket = (0..=MAX_CURVE_BUCKET)
+                .rev()
+                .find(|bucket| card.cmc >= f32::from(*bucket))
+                .unwrap_or(0);
+            *profile.mana_curve.entry(bucket).or_insert(0) += 1;
+            total_mana_value += f64::from(card.cmc);
+            for symbol in mana_symbols(front_mana_cost(card)) {
+                for (i, color) in COLORS.iter().enumerate() {
+                    if symbol.contains(*color) {
+                        pips[i] += 1;
+                    }
+                }
+            }
+        }
+
+        let spells = profile.creatures + profile.noncreature_spells;
+        if spells > 0 {
+            profile.average_mana_value = total_mana_value / f64::from(spells);
+        }
+        profile.colors = COLORS
+            .iter()
+            .zip(pips.into_iter().zip(sources))
+            .filter(|(_, (pips, sources))| *pips > 0 || *sources > 0)
+            .map(|(color, (pips, sources))| ColorRequirement {
+                color: *color,
+                pips,
+                sources,
+            })
+            .collect();
+        profile
+    }
+}
+
+/// Split cards are one card with both halves' types and costs, every other card
+/// with faces is its front face until it is cast or played
+fn is_split(card: &CardDbEntry) -> bool {
+    matches!(card.layout.as_str(), "split" | "aftermath")
+}
+
+fn front_type_line(card: &CardDbEntry) -> &str {
+    match card.card_faces.as_deref() {
+        Some([front, ..]) if !is_split(card) => &front.type_line,
+        _ => &card.type_line,
+    }
+}
+
+fn front_mana_cost(card: &CardDbEntry) -> &str {
+    let mana_cost = match card.card_faces.as_deref() {
+        Some([front, ..]) if !is_split(card) => front.mana_cost.as_deref(),
+        _ => card.mana_cost.as_deref(),
+    };
+    mana_cost.unwrap_or_default()
+}
+
+fn has_land_back(card: &CardDbEntry) -> bool {
+    card.layout == "modal_dfc"
+        && card
+            .card_faces
+            .as_deref()
+            .and_then(|faces| faces.get(1))
+            .is_some_and(|back| back.type_line.contains("Land"))
+}
+
+/// "{1}{R/G}{R}" -> "1", "R/G", "R"
+fn mana_symbols(mana_cost: &str) -> impl Iterator<Item = &str> {
+    mana_cost
+        .split(['{', '}'])
+        .filter(|symbol| !symbol.is_empty() && !symbol.contains("//"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards_db() -> anyhow::Result<CardsDatabase> {
+        let card =
+            |id: i32, name: &str, cmc: f32, mana_cost: &str, type_line: &str, identity: &[&str]| {
+                serde_json::json!({
+                    "id": id, "set": "tst", "name": name, "lang": "en", "cmc": cmc,
+                    "mana_cost": mana_cost, "type_line": type_line, "layout": "normal",
+                    "color_identity": identity,
+                })
+            };
+        let mut mdfc = card(
+            5,
+            "Shatterskull Smashing // Shatterskull, the Hammer Pass",
+            2.0,
+            "",
+            "Sorcery // Land",
+            &["R"],
+        );
+        mdfc["layout"] = "modal_dfc".into();
+        mdfc["mana_cost"] = serde_json::Value::Null;
+        mdfc["card_faces"] = serde_json::json!([
+            {"name": "Shatterskull Smashing", "type_line": "Sorcery", "mana_cost": "{X}{R}{R}"},
+            {"name": "Shatterskull, the Hammer Pass", "type_line": "Land", "mana_cost": ""},
+        ]);
+        let mut adventure = card(
+            6,
+            "Bonecrusher Giant // Stomp",
+            3.0,
+            "{2}{R} // {1}{R}",
+            "Creature — Giant // Instant — Adventure",
+            &["R"],
+        );
+        adventure["layout"] = "adventure".into();
+        adventure["card_faces"] = serde_json::json!([
+            {"name": "Bonecrusher Giant", "type_line": "Creature — Giant", "mana_cost": "{2}{R}"},
+            {"name": "Stomp", "type_line": "Instant — Adventure", "mana_cost": "{1}{R}"},
+        ]);
+        Ok(CardsDatabase {
+            db: serde_json::from_value(serde_json::json!({
+                "1": card(1, "Mountain", 0.0, "", "Basic Land — Mountain", &["R"]),
+                "2": card(2, "Stomping Ground", 0.0, "", "Land — Mountain Forest", &["R", "G"]),
+                "3": card(3, "Lightning Strike", 2.0, "{1}{R}", "Instant", &["R"]),
+                "4": card(4, "Questing Druid", 2.0, "{1}{G}", "Creature — Human Druid", &["R", "G"]),
+                "5": mdfc,
+                "6": adventure,
+                "7": card(7, "Ulamog, the Ceaseless Hunger", 10.0, "{10}", "Legendary Creature — Eldrazi", &[]),
+            }))?,
+        })
+    }
+
+    #[test]
+    fn test_deck_profile() -> anyhow::Result<()> {
+        let mainboard = [vec![1; 4], vec![2; 2], vec![3, 3, 4, 5, 6, 7, 99]].concat();
+        let deck = Deck::new(String::new(), 1, mainboard, vec![]);
+        let profile = DeckProfile::new(&deck, &cards_db()?);
+
+        assert_eq!(profile.cards, 13);
+        assert_eq!(profile.lands, 6);
+        assert_eq!(profile.modal_lands, 1);
+        assert_eq!(profile.creatures, 3);
+        assert_eq!(profile.noncreature_spells, 3);
+        assert_eq!(profile.unknown_cards, 1);
+        assert_eq!(profile.mana_curve.get(&2), Some(&4));
+        assert_eq!(profile.mana_curve.get(&3), Some(&1));
+        assert_eq!(profile.mana_curve.get(&MAX_CURVE_BUCKET), Some(&1));
+        assert!((profile.average_mana_value - 3.5).abs() < f64::EPSILON);
+        assert_eq!(
+            profile.colors,
+            vec![
+                ColorRequirement {
+                    color: 'R',
+                    pips: 5,
+                    sources: 7,
+                },
+                ColorRequirement {
+                    color: 'G',
+                    pips: 1,
+                    sources: 2,
+                },
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cards::{CardsDatabase, LegalityTable};
+use crate::models::deck::Deck;
+use crate::mtga_events::gre::GameInfo;
+
+pub const DEFAULT_MAX_COPIES: u32 = 4;
+
+/// Cards whose rules text lifts the copy limit, with their own limit if they have one
+const COPY_LIMIT_EXCEPTIONS: [(&str, Option<u32>); 12] = [
+    ("Cid, Timeless Artificer", None),
+    ("Dragon's Approach", None),
+    ("Hare Apparent", None),
+    ("Nazgûl", Some(9)),
+    ("Persistent Petitioners", None),
+    ("Rat Colony", None),
+    ("Relentless Rats", None),
+    ("Seven Dwarves", Some(7)),
+    ("Shadowborn Apostle", None),
+    ("Slime Against Humanity", None),
+    ("Tempest Hawk", None),
+    ("Templar Knight", None),
+];
+
+/// Event id fragments and the scryfall format they are played in, checked in order
+const EVENT_FORMATS: [(&str, &str); 8] = [
+    ("historicbrawl", "brawl"),
+    ("historic_brawl", "brawl"),
+    ("brawl", "standardbrawl"),
+    ("alchemy", "alchemy"),
+    ("explorer", "explorer"),
+    ("historic", "historic"),
+    ("timeless", "timeless"),
+    ("standard", "standard"),
+];
+
+/// What a deck has to satisfy to be played in a match
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeckConstraints {
+    pub min_deck_size: u32,
+    pub max_deck_size: Option<u32>,
+    pub max_sideboard_size: Option<u32>,
+    /// copies of a card across mainboard and sideboard, None in limited
+    pub max_copies: Option<u32>,
+    /// scryfall's name for the format, None when legality is not checked
+    pub format: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeckViolation {
+    TooFewCards { count: u32, min: u32 },
+    TooManyCards { count: u32, max: u32 },
+    SideboardTooLarge { count: u32, max: u32 },
+    TooManyCopies { name: String, copies: u32, max: u32 },
+    NotLegal { name: String, format: String },
+}
+
+impl Display for DeckViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooFewCards { count, min } => {
+                write!(f, "{count} cards in the deck, at least {min} needed")
+            }
+            Self::TooManyCards { count, max } => {
+                write!(f, "{count} cards in the deck, at most {max} allowed")
+            }
+            Self::SideboardTooLarge { count, max } => {
+                write!(f, "{count} cards in the sideboard, at most {max} allowed")
+            }
+            Self::TooManyCopies { name, copies, max } => {
+                write!(f, "{copies} copies of {name}, at most {max} allowed")
+            }
+            Self::NotLegal { name, format } => write!(f, "{name} is not legal in {format}"),
+        }
+    }
+}
+
+impl DeckConstraints {
+    /// From the game info MTGA sends with the first game, and the id of the event the match
+    /// was played in for its format. Sizes MTGA leaves at 0 are not limited
+    pub fn new(game_info: &GameInfo, event_id: Option<&str>) -> Self {
+        let limit = |size: i32| u32::try_from(size).ok().filter(|size| *size > 0);
+        let constraints = &game_info.deck_constraint_info;
+        let is_limited = game_info.super_format.contains("Limited");
+        let max_copies = if is_limited {
+            None
+        } else if game_info.variant.contains("Brawl") {
+            Some(1)
+        } else {
+            Some(DEFAULT_MAX_COPIES)
+        };
+        Self {
+            min_deck_size: limit(constraints.min_deck_size).unwrap_or_default(),
+            max_deck_size: limit(constraints.max_deck_size),
+            max_sideboard_size: limit(constraints.max_sideboard_size),
+            max_copies,
+            format: event_id
+                .filter(|_| !is_limited)
+                .and_then(legality_format)
+                .map(ToString::to_string),
+        }
+    }
+
+    /// Everything wrong with `deck`. Legality is only checked with a `legalities` table and
+    /// a format, and cards missing from either database are given the benefit of the doubt
+    pub fn validate(
+        &self,
+        deck: &Deck,
+        cards_db: &CardsDatabase,
+        legalities: Option<&LegalityTable>,
+    ) -> Vec<DeckViolation> {
+        let mut violations = Vec::new();
+        let count = |cards: &[i32]| u32::try_from(cards.len()).unwrap_or(u32::MAX);
+        let deck_size = count(&deck.mainboard);
+        if deck_size < self.min_deck_size {
+            violations.push(DeckViolation::TooFewCards {
+                count: deck_size,
+                min: self.min_deck_size,
+            });
+        }
+        if let Some(max) = self.max_deck_size.filter(|max| deck_size > *max) {
+            violations.push(DeckViolation::TooManyCards {
+                count: deck_size,
+                max,
+            });
+        }
+        let sideboard_size = count(&deck.sideboard);
+        if let Some(max) = self.max_sideboard_size.filter(|max| sideboard_size > *max) {
+            violations.push(DeckViolation::SideboardTooLarge {
+                count: sideboard_size,
+                max,
+            });
+        }
+
+        // printings of a card count together, so cards are told apart by name
+        let mut copies: BTreeMap<String, (i32, u32)> = BTreeMap::new();
+        for grp_id in deck.mainboard.iter().chain(&deck.sideboard) {
+            copies
+                .entry(cards_db.get_pretty_name_defaulted(grp_id))
+                .or_insert((*grp_id, 0))
+                .1 += 1;
+        }
+        for (name, (grp_id, copies)) in copies {
+            // cards missing from the database can't be told apart from basics or each other
+            let has_copy_limit = cards_db
+                .get(&grp_id)
+                .is_some_and(|card| !card.type_line.contains("Basic"));
+            if let Some(max) = self.max_copies.filter(|_| has_copy_limit) {
+                let max = match COPY_LIMIT_EXCEPTIONS
+                    .iter()
+                    .find(|(exception, _)| *exception == name)
+                {
+                    Some((_, limit)) => limit.unwrap_or(u32::MAX),
+                    None => max,
+                };
+                if copies > max {
+                    violations.push(DeckViolation::TooManyCopies {
+                        name: name.clone(),
+                        copies,
+                        max,
+                    });
+                }
+            }
+            if let (Some(legalities), Some(format)) = (legalities, &self.format) {
+                if legalities.is_legal(&grp_id, format) == Some(false) {
+                    violations.push(DeckViolation::NotLegal {
+                        name,
+                        format: format.clone(),
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// The scryfall format an event is played in, e.g. "explorer" for `Traditional_Explorer_Ranked`.
+/// Plain ladder events are standard
+pub fn legality_format(event_id: &str) -> Option<&'static str> {
+    let event_id = event_id.to_lowercase();
+    EVENT_FORMATS
+        .iter()
+        .find(|(fragment, _)| event_id.contains(fragment))
+        .map(|(_, format)| *format)
+        .or_else(|| event_id.contains("ladder").then_some("standard"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::gre::DeckConstraintInfo;
+
+    fn cards_db() -> anyhow::Result<CardsDatabase> {
+        let card = |id: i32, name: &str, type_line: &str| {
+            serde_json::json!({
+                "id": id, "set": "tst", "name": name, "lang": "en", "cmc": 1.0,
+                "type_line": type_line, "layout": "normal", "color_identity": [],
+            })
+        };
+        Ok(CardsDatabase {
+            db: serde_json::from_value(serde_json::json!({
+                "1": card(1, "Mountain", "Basic Land — Mountain"),
+                "2": card(2, "Lightning Bolt", "Instant"),
+                "3": card(3, "Lightning Bolt", "Instant"),
+                "4": card(4, "Relentless Rats", "Creature — Rat"),
+                "5": card(5, "Seven Dwarves", "Creature — Dwarf"),
+            }))?,
+        })
+    }
+
+    fn game_info(super_format: &str, min_deck_size: i32) -> GameInfo {
+        GameInfo {
+            deck_constraint_info: DeckConstraintInfo {
+                max_deck_size: 250,
+                max_sideboard_size: 15,
+                min_deck_size,
+            },
+            super_format: super_format.to_string(),
+            variant: "GameVariant_Normal".to_string(),
+            ..GameInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_deck_constraints() {
+        let constraints = DeckConstraints::new(
+            &game_info("SuperFormat_Constructed", 60),
+            Some("Traditional_Explorer_Ranked"),
+        );
+        assert_eq!(constraints.max_copies, Some(DEFAULT_MAX_COPIES));
+        assert_eq!(constraints.format.as_deref(), Some("explorer"));
+
+        let constraints = DeckConstraints::new(
+            &game_info("SuperFormat_Limited", 40),
+            Some("PremierDraft_OTJ"),
+        );
+        assert_eq!(constraints.min_deck_size, 40);
+        assert_eq!(constraints.max_copies, None);
+        assert_eq!(constraints.format, None);
+
+        assert_eq!(legality_format("Ladder"), Some("standard"));
+        assert_eq!(legality_format("Alchemy_Ladder"), Some("alchemy"));
+        assert_eq!(legality_format("PremierDraft_OTJ"), None);
+    }
+
+    #[test]
+    fn test_validate() -> anyhow::Result<()> {
+        let constraints =
+            DeckConstraints::new(&game_info("SuperFormat_Constructed", 60), Some("Ladder"));
+        let legalities = LegalityTable {
+            legalities: serde_json::from_value(serde_json::json!({
+                "2": {"standard": "not_legal"},
+                "3": {"standard": "not_legal"},
+                "4": {"standard": "legal"},
+            }))?,
+        };
+        // 3 bolts in the mainboard and 16 of another printing in an oversized sideboard,
+        // as many basics and rats as wanted, and no copy limit for cards missing from the database
+        let mainboard = [
+            vec![1; 20],
+            vec![2; 3],
+            vec![4; 20],
+            vec![5; 8],
+            vec![99; 9],
+        ]
+        .concat();
+        let deck = Deck::new(String::new(), 1, mainboard, vec![3; 16]);
+
+        let violations = constraints.validate(&deck, &cards_db()?, Some(&legalities));
+        assert_eq!(
+            violations,
+            vec![
+                DeckViolation::SideboardTooLarge { count: 16, max: 15 },
+                DeckViolation::TooManyCopies {
+                    name: "Lightning Bolt".to_string(),
+                    copies: 19,
+                    max: 4,
+                },
+                DeckViolation::NotLegal {
+                    name: "Lightning Bolt".to_string(),
+                    format: "standard".to_string(),
+                },
+                DeckViolation::TooManyCopies {
+                    name: "Seven Dwarves".to_string(),
+                    copies: 8,
+                    max: 7,
+                },
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "16 cards in the sideboard, at most 15 allowed"
+        );
+
+        let short = Deck::new(String::new(), 1, vec![1; 59], vec![]);
+        assert_eq!(
+            constraints.validate(&short, &cards_db()?, None),
+            vec![DeckViolation::TooFewCards { count: 59, min: 60 }]
+        );
+        Ok(())
+    }
+}
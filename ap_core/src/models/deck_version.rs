@@ -0,0 +1,145 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::deck::Deck;
+use crate::models::win_rate::WinRate;
+
+/// Decks at most this many mainboard cards apart are treated as edits of one another
+pub const MAX_LINEAGE_DISTANCE: u32 = 4;
+
+/// One exact decklist, identified by `Deck::fingerprint`. Edits of a deck are versions
+/// sharing a `lineage_id`, the fingerprint of the first version seen
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckVersion {
+    pub fingerprint: String,
+    /// MTGA's id for the deck, only known for decks seen in the lobby
+    pub deck_id: Option<String>,
+    pub name: Option<String>,
+    pub lineage_id: String,
+    /// the version this one was edited from
+    pub parent_fingerprint: Option<String>,
+    pub mainboard: Vec<i32>,
+    pub sideboard: Vec<i32>,
+    pub first_seen_at: DateTime<Utc>,
+}
+
+/// Every version of a deck and how they did together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeckLineage {
+    pub lineage_id: String,
+    /// name of the most recent version that has one
+    pub name: Option<String>,
+    /// oldest first
+    pub versions: Vec<DeckVersion>,
+    pub win_rate: WinRate,
+}
+
+impl DeckVersion {
+    pub fn deck(&self) -> Deck {
+        Deck::new(
+            self.name.clone().unwrap_or_default(),
+            0,
+            self.mainboard.clone(),
+            self.sideboard.clone(),
+        )
+    }
+
+    /// The version a new decklist was most likely edited from: the latest version with the
+    /// same MTGA deck id, or else the closest one within `MAX_LINEAGE_DISTANCE` cards,
+    /// preferring recent versions on ties
+    pub fn find_parent<'a>(
+        deck: &Deck,
+        deck_id: Option<&str>,
+        versions: &'a [DeckVersion],
+    ) -> Option<&'a DeckVersion> {
+        if let Some(deck_id) = deck_id {
+            let same_deck = versions
+                .iter()
+                .filter(|version| version.deck_id.as_deref() == Some(deck_id))
+                .max_by_key(|version| version.first_seen_at);
+            if same_deck.is_some() {
+                return same_deck;
+            }
+        }
+        versions
+            .iter()
+            .map(|version| (deck.distance(&version.deck()), version))
+            .filter(|(distance, _)| *distance <= MAX_LINEAGE_DISTANCE)
+            .min_by_key(|(distance, version)| (*distance, std::cmp::Reverse(version.first_seen_at)))
+            .map(|(_, version)| version)
+    }
+}
+
+impl DeckLineage {
+    /// The deck's name, or "Deck" and the start of its lineage id for decks never seen by name
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let short_id: String = self.lineage_id.chars().take(8).collect();
+            format!("Deck {short_id}")
+        })
+    }
+
+    /// The most recent version
+    pub fn latest(&self) -> Option<&DeckVersion> {
+        self.versions.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(
+        fingerprint: &str,
+        deck_id: Option<&str>,
+        mainboard: Vec<i32>,
+        day: u32,
+    ) -> DeckVersion {
+        DeckVersion {
+            fingerprint: fingerprint.to_string(),
+            deck_id: deck_id.map(ToString::to_string),
+            name: None,
+            lineage_id: fingerprint.to_string(),
+            parent_fingerprint: None,
+            mainboard,
+            sideboard: Vec::new(),
+            first_seen_at: DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::days(i64::from(day)),
+        }
+    }
+
+    fn deck(mainboard: Vec<i32>) -> Deck {
+        Deck::new(String::new(), 0, mainboard, Vec::new())
+    }
+
+    #[test]
+    fn test_find_parent_by_distance() {
+        let versions = vec![
+            version("a", None, vec![1, 1, 1, 1, 2, 2, 2, 2], 1),
+            version("b", None, vec![5, 5, 5, 5, 6, 6, 6, 6], 2),
+        ];
+        // a one card swap
+        let parent = DeckVersion::find_parent(&deck(vec![1, 1, 1, 1, 2, 2, 2, 3]), None, &versions);
+        assert_eq!(
+            parent.map(|version| version.fingerprint.as_str()),
+            Some("a")
+        );
+        // a different deck altogether
+        let parent = DeckVersion::find_parent(&deck(vec![7, 7, 7, 7, 8, 8, 8, 8]), None, &versions);
+        assert!(parent.is_none());
+    }
+
+    #[test]
+    fn test_find_parent_by_deck_id() {
+        let versions = vec![
+            version("a", Some("deck-1"), vec![1, 1, 1, 1], 1),
+            version("b", Some("deck-1"), vec![2, 2, 2, 2], 2),
+            version("c", None, vec![9, 9, 9, 9], 3),
+        ];
+        // same deck id wins over a closer decklist
+        let parent = DeckVersion::find_parent(&deck(vec![9, 9, 9, 9]), Some("deck-1"), &versions);
+        assert_eq!(
+            parent.map(|version| version.fingerprint.as_str()),
+            Some("b")
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
 
+/// MTGA reports a drawn game or match as won by team 0
+pub const NO_WINNING_TEAM: i32 = 0;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Builder)]
 pub struct MatchResult {
     pub match_id: String,
@@ -8,3 +11,9 @@ pub struct MatchResult {
     pub winning_team_id: i32,
     pub result_scope: String,
 }
+
+impl MatchResult {
+    pub fn is_draw(&self) -> bool {
+        self.winning_team_id == NO_WINNING_TEAM
+    }
+}
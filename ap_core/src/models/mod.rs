@@ -1,4 +1,11 @@
 pub mod deck;
+pub mod deck_diff;
+pub mod deck_profile;
+pub mod deck_validation;
+pub mod deck_version;
 pub mod match_result;
 pub mod mtga_match;
 pub mod mulligan;
+pub mod rank;
+pub mod turn_snapshot;
+pub mod win_rate;
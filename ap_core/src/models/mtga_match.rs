@@ -9,4 +9,13 @@ pub struct MTGAMatch {
     pub controller_player_name: String,
     pub opponent_player_name: String,
     pub created_at: DateTime<Utc>,
+    /// when the match server reported the match over
+    #[builder(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+    /// event id, e.g. `Traditional_Explorer_Ranked`
+    #[builder(default)]
+    pub format: Option<String>,
+    /// colors of every opponent card seen during the match, e.g. "BG"
+    #[builder(default)]
+    pub opponent_identity: Option<String>,
 }
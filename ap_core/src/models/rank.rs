@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::mtga_events::frontdoor::CombinedRankInfo;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rank {
+    pub season_ordinal: Option<i32>,
+    /// Bronze, Silver, Gold, Platinum, Diamond or Mythic
+    pub class: Option<String>,
+    /// 4 is the lowest tier of a class, 1 the highest. MTGA calls this "level"
+    pub tier: Option<i32>,
+    pub step: Option<i32>,
+    pub matches_won: i32,
+    pub matches_lost: i32,
+    pub matches_drawn: i32,
+    pub mythic_percentile: Option<f64>,
+    pub mythic_placement: Option<i32>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RankSnapshot {
+    pub observed_at: DateTime<Utc>,
+    pub constructed: Rank,
+    pub limited: Rank,
+}
+
+/// The closest rank snapshots taken before and after a match
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchRanks {
+    pub before: Option<RankSnapshot>,
+    pub after: Option<RankSnapshot>,
+}
+
+impl Rank {
+    pub fn is_mythic(&self) -> bool {
+        self.class.as_deref() == Some("Mythic")
+    }
+}
+
+impl Display for Rank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let class = self.class.as_deref().unwrap_or("Unranked");
+        if self.is_mythic() {
+            match (self.mythic_placement, self.mythic_percentile) {
+                (Some(placement), _) if placement > 0 => write!(f, "{class} #{placement}"),
+                (_, Some(percentile)) => write!(f, "{class} {percentile}%"),
+                _ => write!(f, "{class}"),
+            }
+        } else {
+            match (self.tier, self.step) {
+                (Some(tier), Some(step)) => write!(f, "{class} {tier} (step {step})"),
+                (Some(tier), None) => write!(f, "{class} {tier}"),
+                _ => write!(f, "{class}"),
+            }
+        }
+    }
+}
+
+impl RankSnapshot {
+    pub fn new(rank_info: &CombinedRankInfo, observed_at: DateTime<Utc>) -> Self {
+        Self {
+            observed_at,
+            constructed: Rank {
+                season_ordinal: rank_info.constructed_season_ordinal,
+                class: rank_info.constructed_class.clone(),
+                tier: rank_info.constructed_level,
+                step: rank_info.constructed_step,
+                matches_won: rank_info.constructed_matches_won,
+                matches_lost: rank_info.constructed_matches_lost,
+                matches_drawn: rank_info.constructed_matches_drawn,
+                mythic_percentile: rank_info.constructed_percentile,
+                mythic_placement: rank_info.constructed_leaderboard_place,
+            },
+            limited: Rank {
+                season_ordinal: rank_info.limited_season_ordinal,
+                class: rank_info.limited_class.clone(),
+                tier: rank_info.limited_level,
+                step: rank_info.limited_step,
+                matches_won: rank_info.limited_matches_won,
+                matches_lost: rank_info.limited_matches_lost,
+                matches_drawn: rank_info.limited_matches_drawn,
+                mythic_percentile: rank_info.limited_percentile,
+                mythic_placement: rank_info.limited_leaderboard_place,
+            },
+        }
+    }
+}
+
+impl MatchRanks {
+    /// Picks the latest snapshot at or before `match_start` and the earliest one after
+    /// `match_end`; snapshots taken during the match are neither
+    pub fn around<'a>(
+        snapshots: impl IntoIterator<Item = &'a RankSnapshot>,
+        match_start: DateTime<Utc>,
+        match_end: DateTime<Utc>,
+    ) -> Self {
+        let mut match_ranks = Self::default();
+        for snapshot in snapshots {
+            if snapshot.observed_at <= match_start {
+                if match_ranks
+                    .before
+                    .as_ref()
+                    .is_none_or(|before| before.observed_at <= snapshot.observed_at)
+                {
+                    match_ranks.before = Some(snapshot.clone());
+                }
+            } else if snapshot.observed_at > match_end
+                && match_ranks
+                    .after
+                    .as_ref()
+                    .is_none_or(|after| after.observed_at > snapshot.observed_at)
+            {
+                match_ranks.after = Some(snapshot.clone());
+            }
+        }
+        match_ranks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn snapshot(hour: u32, class: &str) -> RankSnapshot {
+        RankSnapshot {
+            observed_at: Utc
+                .with_ymd_and_hms(2026, 10, 17, hour, 0, 0)
+                .single()
+                .unwrap_or_default(),
+            constructed: Rank {
+                class: Some(class.to_string()),
+                ..Rank::default()
+            },
+            limited: Rank::default(),
+        }
+    }
+
+    #[test]
+    fn test_rank_display() {
+        let gold = Rank {
+            class: Some("Gold".to_string()),
+            tier: Some(2),
+            step: Some(3),
+            ..Rank::default()
+        };
+        assert_eq!(gold.to_string(), "Gold 2 (step 3)");
+        let mythic = Rank {
+            class: Some("Mythic".to_string()),
+            mythic_percentile: Some(93.5),
+            mythic_placement: Some(0),
+            ..Rank::default()
+        };
+        assert_eq!(mythic.to_string(), "Mythic 93.5%");
+        assert_eq!(Rank::default().to_string(), "Unranked");
+    }
+
+    #[test]
+    fn test_from_combined_rank_info() {
+        let rank_info = CombinedRankInfo {
+            constructed_class: Some("Gold".to_string()),
+            constructed_level: Some(2),
+            constructed_step: Some(3),
+            limited_class: Some("Mythic".to_string()),
+            limited_leaderboard_place: Some(120),
+            ..CombinedRankInfo::default()
+        };
+        let snapshot = RankSnapshot::new(&rank_info, Utc::now());
+        assert_eq!(snapshot.constructed.tier, Some(2));
+        assert_eq!(snapshot.limited.to_string(), "Mythic #120");
+    }
+
+    #[test]
+    fn test_match_ranks_around() {
+        let snapshots = [
+            snapshot(10, "Silver"),
+            snapshot(14, "Platinum"),
+            snapshot(12, "Gold"),
+            snapshot(16, "Diamond"),
+            snapshot(13, "Gold"),
+        ];
+        let match_start = snapshot(12, "").observed_at;
+        let match_end = snapshot(13, "").observed_at;
+        let match_ranks = MatchRanks::around(&snapshots, match_start, match_end);
+        assert_eq!(
+            match_ranks.before.and_then(|s| s.constructed.class),
+            Some("Gold".to_string())
+        );
+        assert_eq!(
+            match_ranks.after.and_then(|s| s.constructed.class),
+            Some("Platinum".to_string())
+        );
+    }
+}
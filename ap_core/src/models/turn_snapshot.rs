@@ -0,0 +1,216 @@
+use serde::Serialize;
+
+use crate::game_state::GameState;
+use crate::mtga_events::gre::GameObject;
+use crate::mtga_events::primitives::{Phase, Step, ZoneType};
+
+/// The board at the end of one phase of a turn
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TurnSnapshot {
+    pub game_number: i32,
+    pub turn_number: i32,
+    pub phase: Option<Phase>,
+    pub step: Option<Step>,
+    pub active_player: Option<i32>,
+    pub game_state_id: i32,
+    pub players: Vec<PlayerBoard>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerBoard {
+    pub seat_id: i32,
+    pub life_total: i32,
+    pub hand_size: usize,
+    pub library_size: usize,
+    pub battlefield: Vec<Permanent>,
+    /// grp ids
+    pub graveyard: Vec<i32>,
+    /// grp ids
+    pub exile: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Permanent {
+    pub grp_id: i32,
+    pub is_tapped: bool,
+    pub power: Option<i32>,
+    pub toughness: Option<i32>,
+}
+
+impl From<&GameObject> for Permanent {
+    fn from(game_object: &GameObject) -> Self {
+        Self {
+            grp_id: game_object.grp_id,
+            is_tapped: game_object.is_tapped.unwrap_or(false),
+            power: game_object.power.as_ref().map(|power| power.value),
+            toughness: game_object
+                .toughness
+                .as_ref()
+                .map(|toughness| toughness.value),
+        }
+    }
+}
+
+impl TurnSnapshot {
+    /// None for states before the first turn, e.g. while mulliganing
+    pub fn new(state: &GameState) -> Option<Self> {
+        let turn_info = state.turn_info.as_ref()?;
+        Some(Self {
+            game_number: state.game_number().unwrap_or(1),
+            turn_number: turn_info.turn_number?,
+            phase: turn_info.phase,
+            step: turn_info.step,
+            active_player: turn_info.active_player,
+            game_state_id: state.game_state_id,
+            players: state
+                .players
+                .values()
+                .map(|player| PlayerBoard::new(state, player.system_seat_number))
+                .collect(),
+        })
+    }
+
+    /// Whether both snapshots are of the same phase of the same turn
+    pub fn same_phase(&self, other: &Self) -> bool {
+        self.game_number == other.game_number
+            && self.turn_number == other.turn_number
+            && self.phase == other.phase
+    }
+}
+
+impl PlayerBoard {
+    fn new(state: &GameState, seat_id: i32) -> Self {
+        let zone_size = |zone_type| {
+            state
+                .zone(zone_type, Some(seat_id))
+                .map_or(0, |zone| zone.object_instance_ids.len())
+        };
+        Self {
+            seat_id,
+            life_total: state.life_total(seat_id).unwrap_or_default(),
+            hand_size: zone_size(ZoneType::Hand),
+            library_size: zone_size(ZoneType::Library),
+            battlefield: state
+                .battlefield(seat_id)
+                .into_iter()
+                .map(Permanent::from)
+                .collect(),
+            graveyard: owned_cards(state, ZoneType::Graveyard, seat_id),
+            exile: owned_cards(state, ZoneType::Exile, seat_id),
+        }
+    }
+}
+
+/// grp ids of the cards owned by `seat_id` in zones of a type,
+/// whether the zone belongs to the player or is shared
+fn owned_cards(state: &GameState, zone_type: ZoneType, seat_id: i32) -> Vec<i32> {
+    state
+        .zones
+        .values()
+        .filter(|zone| {
+            zone.type_field == zone_type
+                && zone
+                    .owner_seat_id
+                    .is_none_or(|owner_seat_id| owner_seat_id == seat_id)
+        })
+        .flat_map(|zone| state.zone_objects(zone))
+        .filter(|game_object| game_object.owner_seat_id == seat_id)
+        .map(|game_object| game_object.grp_id)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::gre::GameInfo;
+    use crate::mtga_events::primitives::{Player, Power, Toughness, TurnInfo, Zone};
+
+    fn zone(zone_id: i32, type_field: ZoneType, owner: Option<i32>, ids: Vec<i32>) -> Zone {
+        Zone {
+            zone_id,
+            type_field,
+            owner_seat_id: owner,
+            object_instance_ids: ids,
+            ..Zone::default()
+        }
+    }
+
+    #[test]
+    fn test_turn_snapshot() {
+        let mut state = GameState {
+            game_state_id: 12,
+            game_info: Some(GameInfo {
+                game_number: 2,
+                ..GameInfo::default()
+            }),
+            turn_info: Some(TurnInfo {
+                turn_number: Some(3),
+                phase: Some(Phase::Combat),
+                active_player: Some(1),
+                ..TurnInfo::default()
+            }),
+            ..GameState::default()
+        };
+        state.players.insert(
+            1,
+            Player {
+                system_seat_number: 1,
+                life_total: 18,
+                ..Player::default()
+            },
+        );
+        for zone in [
+            zone(28, ZoneType::Battlefield, None, vec![200]),
+            zone(29, ZoneType::Exile, None, vec![201]),
+            zone(31, ZoneType::Hand, Some(1), vec![300, 301]),
+            zone(32, ZoneType::Library, Some(1), vec![302, 303, 304]),
+            zone(33, ZoneType::Graveyard, Some(1), vec![202]),
+        ] {
+            state.zones.insert(zone.zone_id, zone);
+        }
+        for (instance_id, grp_id) in [(200, 7000), (201, 7001), (202, 7002)] {
+            state.game_objects.insert(
+                instance_id,
+                GameObject {
+                    instance_id,
+                    grp_id,
+                    owner_seat_id: 1,
+                    is_tapped: Some(instance_id == 200),
+                    power: Some(Power { value: 2 }),
+                    toughness: Some(Toughness { value: 3 }),
+                    ..GameObject::default()
+                },
+            );
+        }
+
+        let snapshot = TurnSnapshot::new(&state);
+        let expected = TurnSnapshot {
+            game_number: 2,
+            turn_number: 3,
+            phase: Some(Phase::Combat),
+            step: None,
+            active_player: Some(1),
+            game_state_id: 12,
+            players: vec![PlayerBoard {
+                seat_id: 1,
+                life_total: 18,
+                hand_size: 2,
+                library_size: 3,
+                battlefield: vec![Permanent {
+                    grp_id: 7000,
+                    is_tapped: true,
+                    power: Some(2),
+                    toughness: Some(3),
+                }],
+                graveyard: vec![7002],
+                exile: vec![7001],
+            }],
+        };
+        assert_eq!(snapshot, Some(expected));
+    }
+
+    #[test]
+    fn test_no_snapshot_before_first_turn() {
+        assert_eq!(TurnSnapshot::new(&GameState::default()), None);
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// z score of a two-sided 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// Wins out of a number of matches or games, for one value of whatever they were grouped by.
+/// Draws are counted on their own and left out of `win_rate` and its interval, which are
+/// over decided results only: 2 wins, 1 loss and 1 draw is a 66.7% win rate
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WinRate {
+    /// the group, e.g. a format, "Play" or "Draw", or a number of mulligans
+    pub label: String,
+    pub wins: u32,
+    pub losses: u32,
+    #[serde(default)]
+    pub draws: u32,
+    pub win_rate: f64,
+    /// 95% Wilson score interval around `win_rate`
+    pub lower_bound: f64,
+    pub upper_bound: f64,
+}
+
+/// Restricts which matches a win rate query looks at. The default includes every match
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WinRateFilter {
+    /// matches that started at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// matches that started before this time
+    pub until: Option<DateTime<Utc>>,
+    /// event id, e.g. `Traditional_Explorer_Ranked`
+    pub format: Option<String>,
+}
+
+impl WinRate {
+    pub fn new(label: String, wins: u32, losses: u32, draws: u32) -> Self {
+        let (lower_bound, upper_bound) = wilson_interval(wins, wins + losses);
+        let total = f64::from(wins + losses);
+        Self {
+            label,
+            wins,
+            losses,
+            draws,
+            win_rate: if total > 0.0 {
+                f64::from(wins) / total
+            } else {
+                0.0
+            },
+            lower_bound,
+            upper_bound,
+        }
+    }
+
+    /// Every result, draws included
+    pub fn total(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    /// e.g. "7-3", or "7-3-1" with a draw
+    pub fn record(&self) -> String {
+        if self.draws > 0 {
+            format!("{}-{}-{}", self.wins, self.losses, self.draws)
+        } else {
+            format!("{}-{}", self.wins, self.losses)
+        }
+    }
+}
+
+/// Runs of consecutive match wins or losses. Draws are left out, neither extending
+/// nor breaking a streak
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Streaks {
+    /// the streak the most recent match is part of, None if there are no matches
+    pub current: Option<Streak>,
+    pub longest_win: u32,
+    pub longest_loss: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Streak {
+    pub won: bool,
+    pub length: u32,
+}
+
+impl Streaks {
+    /// From match outcomes, oldest first
+    pub fn new(outcomes: impl IntoIterator<Item = bool>) -> Self {
+        let mut streaks = Self::default();
+        for won in outcomes {
+            let current = match streaks.current {
+                Some(streak) if streak.won == won => Streak {
+                    won,
+                    length: streak.length + 1,
+                },
+                _ => Streak { won, length: 1 },
+            };
+            let longest = if won {
+                &mut streaks.longest_win
+            } else {
+                &mut streaks.longest_loss
+            };
+            *longest = (*longest).max(current.length);
+            streaks.current = Some(current);
+        }
+        streaks
+    }
+}
+
+/// Unlike the normal approximation, stays within [0, 1] and behaves for small samples
+fn wilson_interval(wins: u32, total: u32) -> (f64, f64) {
+    if total == 0 {
+        return (0.0, 1.0);
+    }
+    let n = f64::from(total);
+    let p = f64::from(wins) / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let win_rate = WinRate::new("Play".to_string(), 7, 3, 0);
+        assert_close(win_rate.win_rate, 0.7);
+        assert_close(win_rate.lower_bound, 0.397);
+        assert_close(win_rate.upper_bound, 0.892);
+        assert_eq!(win_rate.total(), 10);
+        assert_eq!(win_rate.record(), "7-3");
+    }
+
+    #[test]
+    fn test_draws_left_out_of_win_rate() {
+        let win_rate = WinRate::new("Play".to_string(), 7, 3, 2);
+        assert_close(win_rate.win_rate, 0.7);
+        assert_close(win_rate.lower_bound, 0.397);
+        assert_eq!(win_rate.total(), 12);
+        assert_eq!(win_rate.record(), "7-3-2");
+    }
+
+    #[test]
+    fn test_wilson_interval_edges() {
+        let undefeated = WinRate::new("Play".to_string(), 3, 0, 0);
+        assert_close(undefeated.upper_bound, 1.0);
+        assert!(undefeated.lower_bound > 0.0);
+
+        let empty = WinRate::new("Draw".to_string(), 0, 0, 0);
+        assert_close(empty.win_rate, 0.0);
+        assert_close(empty.lower_bound, 0.0);
+        assert_close(empty.upper_bound, 1.0);
+    }
+
+    #[test]
+    fn test_streaks() {
+        let streaks = Streaks::new([true, true, true, false, true, false, false]);
+        assert_eq!(
+            streaks.current,
+            Some(Streak {
+                won: false,
+                length: 2
+            })
+        );
+        assert_eq!(streaks.longest_win, 3);
+        assert_eq!(streaks.longest_loss, 2);
+        assert_eq!(Streaks::new([]), Streaks::default());
+    }
+}
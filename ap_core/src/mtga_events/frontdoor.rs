@@ -0,0 +1,775 @@
+use std::collections::HashMap;
+
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::log_envelope::{Direction, LogEnvelope};
+use crate::processor::{from_json_str, DeserializeError};
+
+//
+// "Front door" is the MTGA lobby service: everything outside of a game (joining events,
+// submitting decks, ranks, drafts, quests...). The client logs each call as
+//
+// `==> EventJoin {"id":"<uuid>","request":"<json string>"}`
+// `<== EventJoin(<uuid>)` followed by the response json
+//
+// so the method name only lives in the log header, see `LogEnvelope`
+//
+
+/// business events share the request shape but are handled by `business.rs`
+pub const BUSINESS_EVENT_METHOD: &str = "LogBusinessEvents";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FrontDoorEvent {
+    Request(FrontDoorRequest),
+    Response(FrontDoorResponse),
+    Notification(FrontDoorNotification),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrontDoorRequest {
+    pub id: String,
+    pub method: String,
+    pub payload: FrontDoorRequestPayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrontDoorResponse {
+    /// id of the request this answers, taken from the log header
+    pub request_id: Option<String>,
+    pub method: String,
+    pub payload: FrontDoorResponsePayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrontDoorNotification {
+    pub method: String,
+    pub payload: FrontDoorNotificationPayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FrontDoorRequestPayload {
+    EventJoin(EventJoinRequest),
+    EventSetDeck(EventSetDeckRequest),
+    DeckUpsertDeck(DeckUpsertDeckRequest),
+    EventGetCourses,
+    RankGetCombinedRankInfo,
+    GraphGetGraphState(GraphGetGraphStateRequest),
+    BotDraftDraftStatus(BotDraftStatusRequest),
+    BotDraftDraftPick(BotDraftPickRequest),
+    EventPlayerDraftMakePick(PlayerDraftMakePickRequest),
+    Other(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FrontDoorResponsePayload {
+    EventJoin(CourseResponse),
+    EventSetDeck(CourseResponse),
+    DeckUpsertDeck(DeckSummary),
+    EventGetCourses(EventGetCoursesResponse),
+    RankGetCombinedRankInfo(CombinedRankInfo),
+    GraphGetGraphState(GraphState),
+    BotDraftDraftStatus(BotDraftResponse),
+    BotDraftDraftPick(BotDraftResponse),
+    EventPlayerDraftMakePick(PlayerDraftMakePickResponse),
+    Other(Value),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FrontDoorNotificationPayload {
+    DraftNotify(DraftNotification),
+    Other(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    EventJoin,
+    EventSetDeck,
+    DeckUpsertDeck,
+    EventGetCourses,
+    RankGetCombinedRankInfo,
+    GraphGetGraphState,
+    BotDraftDraftStatus,
+    BotDraftDraftPick,
+    EventPlayerDraftMakePick,
+    DraftNotify,
+    Other,
+}
+
+impl From<&str> for Method {
+    fn from(method: &str) -> Self {
+        match method {
+            "EventJoin" => Self::EventJoin,
+            "EventSetDeck" | "EventSetDeckV2" => Self::EventSetDeck,
+            "DeckUpsertDeck" | "DeckUpsertDeckV2" => Self::DeckUpsertDeck,
+            "EventGetCourses" | "EventGetCoursesV2" => Self::EventGetCourses,
+            "RankGetCombinedRankInfo" => Self::RankGetCombinedRankInfo,
+            "GraphGetGraphState" => Self::GraphGetGraphState,
+            "BotDraftDraftStatus" => Self::BotDraftDraftStatus,
+            "BotDraftDraftPick" => Self::BotDraftDraftPick,
+            "EventPlayerDraftMakePick" => Self::EventPlayerDraftMakePick,
+            "Draft.Notify" => Self::DraftNotify,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl FrontDoorEvent {
+    /// Builds a front door event from a json blob and the log header it was found under.
+    /// Returns `None` when the envelope does not describe an API call
+    ///
+    /// # Errors
+    ///
+    /// Errors when the json does not match the shape expected for the method
+    pub fn from_envelope(
+        envelope: &LogEnvelope,
+        event: &str,
+    ) -> Result<Option<Self>, DeserializeError> {
+        let Some(method) = envelope.method.clone() else {
+            return Ok(None);
+        };
+        let front_door_event = match envelope.direction {
+            Direction::Request => Self::Request(FrontDoorRequest::parse(method, event)?),
+            Direction::Response => Self::Response(FrontDoorResponse::parse(
+                method,
+                envelope.request_id.clone(),
+                event,
+            )?),
+            Direction::Notification => {
+                Self::Notification(FrontDoorNotification::parse(method, event)?)
+            }
+            Direction::None => return Ok(None),
+        };
+        Ok(Some(front_door_event))
+    }
+
+    pub fn method(&self) -> &str {
+        match self {
+            Self::Request(request) => &request.method,
+            Self::Response(response) => &response.method,
+            Self::Notification(notification) => &notification.method,
+        }
+    }
+}
+
+/// Decodes a typed payload, or gives `None` so the caller keeps the plain json.
+/// An error body or a schema change then only costs the typed view of that one payload
+fn decode<T: DeserializeOwned>(method: &str, value: &Value) -> Option<T> {
+    match serde_path_to_error::deserialize(value) {
+        Ok(payload) => Some(payload),
+        Err(e) => {
+            debug!("{method} payload does not decode, keeping it as json: {e}");
+            None
+        }
+    }
+}
+
+impl FrontDoorRequest {
+    /// # Errors
+    ///
+    /// Errors when the outer json is not an `{id, request}` object, or the request is not json.
+    /// A request that does not match the method's request type is kept as `Other`
+    pub fn parse(method: String, event: &str) -> Result<Self, DeserializeError> {
+        let mut outer: Value = from_json_str(event)?;
+        let id = outer["id"].as_str().unwrap_or_default().to_string();
+        // the request body is usually a json document encoded as a string
+        let request = match outer["request"].take() {
+            Value::String(request) => from_json_str(&request)?,
+            request => request,
+        };
+        let payload = match Method::from(method.as_str()) {
+            Method::EventJoin => decode(&method, &request).map(FrontDoorRequestPayload::EventJoin),
+            Method::EventSetDeck => {
+                decode(&method, &request).map(FrontDoorRequestPayload::EventSetDeck)
+            }
+            Method::DeckUpsertDeck => {
+                decode(&method, &request).map(FrontDoorRequestPayload::DeckUpsertDeck)
+            }
+            Method::EventGetCourses => Some(FrontDoorRequestPayload::EventGetCourses),
+            Method::RankGetCombinedRankInfo => {
+                Some(FrontDoorRequestPayload::RankGetCombinedRankInfo)
+            }
+            Method::GraphGetGraphState => {
+                decode(&method, &request).map(FrontDoorRequestPayload::GraphGetGraphState)
+            }
+            Method::BotDraftDraftStatus => {
+                decode(&method, &request).map(FrontDoorRequestPayload::BotDraftDraftStatus)
+            }
+            Method::BotDraftDraftPick => {
+                decode(&method, &request).map(FrontDoorRequestPayload::BotDraftDraftPick)
+            }
+            Method::EventPlayerDraftMakePick => {
+                decode(&method, &request).map(FrontDoorRequestPayload::EventPlayerDraftMakePick)
+            }
+            Method::DraftNotify | Method::Other => None,
+        }
+        .unwrap_or(FrontDoorRequestPayload::Other(request));
+        Ok(Self {
+            id,
+            method,
+            payload,
+        })
+    }
+}
+
+impl FrontDoorResponse {
+    /// # Errors
+    ///
+    /// Errors when the response is not json.
+    /// A response that does not match the method's response type is kept as `Other`
+    pub fn parse(
+        method: String,
+        request_id: Option<String>,
+        event: &str,
+    ) -> Result<Self, DeserializeError> {
+        let response: Value = from_json_str(event)?;
+        let payload =
+            match Method::from(method.as_str()) {
+                Method::EventJoin => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::EventJoin)
+                }
+                Method::EventSetDeck => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::EventSetDeck)
+                }
+                Method::DeckUpsertDeck => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::DeckUpsertDeck)
+                }
+                Method::EventGetCourses => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::EventGetCourses)
+                }
+                Method::RankGetCombinedRankInfo => decode(&method, &response)
+                    .map(FrontDoorResponsePayload::RankGetCombinedRankInfo),
+                Method::GraphGetGraphState => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::GraphGetGraphState)
+                }
+                Method::BotDraftDraftStatus => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::BotDraftDraftStatus)
+                }
+                Method::BotDraftDraftPick => {
+                    decode(&method, &response).map(FrontDoorResponsePayload::BotDraftDraftPick)
+                }
+                Method::EventPlayerDraftMakePick => decode(&method, &response)
+                    .map(FrontDoorResponsePayload::EventPlayerDraftMakePick),
+                Method::DraftNotify | Method::Other => None,
+            }
+            .unwrap_or(FrontDoorResponsePayload::Other(response));
+        Ok(Self {
+            request_id,
+            method,
+            payload,
+        })
+    }
+}
+
+impl FrontDoorNotification {
+    /// # Errors
+    ///
+    /// Errors when the notification is not json.
+    /// A notification that does not match the method's notification type is kept as `Other`
+    pub fn parse(method: String, event: &str) -> Result<Self, DeserializeError> {
+        let notification: Value = from_json_str(event)?;
+        let payload = match Method::from(method.as_str()) {
+            Method::DraftNotify => {
+                decode(&method, &notification).map(FrontDoorNotificationPayload::DraftNotify)
+            }
+            _ => None,
+        }
+        .unwrap_or(FrontDoorNotificationPayload::Other(notification));
+        Ok(Self { method, payload })
+    }
+}
+
+/// Some payloads are json documents encoded as strings inside the outer json
+fn json_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(json) => serde_json::from_str(&json).map_err(D::Error::custom),
+        value => serde_json::from_value(value).map_err(D::Error::custom),
+    }
+}
+
+/// Card ids in draft messages are sometimes numbers, sometimes numeric strings
+fn grp_ids<'de, D>(deserializer: D) -> Result<Vec<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values: Vec<Value> = Deserialize::deserialize(deserializer)?;
+    values
+        .iter()
+        .map(|value| match value {
+            Value::String(id) => id.trim().parse().map_err(D::Error::custom),
+            value => value
+                .as_i64()
+                .and_then(|id| i32::try_from(id).ok())
+                .ok_or_else(|| D::Error::custom(format!("invalid card id: {value}"))),
+        })
+        .collect()
+}
+
+/// `"90123,90124"` comma separated lists, used by `Draft.Notify`
+fn comma_separated_grp_ids<'de, D>(deserializer: D) -> Result<Vec<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ids: String = Deserialize::deserialize(deserializer)?;
+    ids.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| id.parse().map_err(D::Error::custom))
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventJoinRequest {
+    pub event_name: String,
+    pub entry_currency_type: Option<String>,
+    pub entry_currency_paid: Option<i32>,
+    pub custom_token_id: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventSetDeckRequest {
+    pub event_name: String,
+    pub summary: DeckSummary,
+    pub deck: FrontDoorDeck,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeckUpsertDeckRequest {
+    pub summary: DeckSummary,
+    pub deck: FrontDoorDeck,
+    pub action_type: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GraphGetGraphStateRequest {
+    pub graph_id: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeckSummary {
+    pub deck_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub attributes: Vec<DeckAttribute>,
+    pub deck_tile_id: Option<i32>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeckAttribute {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FrontDoorDeck {
+    #[serde(default)]
+    pub main_deck: Vec<CardQuantity>,
+    #[serde(default)]
+    pub sideboard: Vec<CardQuantity>,
+    #[serde(default)]
+    pub command_zone: Vec<CardQuantity>,
+    #[serde(default)]
+    pub companions: Vec<CardQuantity>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardQuantity {
+    pub card_id: i32,
+    pub quantity: i32,
+}
+
+impl FrontDoorDeck {
+    /// grp ids of the main deck, one entry per copy like `DeckMessage::deck_cards`
+    pub fn mainboard(&self) -> Vec<i32> {
+        expand_quantities(&self.main_deck)
+    }
+
+    /// grp ids of the sideboard, one entry per copy like `DeckMessage::sideboard_cards`
+    pub fn sideboard(&self) -> Vec<i32> {
+        expand_quantities(&self.sideboard)
+    }
+}
+
+fn expand_quantities(cards: &[CardQuantity]) -> Vec<i32> {
+    cards
+        .iter()
+        .flat_map(|card| {
+            std::iter::repeat_n(card.card_id, usize::try_from(card.quantity).unwrap_or(0))
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CourseResponse {
+    pub course: Option<Course>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventGetCoursesResponse {
+    #[serde(default)]
+    pub courses: Vec<Course>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Course {
+    pub course_id: Option<String>,
+    pub internal_event_name: String,
+    pub current_module: Option<String>,
+    #[serde(default)]
+    pub current_wins: i32,
+    #[serde(default)]
+    pub current_losses: i32,
+    pub course_deck_summary: Option<DeckSummary>,
+    pub course_deck: Option<FrontDoorDeck>,
+    #[serde(default)]
+    pub card_pool: Vec<i32>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CombinedRankInfo {
+    pub player_id: Option<String>,
+    pub constructed_season_ordinal: Option<i32>,
+    pub constructed_class: Option<String>,
+    pub constructed_level: Option<i32>,
+    pub constructed_step: Option<i32>,
+    #[serde(default)]
+    pub constructed_matches_won: i32,
+    #[serde(default)]
+    pub constructed_matches_lost: i32,
+    #[serde(default)]
+    pub constructed_matches_drawn: i32,
+    pub constructed_percentile: Option<f64>,
+    pub constructed_leaderboard_place: Option<i32>,
+    pub limited_season_ordinal: Option<i32>,
+    pub limited_class: Option<String>,
+    pub limited_level: Option<i32>,
+    pub limited_step: Option<i32>,
+    #[serde(default)]
+    pub limited_matches_won: i32,
+    #[serde(default)]
+    pub limited_matches_lost: i32,
+    #[serde(default)]
+    pub limited_matches_drawn: i32,
+    pub limited_percentile: Option<f64>,
+    pub limited_leaderboard_place: Option<i32>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BotDraftStatusRequest {
+    pub event_name: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BotDraftPickRequest {
+    pub event_name: String,
+    pub pick_info: BotDraftPickInfo,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BotDraftPickInfo {
+    pub event_name: Option<String>,
+    /// older clients send a single `CardId`
+    #[serde(default, deserialize_with = "grp_ids")]
+    pub card_ids: Vec<i32>,
+    pub card_id: Option<String>,
+    /// 0-based
+    pub pack_number: i32,
+    /// 0-based
+    pub pick_number: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BotDraftResponse {
+    pub current_module: Option<String>,
+    #[serde(deserialize_with = "json_string")]
+    pub payload: BotDraftStatus,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BotDraftStatus {
+    pub result: Option<String>,
+    pub event_name: String,
+    /// `PickNext` while picking, `Completed` once the last pick is made
+    pub draft_status: String,
+    /// 0-based
+    #[serde(default)]
+    pub pack_number: i32,
+    /// 0-based
+    #[serde(default)]
+    pub pick_number: i32,
+    pub num_cards_to_pick: Option<i32>,
+    #[serde(default, deserialize_with = "grp_ids")]
+    pub draft_pack: Vec<i32>,
+    #[serde(default, deserialize_with = "grp_ids")]
+    pub picked_cards: Vec<i32>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerDraftMakePickRequest {
+    pub draft_id: String,
+    /// older clients send a single `GrpId`
+    #[serde(default)]
+    pub grp_ids: Vec<i32>,
+    pub grp_id: Option<i32>,
+    /// 1-based
+    pub pack: i32,
+    /// 1-based
+    pub pick: i32,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerDraftMakePickResponse {
+    pub is_picking_completed: Option<bool>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DraftNotification {
+    #[serde(rename = "draftId")]
+    pub draft_id: String,
+    /// 1-based
+    #[serde(rename = "SelfPack")]
+    pub self_pack: i32,
+    /// 1-based
+    #[serde(rename = "SelfPick")]
+    pub self_pick: i32,
+    #[serde(rename = "PackCards", deserialize_with = "comma_separated_grp_ids")]
+    pub pack_cards: Vec<i32>,
+}
+
+impl BotDraftPickInfo {
+    pub fn picked(&self) -> Vec<i32> {
+        if self.card_ids.is_empty() {
+            self.card_id
+                .iter()
+                .filter_map(|id| id.trim().parse().ok())
+                .collect()
+        } else {
+            self.card_ids.clone()
+        }
+    }
+}
+
+impl PlayerDraftMakePickRequest {
+    pub fn picked(&self) -> Vec<i32> {
+        if self.grp_ids.is_empty() {
+            self.grp_id.into_iter().collect()
+        } else {
+            self.grp_ids.clone()
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GraphState {
+    #[serde(default)]
+    pub node_states: HashMap<String, Value>,
+    #[serde(default)]
+    pub milestone_states: HashMap<String, Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn envelope(direction: Direction, method: &str, request_id: Option<&str>) -> LogEnvelope {
+        LogEnvelope {
+            direction,
+            method: Some(method.to_string()),
+            request_id: request_id.map(ToString::to_string),
+            ..LogEnvelope::default()
+        }
+    }
+
+    #[test]
+    fn test_event_join_request() -> Result<()> {
+        let event = r#"{"id":"abc","request":"{\"EventName\":\"QuickDraft_MKM_20240301\",\"EntryCurrencyType\":\"Gem\",\"EntryCurrencyPaid\":750,\"CustomTokenId\":null}"}"#;
+        let parsed =
+            FrontDoorEvent::from_envelope(&envelope(Direction::Request, "EventJoin", None), event)?;
+        let Some(FrontDoorEvent::Request(request)) = parsed else {
+            panic!("expected a request, got {parsed:?}");
+        };
+        assert_eq!(request.id, "abc");
+        let FrontDoorRequestPayload::EventJoin(join) = request.payload else {
+            panic!("expected EventJoin payload");
+        };
+        assert_eq!(join.event_name, "QuickDraft_MKM_20240301");
+        assert_eq!(join.entry_currency_paid, Some(750));
+        Ok(())
+    }
+
+    #[test]
+    fn test_deck_upsert_request() -> Result<()> {
+        let event = r#"{"id":"abc","request":"{\"Summary\":{\"DeckId\":\"d1\",\"Name\":\"Mono Red {Aggro}\",\"Attributes\":[{\"name\":\"Format\",\"value\":\"Standard\"}]},\"Deck\":{\"MainDeck\":[{\"cardId\":1,\"quantity\":2},{\"cardId\":3,\"quantity\":1}],\"Sideboard\":[{\"cardId\":4,\"quantity\":1}]},\"ActionType\":\"Updated\"}"}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Request, "DeckUpsertDeckV2", None),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Request(FrontDoorRequest {
+            payload: FrontDoorRequestPayload::DeckUpsertDeck(upsert),
+            ..
+        })) = parsed
+        else {
+            panic!("expected DeckUpsertDeck request, got {parsed:?}");
+        };
+        assert_eq!(upsert.summary.name, "Mono Red {Aggro}");
+        assert_eq!(upsert.summary.attributes[0].value, "Standard");
+        assert_eq!(upsert.deck.mainboard(), vec![1, 1, 3]);
+        assert_eq!(upsert.deck.sideboard(), vec![4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rank_response() -> Result<()> {
+        let event = r#"{"constructedSeasonOrdinal":90,"constructedClass":"Gold","constructedLevel":2,"constructedStep":3,"constructedMatchesWon":10,"constructedMatchesLost":5,"constructedMatchesDrawn":0,"limitedSeasonOrdinal":90,"limitedClass":"Mythic","limitedLevel":4,"limitedStep":0,"limitedPercentile":93.5,"limitedLeaderboardPlace":0}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Response, "RankGetCombinedRankInfo", Some("abc")),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Response(response)) = parsed else {
+            panic!("expected a response, got {parsed:?}");
+        };
+        assert_eq!(response.request_id.as_deref(), Some("abc"));
+        let FrontDoorResponsePayload::RankGetCombinedRankInfo(rank) = response.payload else {
+            panic!("expected rank payload");
+        };
+        assert_eq!(rank.constructed_class.as_deref(), Some("Gold"));
+        assert_eq!(rank.limited_percentile, Some(93.5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_that_does_not_decode() -> Result<()> {
+        let event = r#"{"constructedClass":"Gold","constructedLevel":"two"}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Response, "RankGetCombinedRankInfo", Some("abc")),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Response(response)) = parsed else {
+            panic!("expected a response, got {parsed:?}");
+        };
+        let FrontDoorResponsePayload::Other(payload) = response.payload else {
+            panic!("expected the payload to be kept as json");
+        };
+        assert_eq!(payload["constructedLevel"], "two");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_method() -> Result<()> {
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Response, "QuestGetQuests", None),
+            r#"{"quests":[]}"#,
+        )?;
+        let Some(FrontDoorEvent::Response(response)) = parsed else {
+            panic!("expected a response, got {parsed:?}");
+        };
+        assert_eq!(response.method, "QuestGetQuests");
+        assert!(matches!(
+            response.payload,
+            FrontDoorResponsePayload::Other(_)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bot_draft_status_response() -> Result<()> {
+        let event = r#"{"CurrentModule":"BotDraft","Payload":"{\"Result\":\"Success\",\"EventName\":\"QuickDraft_MKM_20240301\",\"DraftStatus\":\"PickNext\",\"PackNumber\":0,\"PickNumber\":1,\"NumCardsToPick\":1,\"DraftPack\":[\"90123\",\"90124\"],\"PackStyles\":[],\"PickedCards\":[\"90001\"],\"PickedStyles\":[]}"}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Response, "BotDraftDraftStatus", None),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Response(FrontDoorResponse {
+            payload: FrontDoorResponsePayload::BotDraftDraftStatus(response),
+            ..
+        })) = parsed
+        else {
+            panic!("expected BotDraftDraftStatus response, got {parsed:?}");
+        };
+        assert_eq!(response.payload.event_name, "QuickDraft_MKM_20240301");
+        assert_eq!(response.payload.pick_number, 1);
+        assert_eq!(response.payload.draft_pack, vec![90123, 90124]);
+        assert_eq!(response.payload.picked_cards, vec![90001]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_draft_notify() -> Result<()> {
+        let event =
+            r#"{"draftId":"d-1","SelfPick":2,"SelfPack":1,"PackCards":"90123,90124,90125"}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Notification, "Draft.Notify", None),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Notification(FrontDoorNotification {
+            payload: FrontDoorNotificationPayload::DraftNotify(notification),
+            ..
+        })) = parsed
+        else {
+            panic!("expected Draft.Notify, got {parsed:?}");
+        };
+        assert_eq!(notification.draft_id, "d-1");
+        assert_eq!(notification.pack_cards, vec![90123, 90124, 90125]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_player_draft_make_pick_request() -> Result<()> {
+        let event = r#"{"id":"abc","request":"{\"DraftId\":\"d-1\",\"GrpId\":90123,\"Pack\":1,\"Pick\":2}"}"#;
+        let parsed = FrontDoorEvent::from_envelope(
+            &envelope(Direction::Request, "EventPlayerDraftMakePick", None),
+            event,
+        )?;
+        let Some(FrontDoorEvent::Request(FrontDoorRequest {
+            payload: FrontDoorRequestPayload::EventPlayerDraftMakePick(pick),
+            ..
+        })) = parsed
+        else {
+            panic!("expected EventPlayerDraftMakePick request, got {parsed:?}");
+        };
+        assert_eq!(pick.picked(), vec![90123]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_direction() -> Result<()> {
+        let parsed = FrontDoorEvent::from_envelope(&LogEnvelope::default(), "{}")?;
+        assert!(parsed.is_none());
+        Ok(())
+    }
+}
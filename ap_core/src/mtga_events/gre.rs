@@ -1,20 +1,21 @@
 use std::collections::HashMap;
 
-use crate::mtga_events::primitives::ResultListEntry;
+use crate::mtga_events::primitives::{timestamp_from_ticks, ResultListEntry};
 use crate::mtga_events::primitives::{
     Action, Annotation, MulliganType, OptionPrompt, Player, PlayerDieRoll, Power, Prompt, Skin,
     Stop, Target, Timer, Toughness, TurnInfo, Zone,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-///
-/// GRE refers to the server-side MTGA engine
-///
-/// no clue what it actually stands for, but these are a bunch of events that come from
-/// the server to the game client
-///
-///
+//
+// GRE refers to the server-side MTGA engine
+//
+// no clue what it actually stands for, but these are a bunch of events that come from
+// the server to the game client
+//
+//
 
 macro_rules! wrapper {
     ($wrapperName:ident, $name:ident, $snake:ident) => {
@@ -47,6 +48,12 @@ pub struct RequestTypeGREToClientEvent {
     pub transaction_id: Option<String>,
 }
 
+impl RequestTypeGREToClientEvent {
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        timestamp_from_ticks(&self.timestamp)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GREToClientEvent {
@@ -450,6 +457,9 @@ pub struct GameObject {
     pub is_tapped: Option<bool>,
     pub power: Option<Power>,
     pub toughness: Option<Toughness>,
+    /// for abilities, the card they come from
+    pub object_source_grp_id: Option<i32>,
+    pub parent_id: Option<i32>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
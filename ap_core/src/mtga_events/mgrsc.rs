@@ -1,4 +1,5 @@
-use crate::mtga_events::primitives::ResultListEntry;
+use crate::mtga_events::primitives::{timestamp_from_ticks, ResultListEntry};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 ///
@@ -19,6 +20,12 @@ pub struct RequestTypeMGRSCEvent {
     pub transaction_id: String,
 }
 
+impl RequestTypeMGRSCEvent {
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        timestamp_from_ticks(&self.timestamp)
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MatchGameRoomStateChangedEvent {
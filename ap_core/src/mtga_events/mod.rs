@@ -1,5 +1,6 @@
 pub mod business;
 pub mod client;
+pub mod frontdoor;
 pub mod gre;
 pub mod mgrsc;
 pub mod primitives;
@@ -1,4 +1,5 @@
 use crate::mtga_events::gre::Reference;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
@@ -331,6 +332,28 @@ pub struct AnnotationDetail {
     pub value_string: Vec<String>,
 }
 
+impl Annotation {
+    pub fn has_type(&self, annotation_type: &AnnotationType) -> bool {
+        self.type_field.contains(annotation_type)
+    }
+
+    /// First integer value of the detail with this key
+    pub fn detail_i32(&self, key: &str) -> Option<i32> {
+        self.details
+            .iter()
+            .find(|detail| detail.key == key)
+            .and_then(|detail| detail.value_int32.first().copied())
+    }
+
+    /// First string value of the detail with this key
+    pub fn detail_str(&self, key: &str) -> Option<&str> {
+        self.details
+            .iter()
+            .find(|detail| detail.key == key)
+            .and_then(|detail| detail.value_string.first().map(String::as_str))
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Power {
@@ -577,3 +600,34 @@ pub enum SubZoneType {
     #[serde(rename = "SubZoneType_Bottom")]
     Bottom,
 }
+
+/// .NET ticks (100ns intervals since 0001-01-01) at the unix epoch
+const UNIX_EPOCH_TICKS: i64 = 621_355_968_000_000_000;
+
+/// Match server timestamps are .NET ticks, anything smaller than the unix epoch in ticks
+/// is taken to be unix milliseconds instead
+pub fn timestamp_from_ticks(timestamp: &str) -> Option<DateTime<Utc>> {
+    let value: i64 = timestamp.parse().ok()?;
+    if value < UNIX_EPOCH_TICKS {
+        DateTime::from_timestamp_millis(value)
+    } else {
+        let since_epoch = value - UNIX_EPOCH_TICKS;
+        let nanos = u32::try_from(since_epoch % 10_000_000 * 100).ok()?;
+        DateTime::from_timestamp(since_epoch / 10_000_000, nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_from_ticks() {
+        let expected = DateTime::parse_from_rfc3339("2024-05-01T12:00:00.5Z")
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc));
+        assert_eq!(timestamp_from_ticks("638501616005000000"), expected);
+        assert_eq!(timestamp_from_ticks("1714564800500"), expected);
+        assert_eq!(timestamp_from_ticks(""), None);
+    }
+}
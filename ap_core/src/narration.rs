@@ -0,0 +1,609 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use anyhow::Result;
+use tracing::warn;
+
+use crate::cards::CardsDatabase;
+use crate::game_state::{GameState, GameStateTracker};
+use crate::mtga_events::gre::GameStateMessage;
+use crate::mtga_events::primitives::{Annotation, AnnotationType, Phase};
+use crate::replay::MatchReplay;
+
+//
+// Play-by-play of a match in Markdown, one line per thing that happened, e.g.
+//
+// - T3 Opponent casts Sheoldred, the Apocalypse
+// - T4 You attack with 2 creatures, opponent takes 5, 20 -> 15
+//
+// built from the transient annotations of each `GameStateMessage`, under a heading for
+// every game and every turn (`NewTurnStarted`)
+//
+
+// `UserActionTaken` action types, casting and playing lands are narrated from `ZoneTransfer`
+const ACTION_TYPE_ACTIVATE: i32 = 2;
+
+#[derive(Debug, Clone, Copy)]
+struct SeenObject {
+    grp_id: i32,
+    controller: i32,
+    source_grp_id: Option<i32>,
+}
+
+#[derive(Debug, Default)]
+struct DamageToPlayer {
+    sources: Vec<i32>,
+    damage: i32,
+    in_combat: bool,
+}
+
+/// Life total by seat, all that's needed of the board before a message
+fn life_totals(state: Option<&GameState>) -> BTreeMap<i32, i32> {
+    state
+        .map(|state| {
+            state
+                .players
+                .iter()
+                .map(|(seat_id, player)| (*seat_id, player.life_total))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+struct Narrator<'a> {
+    cards_db: &'a CardsDatabase,
+    controller_seat_id: i32,
+    tracker: GameStateTracker,
+    /// every object seen so far, objects that changed zones are still needed to name them
+    objects: BTreeMap<i32, SeenObject>,
+    game_number: Option<i32>,
+    text: String,
+}
+
+impl Narrator<'_> {
+    fn is_you(&self, seat_id: i32) -> bool {
+        seat_id == self.controller_seat_id
+    }
+
+    /// "You cast" or "Opponent casts"
+    fn subject(&self, seat_id: i32, verb: &str) -> String {
+        if self.is_you(seat_id) {
+            format!("You {verb}")
+        } else {
+            format!("Opponent {verb}s")
+        }
+    }
+
+    fn object(&self, seat_id: i32) -> &'static str {
+        if self.is_you(seat_id) {
+            "you"
+        } else {
+            "opponent"
+        }
+    }
+
+    fn card_name(&self, instance_id: i32) -> String {
+        self.objects.get(&instance_id).map_or_else(
+            || "a card".to_string(),
+            |object| self.cards_db.get_pretty_name_defaulted(&object.grp_id),
+        )
+    }
+
+    fn controller(&self, instance_id: i32) -> Option<i32> {
+        self.objects
+            .get(&instance_id)
+            .map(|object| object.controller)
+    }
+
+    fn line(&mut self, turn_number: i32, line: &str) {
+        let _ = writeln!(self.text, "- T{turn_number} {line}");
+    }
+
+    /// Game state ids and instance ids start over with every game
+    fn narrate_game<'b>(
+        &mut self,
+        game_state_messages: impl Iterator<Item = &'b GameStateMessage>,
+    ) {
+        self.tracker = GameStateTracker::new();
+        self.objects.clear();
+        for gsm in game_state_messages {
+            self.narrate_game_state(gsm);
+        }
+    }
+
+    fn narrate_game_state(&mut self, gsm: &GameStateMessage) {
+        // the same state is sometimes sent more than once
+        if self.tracker.contains(gsm.game_state_id) {
+            return;
+        }
+        let before = life_totals(self.tracker.current());
+        let mut tracker = std::mem::take(&mut self.tracker);
+        match tracker.apply(gsm) {
+            Ok(after) => self.narrate_changes(gsm, &before, after),
+            Err(e) => warn!("Error applying game state {}: {e}", gsm.game_state_id),
+        }
+        self.tracker = tracker;
+    }
+
+    fn narrate_changes(
+        &mut self,
+        gsm: &GameStateMessage,
+        before: &BTreeMap<i32, i32>,
+        after: &GameState,
+    ) {
+        for game_object in &gsm.game_objects {
+            self.objects.insert(
+                game_object.instance_id,
+                SeenObject {
+                    grp_id: game_object.grp_id,
+                    controller: game_object
+                        .controller_seat_id
+                        .unwrap_or(game_object.owner_seat_id),
+                    source_grp_id: game_object.object_source_grp_id,
+                },
+            );
+        }
+        if after.game_number().is_some() && after.game_number() != self.game_number {
+            self.game_number = after.game_number();
+            let _ = writeln!(
+                self.text,
+                "\n## Game {}\n",
+                self.game_number.unwrap_or_default()
+            );
+        }
+
+        let turn_number = after.turn_number().unwrap_or_default();
+        let in_combat = after
+            .turn_info
+            .as_ref()
+            .is_some_and(|turn_info| turn_info.phase == Some(Phase::Combat));
+        let mut damage_to_players = BTreeMap::<i32, DamageToPlayer>::new();
+        for annotation in &gsm.annotations {
+            if annotation.has_type(&AnnotationType::NewTurnStarted) {
+                self.narrate_new_turn(turn_number, after);
+            } else if annotation.has_type(&AnnotationType::ZoneTransfer) {
+                self.narrate_zone_transfer(turn_number, annotation);
+            } else if annotation.has_type(&AnnotationType::DamageDealt) {
+                let Some(&target) = annotation.affected_ids.first() else {
+                    continue;
+                };
+                let source = annotation.affector_id.unwrap_or_default();
+                let damage = annotation.detail_i32("damage").unwrap_or_default();
+                if after.players.contains_key(&target) {
+                    let damage_to_player = damage_to_players.entry(target).or_default();
+                    damage_to_player.sources.push(source);
+                    damage_to_player.damage += damage;
+                    damage_to_player.in_combat = in_combat;
+                } else if !in_combat {
+                    let line = format!(
+                        "{} deals {damage} to {}",
+                        self.card_name(source),
+                        self.card_name(target)
+                    );
+                    self.line(turn_number, &line);
+                }
+            } else if annotation.has_type(&AnnotationType::ModifiedLife) {
+                self.narrate_modified_life(
+                    turn_number,
+                    annotation,
+                    &damage_to_players,
+                    (before, after),
+                );
+            } else if annotation.has_type(&AnnotationType::UserActionTaken)
+                && annotation.detail_i32("actionType") == Some(ACTION_TYPE_ACTIVATE)
+            {
+                self.narrate_activation(turn_number, annotation);
+            }
+        }
+        for (seat_id, damage_to_player) in damage_to_players {
+            self.narrate_damage_to_player(seat_id, &damage_to_player, (before, after));
+        }
+    }
+
+    fn narrate_new_turn(&mut self, turn_number: i32, after: &GameState) {
+        let active_player = after
+            .turn_info
+            .as_ref()
+            .and_then(|turn_info| turn_info.active_player)
+            .map_or("", |seat_id| {
+                if self.is_you(seat_id) {
+                    ", you"
+                } else {
+                    ", opponent"
+                }
+            });
+        // right under the game heading there is a blank line already
+        if !self.text.ends_with("\n\n") {
+            self.text.push('\n');
+        }
+        let _ = writeln!(self.text, "### Turn {turn_number}{active_player}\n");
+    }
+
+    fn narrate_zone_transfer(&mut self, turn_number: i32, annotation: &Annotation) {
+        let Some(&instance_id) = annotation.affected_ids.first() else {
+            return;
+        };
+        let card = self.card_name(instance_id);
+        let seat_id = self.controller(instance_id).unwrap_or_default();
+        let line = match annotation.detail_str("category").unwrap_or_default() {
+            "CastSpell" => format!("{} {card}", self.subject(seat_id, "cast")),
+            "PlayLand" => format!("{} {card}", self.subject(seat_id, "play")),
+            "Discard" => format!("{} {card}", self.subject(seat_id, "discard")),
+            "Sacrifice" => format!("{} {card}", self.subject(seat_id, "sacrifice")),
+            "Destroy" => format!("{card} is destroyed"),
+            "SBA_Damage" | "SBA_Deathtouch" | "SBA_ZeroToughness" | "SBA_ZeroLoyalty" => {
+                format!("{card} dies")
+            }
+            "Exile" => format!("{card} is exiled"),
+            "Countered" => format!("{card} is countered"),
+            "Return" => format!("{card} is returned to hand"),
+            "Mill" => format!("{card} is milled"),
+            _ => return,
+        };
+        self.line(turn_number, &line);
+    }
+
+    fn narrate_activation(&mut self, turn_number: i32, annotation: &Annotation) {
+        let Some(seat_id) = annotation.affector_id else {
+            return;
+        };
+        let source = annotation
+            .affected_ids
+            .first()
+            .and_then(|instance_id| self.objects.get(instance_id))
+            .and_then(|object| object.source_grp_id);
+        let line = match source {
+            Some(grp_id) => format!(
+                "{} {}",
+                self.subject(seat_id, "activate"),
+                self.cards_db.get_pretty_name_defaulted(&grp_id)
+            ),
+            None => format!("{} an ability", self.subject(seat_id, "activate")),
+        };
+        self.line(turn_number, &line);
+    }
+
+    /// Life changes that didn't come from damage, which is narrated with its source
+    fn narrate_modified_life(
+        &mut self,
+        turn_number: i32,
+        annotation: &Annotation,
+        damage_to_players: &BTreeMap<i32, DamageToPlayer>,
+        (before, after): (&BTreeMap<i32, i32>, &GameState),
+    ) {
+        let Some(&seat_id) = annotation.affected_ids.first() else {
+            return;
+        };
+        let delta = annotation.detail_i32("life").unwrap_or_default();
+        if delta == 0 || damage_to_players.contains_key(&seat_id) {
+            return;
+        }
+        let verb = if delta > 0 { "gain" } else { "lose" };
+        let line = format!(
+            "{} {}, {} -> {}",
+            self.subject(seat_id, verb),
+            delta.abs(),
+            before.get(&seat_id).copied().unwrap_or_default(),
+            after.life_total(seat_id).unwrap_or_default()
+        );
+        self.line(turn_number, &line);
+    }
+
+    fn narrate_damage_to_player(
+        &mut self,
+        seat_id: i32,
+        damage_to_player: &DamageToPlayer,
+        (before, after): (&BTreeMap<i32, i32>, &GameState),
+    ) {
+        let turn_number = after.turn_number().unwrap_or_default();
+        let life_change = format!(
+            "{} -> {}",
+            before.get(&seat_id).copied().unwrap_or_default(),
+            after.life_total(seat_id).unwrap_or_default()
+        );
+        let line = if damage_to_player.in_combat {
+            let attacker = damage_to_player
+                .sources
+                .first()
+                .and_then(|source| self.controller(*source))
+                .unwrap_or_default();
+            let creatures = damage_to_player.sources.len();
+            format!(
+                "{} with {creatures} creature{}, {} takes {}, {life_change}",
+                self.subject(attacker, "attack"),
+                if creatures == 1 { "" } else { "s" },
+                self.object(seat_id),
+                damage_to_player.damage
+            )
+        } else {
+            let sources: Vec<_> = damage_to_player
+                .sources
+                .iter()
+                .map(|source| self.card_name(*source))
+                .collect();
+            format!(
+                "{} deals {} to {}, {life_change}",
+                sources.join(" and "),
+                damage_to_player.damage,
+                self.object(seat_id)
+            )
+        };
+        self.line(turn_number, &line);
+    }
+
+    fn narrate_results(&mut self, match_replay: &MatchReplay) {
+        let Ok(final_match_result) = match_replay.get_final_match_result() else {
+            return;
+        };
+        let controller_team_id = match_replay
+            .match_start_message
+            .mgrsc_event
+            .game_room_info
+            .players
+            .iter()
+            .flatten()
+            .find(|player| player.system_seat_id == self.controller_seat_id)
+            .map_or(self.controller_seat_id, |player| player.team_id);
+        let game_results = match_replay.games().into_iter().filter_map(|game| {
+            let result = game.result?;
+            Some((format!("Game {}", game.game_number), result))
+        });
+        let match_results = final_match_result
+            .result_list
+            .into_iter()
+            .filter(|result| result.scope != "MatchScope_Game")
+            .map(|result| ("Match".to_string(), result));
+        let _ = writeln!(self.text, "\n## Result\n");
+        for (scope, result) in game_results.chain(match_results) {
+            let winner = if result.winning_team_id == controller_team_id {
+                "You"
+            } else {
+                "Opponent"
+            };
+            let reason = result
+                .reason
+                .as_deref()
+                .map(|reason| format!(" ({})", reason.trim_start_matches("ResultReason_")))
+                .unwrap_or_default();
+            let _ = writeln!(self.text, "- {scope}: {winner} won{reason}");
+        }
+    }
+}
+
+impl MatchReplay {
+    /// Markdown play-by-play of the match
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the controller seat ID is not found
+    pub fn narrate(&self, cards_db: &CardsDatabase) -> Result<String> {
+        let mut narrator = Narrator {
+            cards_db,
+            controller_seat_id: self.get_controller_seat_id()?,
+            tracker: GameStateTracker::new(),
+            objects: BTreeMap::new(),
+            game_number: None,
+            text: format!("# {}\n", self.match_id),
+        };
+        for game in self.games() {
+            narrator.narrate_game(game.game_state_messages_iter());
+        }
+        narrator.narrate_results(self);
+        Ok(narrator.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtga_events::gre::{GameInfo, GameObject};
+    use crate::mtga_events::primitives::{AnnotationDetail, Player, TurnInfo};
+
+    fn narrator(cards_db: &CardsDatabase) -> Narrator<'_> {
+        Narrator {
+            cards_db,
+            controller_seat_id: 1,
+            tracker: GameStateTracker::new(),
+            objects: BTreeMap::new(),
+            game_number: None,
+            text: String::new(),
+        }
+    }
+
+    fn annotation(
+        annotation_type: AnnotationType,
+        affector_id: Option<i32>,
+        affected_ids: Vec<i32>,
+        details: Vec<(&str, AnnotationDetail)>,
+    ) -> Annotation {
+        Annotation {
+            affected_ids,
+            affector_id,
+            type_field: vec![annotation_type],
+            details: details
+                .into_iter()
+                .map(|(key, detail)| AnnotationDetail {
+                    key: key.to_string(),
+                    ..detail
+                })
+                .collect(),
+            ..Annotation::default()
+        }
+    }
+
+    fn int_detail(value: i32) -> AnnotationDetail {
+        AnnotationDetail {
+            value_int32: vec![value],
+            ..AnnotationDetail::default()
+        }
+    }
+
+    fn string_detail(value: &str) -> AnnotationDetail {
+        AnnotationDetail {
+            value_string: vec![value.to_string()],
+            ..AnnotationDetail::default()
+        }
+    }
+
+    fn creature(instance_id: i32, seat_id: i32) -> GameObject {
+        GameObject {
+            instance_id,
+            grp_id: 90000 + instance_id,
+            owner_seat_id: seat_id,
+            controller_seat_id: Some(seat_id),
+            ..GameObject::default()
+        }
+    }
+
+    fn player(system_seat_number: i32, life_total: i32) -> Player {
+        Player {
+            life_total,
+            system_seat_number,
+            ..Player::default()
+        }
+    }
+
+    #[test]
+    fn test_narrate_cast_and_attack() {
+        let cards_db = CardsDatabase {
+            db: BTreeMap::new(),
+        };
+        let mut narrator = narrator(&cards_db);
+        narrator.narrate_game_state(&GameStateMessage {
+            game_state_id: 1,
+            type_field: "GameStateType_Full".to_string(),
+            game_info: Some(GameInfo {
+                game_number: 1,
+                ..GameInfo::default()
+            }),
+            turn_info: Some(TurnInfo {
+                turn_number: Some(3),
+                phase: Some(Phase::PrecombatMain),
+                ..TurnInfo::default()
+            }),
+            players: vec![player(1, 20), player(2, 20)],
+            game_objects: vec![creature(200, 2)],
+            annotations: vec![annotation(
+                AnnotationType::ZoneTransfer,
+                Some(2),
+                vec![200],
+                vec![("category", string_detail("CastSpell"))],
+            )],
+            ..GameStateMessage::default()
+        });
+        narrator.narrate_game_state(&GameStateMessage {
+            game_state_id: 2,
+            prev_game_state_id: Some(1),
+            turn_info: Some(TurnInfo {
+                turn_number: Some(4),
+                phase: Some(Phase::Combat),
+                ..TurnInfo::default()
+            }),
+            players: vec![player(2, 15)],
+            game_objects: vec![creature(300, 1), creature(301, 1)],
+            annotations: vec![
+                annotation(
+                    AnnotationType::DamageDealt,
+                    Some(300),
+                    vec![2],
+                    vec![("damage", int_detail(2))],
+                ),
+                annotation(
+                    AnnotationType::DamageDealt,
+                    Some(301),
+                    vec![2],
+                    vec![("damage", int_detail(3))],
+                ),
+                annotation(
+                    AnnotationType::ModifiedLife,
+                    None,
+                    vec![2],
+                    vec![("life", int_detail(-5))],
+                ),
+            ],
+            ..GameStateMessage::default()
+        });
+        assert_eq!(
+            narrator.text,
+            "\n## Game 1\n\n\
+             - T3 Opponent casts 90200\n\
+             - T4 You attack with 2 creatures, opponent takes 5, 20 -> 15\n"
+        );
+    }
+
+    #[test]
+    fn test_narrate_life_gain() {
+        let cards_db = CardsDatabase {
+            db: BTreeMap::new(),
+        };
+        let mut narrator = narrator(&cards_db);
+        narrator.narrate_game_state(&GameStateMessage {
+            game_state_id: 1,
+            type_field: "GameStateType_Full".to_string(),
+            turn_info: Some(TurnInfo {
+                turn_number: Some(5),
+                ..TurnInfo::default()
+            }),
+            players: vec![player(1, 20)],
+            ..GameStateMessage::default()
+        });
+        narrator.narrate_game_state(&GameStateMessage {
+            game_state_id: 2,
+            prev_game_state_id: Some(1),
+            players: vec![player(1, 23)],
+            annotations: vec![annotation(
+                AnnotationType::ModifiedLife,
+                None,
+                vec![1],
+                vec![("life", int_detail(3))],
+            )],
+            ..GameStateMessage::default()
+        });
+        assert_eq!(narrator.text, "- T5 You gain 3, 20 -> 23\n");
+    }
+
+    #[test]
+    fn test_narrate_turns_and_games() {
+        let cards_db = CardsDatabase {
+            db: BTreeMap::new(),
+        };
+        let full_state = |game_number, turn_number, annotations| GameStateMessage {
+            game_state_id: 1,
+            type_field: "GameStateType_Full".to_string(),
+            game_info: Some(GameInfo {
+                game_number,
+                ..GameInfo::default()
+            }),
+            turn_info: Some(TurnInfo {
+                turn_number: Some(turn_number),
+                active_player: Some(2),
+                ..TurnInfo::default()
+            }),
+            players: vec![player(1, 20), player(2, 20)],
+            game_objects: vec![creature(200, 2)],
+            annotations,
+            ..GameStateMessage::default()
+        };
+        let new_turn = || annotation(AnnotationType::NewTurnStarted, Some(2), vec![2], vec![]);
+        let cast = || {
+            annotation(
+                AnnotationType::ZoneTransfer,
+                Some(2),
+                vec![200],
+                vec![("category", string_detail("CastSpell"))],
+            )
+        };
+        let mut narrator = narrator(&cards_db);
+        narrator.narrate_game([full_state(1, 1, vec![new_turn()])].iter());
+        // game state ids start over in game 2
+        narrator.narrate_game([full_state(2, 2, vec![new_turn(), cast()])].iter());
+        assert_eq!(
+            narrator.text,
+            "\n## Game 1\n\n\
+             ### Turn 1, opponent\n\n\
+             \n## Game 2\n\n\
+             ### Turn 2, opponent\n\n\
+             - T2 Opponent casts 90200\n"
+        );
+    }
+}
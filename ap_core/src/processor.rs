@@ -1,111 +1,635 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::collections::VecDeque;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 use std::result::Result as StdResult;
-use tracing::{debug, error};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 
+use crate::adapters::{Events, LogEvents, MatchReplays};
+use crate::checkpoint::{LogCheckpoint, LogFileIdentity, LogPosition};
+use crate::log_envelope::LogEnvelope;
 use crate::mtga_events::business::RequestTypeBusinessEvent;
 use crate::mtga_events::client::RequestTypeClientToMatchServiceMessage;
+use crate::mtga_events::frontdoor::{FrontDoorEvent, BUSINESS_EVENT_METHOD};
 use crate::mtga_events::gre::RequestTypeGREToClientEvent;
 use crate::mtga_events::mgrsc::RequestTypeMGRSCEvent;
 
 pub trait ArenaEventSource {
     /// # Errors
     ///
-    /// Errors when json events that look parseable do not parse, or when no events are found
-    fn get_next_event(&mut self) -> StdResult<ParseOutput, ParseError>;
+    /// Errors with `ParseError::EndOfInput` when there are no more events to read,
+    /// or with the reason the next event in the log could not be parsed
+    fn get_next_log_event(&mut self) -> StdResult<LogEvent, ParseError>;
+
+    /// # Errors
+    ///
+    /// Errors with `ParseError::EndOfInput` when there are no more events to read,
+    /// or with the reason the next event in the log could not be parsed
+    fn get_next_event(&mut self) -> StdResult<ParseOutput, ParseError> {
+        self.get_next_log_event().map(|log_event| log_event.output)
+    }
+
+    /// Iterates over the events of this source, see `LogEvents`
+    fn log_events(self) -> LogEvents<Self>
+    where
+        Self: Sized,
+    {
+        LogEvents::new(self)
+    }
+
+    /// Iterates over the events of this source, see `LogEvents`
+    fn events(self) -> Events<Self>
+    where
+        Self: Sized,
+    {
+        Events::new(self)
+    }
+
+    /// Iterates over the matches found in this source, see `MatchReplays`
+    fn match_replays(self) -> MatchReplays<Self>
+    where
+        Self: Sized,
+    {
+        MatchReplays::new(self)
+    }
+
+    /// Skips past events that can't be parsed, handing each one to `on_error`.
+    /// Returns `None` at the end of the input, or when the log can't be read
+    fn get_next_valid_log_event(
+        &mut self,
+        on_error: &mut dyn FnMut(ParseError),
+    ) -> Option<LogEvent> {
+        loop {
+            match self.get_next_log_event() {
+                Ok(log_event) => return Some(log_event),
+                Err(ParseError::EndOfInput) => return None,
+                Err(e) if e.is_bad_event() => on_error(e),
+                Err(e) => {
+                    on_error(e);
+                    return None;
+                }
+            }
+        }
+    }
 }
 
+impl<S: ArenaEventSource + ?Sized> ArenaEventSource for &mut S {
+    fn get_next_log_event(&mut self) -> StdResult<LogEvent, ParseError> {
+        (**self).get_next_log_event()
+    }
+}
+
+/// A parsed event along with the log header metadata it was found under
 #[derive(Debug)]
+pub struct LogEvent {
+    pub envelope: LogEnvelope,
+    pub output: ParseOutput,
+}
+
 pub struct PlayerLogProcessor {
-    player_log_reader: BufReader<File>,
-    json_events: VecDeque<String>,
-    current_json_str: Option<String>,
-    bracket_depth: usize,
+    /// only set when reading from a file, which checkpoints and following rotation rely on
+    log_file: Option<LogFile>,
+    player_log_reader: BufReader<Box<dyn Read + Send>>,
+    json_events: VecDeque<QueuedEvent>,
+    json_extractor: JsonExtractor,
+    position: LogPosition,
 }
 
-impl PlayerLogProcessor {
-    /// # Errors
-    ///
-    /// Will return an error if the player log file cannot be opened
-    pub fn try_new(player_log_path: PathBuf) -> Result<Self> {
-        let reader = BufReader::new(File::open(player_log_path)?);
-        Ok(Self {
-            player_log_reader: reader,
-            json_events: VecDeque::new(),
-            current_json_str: None,
-            bracket_depth: 0,
-        })
+#[derive(Debug)]
+struct LogFile {
+    player_log_path: PathBuf,
+    /// either `player_log_path` or Player-prev.log next to it
+    current_log_path: PathBuf,
+    identity: LogFileIdentity,
+    /// see `LogPosition::generation`
+    generation: u32,
+}
+
+impl std::fmt::Debug for PlayerLogProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlayerLogProcessor")
+            .field("log_file", &self.log_file)
+            .field("json_events", &self.json_events)
+            .field("json_extractor", &self.json_extractor)
+            .field("position", &self.position)
+            .finish_non_exhaustive()
     }
+}
 
-    // try to find the json strings in the logs. ignoring all other info
-    // purges whitespace from the internal json strings, but I don't think that will cause
-    // any issues given the log entries I've read
-    pub fn process_line(&mut self, log_line: &str) -> Vec<String> {
+impl LogFile {
+    fn is_reading_previous_log(&self) -> bool {
+        self.current_log_path != self.player_log_path
+    }
+}
+
+#[derive(Debug)]
+struct QueuedEvent {
+    envelope: LogEnvelope,
+    json_str: String,
+    /// set on the last event before a place processing could restart from
+    resume_position: Option<LogPosition>,
+}
+
+/// Pulls top level json objects out of raw log text, which may span several lines.
+/// Tracks string literals and escapes so braces and whitespace inside strings are kept as-is,
+/// and collects the non-json text in between into a `LogEnvelope` for the next event
+#[derive(Debug, Default)]
+struct JsonExtractor {
+    current_json_str: Option<String>,
+    bracket_depth: usize,
+    in_string: bool,
+    escaped: bool,
+    envelope: LogEnvelope,
+    current_envelope: LogEnvelope,
+    header: String,
+    byte_offset: u64,
+    line_number: usize,
+    generation: u32,
+}
+
+impl JsonExtractor {
+    fn process_line(&mut self, log_line: &str) -> Vec<(LogEnvelope, String)> {
         let mut completed_json_strings = Vec::new();
-        for char in log_line.chars() {
-            match char {
-                '{' => {
-                    if self.current_json_str.is_none() {
-                        self.current_json_str = Some(String::new());
+        let line_offset = self.byte_offset;
+        self.line_number += 1;
+        self.byte_offset += log_line.len() as u64;
+
+        for (index, char) in log_line.char_indices() {
+            let Some(json_str) = &mut self.current_json_str else {
+                if char == '{' {
+                    self.envelope.merge_header(&self.header);
+                    self.header.clear();
+                    self.current_envelope = LogEnvelope {
+                        byte_offset: line_offset + index as u64,
+                        line_number: self.line_number,
+                        ..self.envelope.clone()
+                    };
+                    self.envelope = self.envelope.carry_forward();
+                    self.current_json_str = Some(String::from('{'));
+                    self.bracket_depth = 1;
+                } else {
+                    self.header.push(char);
+                }
+                continue;
+            };
+
+            if self.in_string {
+                match char {
+                    // a json string can't contain a raw line break, only the escaped form
+                    '\n' | '\r' => {}
+                    _ if self.escaped => {
+                        json_str.push(char);
+                        self.escaped = false;
                     }
-                    if let Some(json_str) = &mut self.current_json_str {
-                        json_str.push('{');
+                    '\\' => {
+                        json_str.push(char);
+                        self.escaped = true;
+                    }
+                    '"' => {
+                        json_str.push(char);
+                        self.in_string = false;
                     }
+                    _ => json_str.push(char),
+                }
+                continue;
+            }
+
+            match char {
+                '"' => {
+                    json_str.push(char);
+                    self.in_string = true;
+                }
+                '{' => {
+                    json_str.push(char);
                     self.bracket_depth += 1;
                 }
                 '}' => {
-                    if let Some(json_str) = &mut self.current_json_str {
-                        json_str.push('}');
-                        self.bracket_depth -= 1;
-                        if self.bracket_depth == 0 {
-                            completed_json_strings.push(json_str.clone());
-                            self.current_json_str = None;
-                        }
-                    }
-                }
-                ' ' | '\n' | '\r' => {}
-                _ => {
-                    if let Some(json_str) = &mut self.current_json_str {
-                        json_str.push(char);
+                    json_str.push(char);
+                    self.bracket_depth -= 1;
+                    if self.bracket_depth == 0 {
+                        completed_json_strings
+                            .push((std::mem::take(&mut self.current_envelope), json_str.clone()));
+                        self.current_json_str = None;
                     }
                 }
+                ' ' | '\t' | '\n' | '\r' => {}
+                _ => json_str.push(char),
             }
         }
+
+        if !self.header.trim().is_empty() {
+            self.envelope.merge_header(&self.header);
+        }
+        self.header.clear();
         completed_json_strings
     }
 
-    fn process_lines(&mut self) {
+    /// Where processing could restart from, if the extractor is between events
+    fn position(&self) -> Option<LogPosition> {
+        if self.current_json_str.is_some() || !self.header.is_empty() {
+            return None;
+        }
+        Some(LogPosition {
+            byte_offset: self.byte_offset,
+            line_number: self.line_number,
+            envelope: self.envelope.clone(),
+            generation: self.generation,
+        })
+    }
+
+    fn resume(position: &LogPosition) -> Self {
+        Self {
+            envelope: position.envelope.clone(),
+            byte_offset: position.byte_offset,
+            line_number: position.line_number,
+            generation: position.generation,
+            ..Self::default()
+        }
+    }
+}
+
+impl PlayerLogProcessor {
+    /// # Errors
+    ///
+    /// Will return an error if the player log file cannot be opened
+    pub fn try_new(player_log_path: PathBuf) -> Result<Self> {
+        let mut processor = Self::from_reader(std::io::empty());
+        processor.open_log(player_log_path, &LogPosition::default())?;
+        Ok(processor)
+    }
+
+    /// Like `try_new`, but reads Player-prev.log (if there is one) before Player.log,
+    /// so a match that was in progress when MTGA restarted can still be pieced together
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the player log file cannot be opened
+    pub fn try_new_with_previous_log(player_log_path: PathBuf) -> Result<Self> {
+        let previous_log_path = previous_log_path(&player_log_path);
+        let mut processor = Self::try_new(player_log_path)?;
+        if previous_log_path.is_file() {
+            processor.open_log(previous_log_path, &LogPosition::default())?;
+        }
+        Ok(processor)
+    }
+
+    /// Reads log text from anything, e.g. a string's bytes, stdin or an archived log.
+    /// Checkpoints and following log rotation need a file, see `try_new`
+    pub fn from_reader(reader: impl Read + Send + 'static) -> Self {
+        Self {
+            log_file: None,
+            player_log_reader: BufReader::new(Box::new(reader)),
+            json_events: VecDeque::new(),
+            json_extractor: JsonExtractor::default(),
+            position: LogPosition::default(),
+        }
+    }
+
+    /// Starts reading `log_path` from `position`, as a new generation.
+    /// The first log opened becomes the Player.log that rotation is checked against
+    fn open_log(&mut self, log_path: PathBuf, position: &LogPosition) -> Result<()> {
+        let mut file = File::open(&log_path)?;
+        info!("Reading {}", log_path.display());
+        file.seek(SeekFrom::Start(position.byte_offset))?;
+        let identity = LogFileIdentity::new(&file.metadata()?);
+        let (player_log_path, generation) = match self.log_file.take() {
+            Some(log_file) => (log_file.player_log_path, log_file.generation + 1),
+            None => (log_path.clone(), 0),
+        };
+        self.log_file = Some(LogFile {
+            player_log_path,
+            current_log_path: log_path,
+            identity,
+            generation,
+        });
+        self.player_log_reader = BufReader::new(Box::new(file));
+        self.json_events.clear();
+        self.position = LogPosition {
+            generation,
+            ..position.clone()
+        };
+        self.json_extractor = JsonExtractor::resume(&self.position);
+        Ok(())
+    }
+
+    fn log_file(&self) -> Result<&LogFile> {
+        self.log_file
+            .as_ref()
+            .ok_or_else(|| anyhow!("Checkpoints are only supported when reading a log file"))
+    }
+
+    /// Skips ahead to `checkpoint` if it was taken from the log being read, or from Player.log
+    /// when Player-prev.log is being read first.
+    /// Returns false, and leaves the processor at the start of the log,
+    /// if the log has been truncated or replaced since
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the processor isn't reading a file, or the file cannot be read
+    pub fn resume_from(&mut self, checkpoint: &LogCheckpoint) -> Result<bool> {
+        let log_file = self.log_file()?;
+        let log_path = if checkpoint.is_valid_for(&log_file.current_log_path)? {
+            log_file.current_log_path.clone()
+        } else if log_file.is_reading_previous_log()
+            && checkpoint.is_valid_for(&log_file.player_log_path)?
+        {
+            // Player-prev.log was fully processed before MTGA restarted
+            log_file.player_log_path.clone()
+        } else {
+            info!(
+                "Processing {} from the start",
+                log_file.current_log_path.display()
+            );
+            return Ok(false);
+        };
+        let position = &checkpoint.position;
+        info!(
+            "Resuming {} from line {} (byte {})",
+            log_path.display(),
+            position.line_number,
+            position.byte_offset
+        );
+        self.open_log(log_path, position)?;
+        Ok(true)
+    }
+
+    /// `None` when not reading from a file
+    pub fn player_log_path(&self) -> Option<&Path> {
+        self.log_file
+            .as_ref()
+            .map(|log_file| log_file.player_log_path.as_path())
+    }
+
+    /// True when every event read from the log so far has been returned by `get_next_log_event`
+    pub fn is_caught_up(&self) -> bool {
+        self.json_events.is_empty()
+    }
+
+    /// The latest place in the log where every event before it
+    /// has already been returned by `get_next_log_event`
+    pub fn position(&self) -> &LogPosition {
+        &self.position
+    }
+
+    /// Pairs `position` with the log being read, which must be the log the position
+    /// was taken in; see `LogPosition::generation`
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the processor isn't reading a file, the file cannot be read,
+    /// or the position is in a log the processor has since moved on from
+    pub fn checkpoint(&self, position: LogPosition) -> Result<LogCheckpoint> {
+        let log_file = self.log_file()?;
+        if position.generation != log_file.generation {
+            return Err(anyhow!(
+                "Position at line {} is not in {}, which has been switched to since",
+                position.line_number,
+                log_file.current_log_path.display()
+            ));
+        }
+        LogCheckpoint::capture(&log_file.current_log_path, position)
+    }
+
+    /// Once the log being read has run dry, moves on from Player-prev.log to Player.log,
+    /// or reopens Player.log if MTGA has replaced or truncated it.
+    /// Returns true if a different file, with a new `LogPosition::generation`, is now being read
+    fn switch_log_if_needed(&mut self) -> bool {
+        let Some(log_file) = &self.log_file else {
+            return false;
+        };
+        if !log_file.is_reading_previous_log() {
+            let Ok(metadata) = std::fs::metadata(&log_file.player_log_path) else {
+                // MTGA may be in the middle of moving it to Player-prev.log
+                return false;
+            };
+            // the size check also catches replacements that keep the creation time
+            if log_file
+                .identity
+                .is_same_file(&LogFileIdentity::new(&metadata))
+                && metadata.len() >= self.json_extractor.byte_offset
+            {
+                return false;
+            }
+            info!("Player.log has been replaced or truncated");
+        }
+        let player_log_path = log_file.player_log_path.clone();
+        if let Err(e) = self.open_log(player_log_path, &LogPosition::default()) {
+            error!("Error reopening Player.log: {e}");
+            return false;
+        }
+        true
+    }
+
+    // try to find the json strings in the logs, along with the header info preceding them
+    pub fn process_line(&mut self, log_line: &str) -> Vec<(LogEnvelope, String)> {
+        self.json_extractor.process_line(log_line)
+    }
+
+    /// Events from lines read before an IO error are still queued
+    fn process_lines(&mut self) -> std::io::Result<()> {
         let mut lines = Vec::new();
+        let mut read_result = Ok(());
         loop {
-            let mut line = String::new();
-            match self.player_log_reader.read_line(&mut line) {
+            let mut line = Vec::new();
+            match self.player_log_reader.read_until(b'\n', &mut line) {
                 Ok(0) => break,
-                Ok(_) => lines.push(line),
+                // a stray invalid byte shouldn't stop the rest of the log from being read
+                Ok(_) => lines.push(String::from_utf8_lossy(&line).into_owned()),
                 Err(e) => {
-                    error!("Error reading line: {:?}", e);
+                    read_result = Err(e);
                     break;
                 }
             }
         }
         for line in lines {
             let json_strings = self.process_line(&line);
-            self.json_events.extend(json_strings);
+            let first_new_event = self.json_events.len();
+            self.json_events
+                .extend(
+                    json_strings
+                        .into_iter()
+                        .map(|(envelope, json_str)| QueuedEvent {
+                            envelope,
+                            json_str,
+                            resume_position: None,
+                        }),
+                );
+            // a line MTGA hasn't finished writing isn't a safe place to restart from
+            if !line.ends_with('\n') || self.json_events.len() == first_new_event {
+                continue;
+            }
+            if let Some(last_event) = self.json_events.back_mut() {
+                last_event.resume_position = self.json_extractor.position();
+            }
         }
+        read_result
     }
 }
 
+/// `Player.log` -> `Player-prev.log`
+fn previous_log_path(player_log_path: &Path) -> PathBuf {
+    let stem = player_log_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut file_name = format!("{stem}-prev");
+    if let Some(extension) = player_log_path.extension() {
+        file_name = format!("{file_name}.{}", extension.to_string_lossy());
+    }
+    player_log_path.with_file_name(file_name)
+}
+
 impl ArenaEventSource for PlayerLogProcessor {
-    fn get_next_event(&mut self) -> StdResult<ParseOutput, ParseError> {
-        self.process_lines();
-        let event = self.json_events.pop_front().ok_or(ParseError::NoEvent)?;
-        parse(&event).map_err(|e| {
-            error!("Error parsing event: {}", e);
-            debug!("Event: {}", event);
-            ParseError::Error(event)
-        })
+    fn get_next_log_event(&mut self) -> StdResult<LogEvent, ParseError> {
+        self.process_lines().map_err(ParseError::Io)?;
+        if self.json_events.is_empty() && self.switch_log_if_needed() {
+            self.process_lines().map_err(ParseError::Io)?;
+        }
+        let QueuedEvent {
+            envelope,
+            json_str,
+            resume_position,
+        } = self.json_events.pop_front().ok_or(ParseError::EndOfInput)?;
+        if let Some(resume_position) = resume_position {
+            self.position = resume_position;
+        }
+        match parse_with_envelope(&json_str, &envelope) {
+            Ok(Some(output)) => Ok(LogEvent { envelope, output }),
+            Ok(None) => Err(ParseError::UnknownShape(Box::new(UnparsedEvent {
+                envelope,
+                json_str,
+            }))),
+            Err(e) => Err(ParseError::Deserialize(
+                Box::new(UnparsedEvent { envelope, json_str }),
+                e,
+            )),
+        }
+    }
+}
+
+// how long to wait between checks of the log when filesystem notifications are unavailable
+const POLLING_INTERVAL: Duration = Duration::from_secs(1);
+// notifications can be delayed or dropped (e.g. network drives), so check now and then regardless
+const NOTIFY_POLLING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Wraps a `PlayerLogProcessor` so `get_next_log_event` waits for MTGA to write more events
+/// instead of returning `ParseError::EndOfInput`, until it is stopped.
+/// Wakes up on filesystem notifications for Player.log, falling back to polling
+pub struct FollowingEventSource {
+    processor: PlayerLogProcessor,
+    // notifications stop once the watcher is dropped
+    _watcher: Option<RecommendedWatcher>,
+    wake_rx: Receiver<()>,
+    stop_handle: FollowingStopHandle,
+    poll_interval: Duration,
+}
+
+/// Stops a `FollowingEventSource` from another thread, e.g. a ctrl-c handler
+#[derive(Debug, Clone)]
+pub struct FollowingStopHandle {
+    stopped: Arc<AtomicBool>,
+    wake_tx: Sender<()>,
+}
+
+impl FollowingStopHandle {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.wake_tx.send(()).unwrap_or(());
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+}
+
+impl FollowingEventSource {
+    pub fn new(processor: PlayerLogProcessor) -> Self {
+        let (wake_tx, wake_rx) = channel();
+        // logs that aren't files can only be polled
+        let watcher = processor.player_log_path().and_then(|player_log_path| {
+            watch_player_log(player_log_path, wake_tx.clone())
+                .map_err(|e| warn!("Could not watch Player.log for changes, polling instead: {e}"))
+                .ok()
+        });
+        let poll_interval = if watcher.is_some() {
+            NOTIFY_POLLING_INTERVAL
+        } else {
+            POLLING_INTERVAL
+        };
+        Self {
+            processor,
+            _watcher: watcher,
+            wake_rx,
+            stop_handle: FollowingStopHandle {
+                stopped: Arc::new(AtomicBool::new(false)),
+                wake_tx,
+            },
+            poll_interval,
+        }
+    }
+
+    #[must_use]
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn stop_handle(&self) -> FollowingStopHandle {
+        self.stop_handle.clone()
+    }
+
+    pub fn processor(&self) -> &PlayerLogProcessor {
+        &self.processor
+    }
+}
+
+fn watch_player_log(player_log_path: &Path, wake_tx: Sender<()>) -> Result<RecommendedWatcher> {
+    let file_name = player_log_path.file_name().map(ToOwned::to_owned);
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        // the directory is watched so a replaced Player.log is noticed too
+        if event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == file_name.as_deref())
+        {
+            wake_tx.send(()).unwrap_or(());
+        }
+    })?;
+    let directory = match player_log_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    Ok(watcher)
+}
+
+impl ArenaEventSource for FollowingEventSource {
+    /// Blocks until the next event is written to the log.
+    /// Only returns `ParseError::EndOfInput` once stopped
+    fn get_next_log_event(&mut self) -> StdResult<LogEvent, ParseError> {
+        loop {
+            match self.processor.get_next_log_event() {
+                Err(ParseError::EndOfInput) => {}
+                result => return result,
+            }
+            if self.stop_handle.is_stopped() {
+                return Err(ParseError::EndOfInput);
+            }
+            // a timeout just means it's time to poll
+            self.wake_rx.recv_timeout(self.poll_interval).unwrap_or(());
+            self.wake_rx.try_iter().for_each(drop);
+        }
     }
 }
 
@@ -115,31 +639,560 @@ pub enum ParseOutput {
     ClientMessage(RequestTypeClientToMatchServiceMessage),
     MGRSCMessage(RequestTypeMGRSCEvent),
     BusinessMessage(RequestTypeBusinessEvent),
-    NoEvent,
+    FrontDoor(Box<FrontDoorEvent>),
 }
 
+/// A json event from the log that could not be turned into a `ParseOutput`
+#[derive(Debug)]
+pub struct UnparsedEvent {
+    pub envelope: LogEnvelope,
+    pub json_str: String,
+}
+
+#[derive(Debug)]
 pub enum ParseError {
-    NoEvent,
-    Error(String),
+    /// every event written so far has been returned, MTGA may still write more
+    EndOfInput,
+    /// json that isn't shaped like any event we know about
+    UnknownShape(Box<UnparsedEvent>),
+    /// json that looks like a known event, but doesn't decode as one
+    Deserialize(Box<UnparsedEvent>, DeserializeError),
+    /// the log could not be read
+    Io(std::io::Error),
 }
 
+impl ParseError {
+    /// True for problems with a single event, which later events aren't affected by
+    pub fn is_bad_event(&self) -> bool {
+        matches!(self, Self::UnknownShape(_) | Self::Deserialize(..))
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EndOfInput => write!(f, "No more events in the log yet"),
+            Self::UnknownShape(event) => write!(
+                f,
+                "Unrecognized event on line {}",
+                event.envelope.line_number
+            ),
+            Self::Deserialize(event, e) => write!(
+                f,
+                "Could not decode event on line {}: {e}",
+                event.envelope.line_number
+            ),
+            Self::Io(e) => write!(f, "Could not read log: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deserialize(_, e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::EndOfInput | Self::UnknownShape(_) => None,
+        }
+    }
+}
+
+/// A serde error along with where in the json it happened
+#[derive(Debug)]
+pub struct DeserializeError {
+    /// e.g. `greToClientEvent.greToClientMessages[0].gameStateMessage.turnInfo.phase`
+    pub path: String,
+    pub source: serde_json::Error,
+}
+
+impl DeserializeError {
+    /// line within the event json, 0 if the error came from decoding an already parsed value
+    pub fn line(&self) -> usize {
+        self.source.line()
+    }
+
+    pub fn column(&self) -> usize {
+        self.source.column()
+    }
+}
+
+impl std::fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at `{}`", self.source, self.path)
+    }
+}
+
+impl std::error::Error for DeserializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<serde_path_to_error::Error<serde_json::Error>> for DeserializeError {
+    fn from(e: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        Self {
+            path: e.path().to_string(),
+            source: e.into_inner(),
+        }
+    }
+}
+
+/// `serde_json::from_str`, keeping track of where in the json decoding failed
+///
+/// # Errors
+///
+/// Errors if `json_str` does not decode as a `T`
+pub fn from_json_str<T: DeserializeOwned>(json_str: &str) -> StdResult<T, DeserializeError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json_str);
+    Ok(serde_path_to_error::deserialize(deserializer)?)
+}
+
+/// `serde_json::from_value`, keeping track of where in the json decoding failed
+///
+/// # Errors
+///
+/// Errors if `value` does not decode as a `T`
+pub fn from_json_value<T: DeserializeOwned>(value: Value) -> StdResult<T, DeserializeError> {
+    Ok(serde_path_to_error::deserialize(value)?)
+}
+
+/// Returns `None` for json that isn't shaped like any event we know about
+///
 /// # Errors
 ///
 /// Errors if event appears to be a relevant json string, but does not decode properly
-pub fn parse(event: &str) -> Result<ParseOutput> {
+pub fn parse(event: &str) -> StdResult<Option<ParseOutput>, DeserializeError> {
     if event.contains("clientToMatchServiceMessage") {
-        let client_to_match_service_message: RequestTypeClientToMatchServiceMessage =
-            serde_json::from_str(event)?;
-        Ok(ParseOutput::ClientMessage(client_to_match_service_message))
+        Ok(Some(ParseOutput::ClientMessage(from_json_str(event)?)))
     } else if event.contains("matchGameRoomStateChangedEvent") {
-        let mgrsc_event: RequestTypeMGRSCEvent = serde_json::from_str(event)?;
-        Ok(ParseOutput::MGRSCMessage(mgrsc_event))
+        Ok(Some(ParseOutput::MGRSCMessage(from_json_str(event)?)))
     } else if event.contains("greToClientEvent") {
-        let request_gre_to_client_event: RequestTypeGREToClientEvent = serde_json::from_str(event)?;
-        Ok(ParseOutput::GREMessage(request_gre_to_client_event))
-    } else if let Ok(business_event) = serde_json::from_str::<RequestTypeBusinessEvent>(event) {
-        Ok(ParseOutput::BusinessMessage(business_event))
+        Ok(Some(ParseOutput::GREMessage(from_json_str(event)?)))
     } else {
-        Ok(ParseOutput::NoEvent)
+        Ok(serde_json::from_str::<RequestTypeBusinessEvent>(event)
+            .ok()
+            .map(ParseOutput::BusinessMessage))
+    }
+}
+
+/// Like `parse`, but uses the log header to recognize front door API calls,
+/// whose method name is only found in the header
+///
+/// # Errors
+///
+/// Errors if event appears to be a relevant json string, but does not decode properly
+pub fn parse_with_envelope(
+    event: &str,
+    envelope: &LogEnvelope,
+) -> StdResult<Option<ParseOutput>, DeserializeError> {
+    let parse_output = parse(event)?;
+    if matches!(parse_output, None | Some(ParseOutput::BusinessMessage(_)))
+        && envelope.method.as_deref() != Some(BUSINESS_EVENT_METHOD)
+    {
+        if let Some(front_door_event) = FrontDoorEvent::from_envelope(envelope, event)? {
+            return Ok(Some(ParseOutput::FrontDoor(Box::new(front_door_event))));
+        }
+    }
+    Ok(parse_output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse, parse_with_envelope, previous_log_path, ArenaEventSource, FollowingEventSource,
+        JsonExtractor, ParseError, ParseOutput, PlayerLogProcessor,
+    };
+    use crate::log_envelope::{Direction, LogEnvelope};
+    use anyhow::Result;
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    fn scratch_dir(name: &str) -> Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("ap_core_{}_{name}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    // the scratch logs use made up json, which parses as an unknown shape
+    fn next_envelope(processor: &mut impl ArenaEventSource) -> Option<LogEnvelope> {
+        match processor.get_next_log_event() {
+            Ok(log_event) => Some(log_event.envelope),
+            Err(ParseError::UnknownShape(event)) => Some(event.envelope),
+            Err(_) => None,
+        }
+    }
+
+    fn next_line_number(processor: &mut impl ArenaEventSource) -> Option<usize> {
+        next_envelope(processor).map(|envelope| envelope.line_number)
+    }
+
+    fn extract(lines: &[&str]) -> Vec<String> {
+        let mut extractor = JsonExtractor::default();
+        lines
+            .iter()
+            .flat_map(|line| extractor.process_line(line))
+            .map(|(_, json_str)| json_str)
+            .collect()
+    }
+
+    #[test]
+    fn test_extract_strips_whitespace_outside_strings() {
+        let events = extract(&["[UnityCrossThreadLogger] { \"a\" : 1,\t\"b\": [1, 2] }\r\n"]);
+        assert_eq!(events, vec![r#"{"a":1,"b":[1,2]}"#]);
+    }
+
+    #[test]
+    fn test_extract_preserves_spaces_in_strings() {
+        let events =
+            extract(&[r#"{"playerName": "Sir  Grant the 3rd", "deckName": "Mono Red Aggro"}"#]);
+        assert_eq!(
+            events,
+            vec![r#"{"playerName":"Sir  Grant the 3rd","deckName":"Mono Red Aggro"}"#]
+        );
+    }
+
+    #[test]
+    fn test_extract_braces_in_strings() {
+        let events = extract(&[r#"{"name": "}{ weird {deck", "n": {"x": "}"}}"#]);
+        assert_eq!(events, vec![r#"{"name":"}{ weird {deck","n":{"x":"}"}}"#]);
+        let parsed: serde_json::Value = serde_json::from_str(&events[0]).unwrap_or_default();
+        assert_eq!(parsed["name"], "}{ weird {deck");
+    }
+
+    #[test]
+    fn test_extract_escaped_quotes_and_backslashes() {
+        let events = extract(&[r#"{"a": "say \"hi }\" ", "b": "C:\\ {", "c": "\\"}"#]);
+        assert_eq!(
+            events,
+            vec![r#"{"a":"say \"hi }\" ","b":"C:\\ {","c":"\\"}"#]
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&events[0]).unwrap_or_default();
+        assert_eq!(parsed["a"], "say \"hi }\" ");
+        assert_eq!(parsed["b"], "C:\\ {");
+        assert_eq!(parsed["c"], "\\");
+    }
+
+    #[test]
+    fn test_extract_multiline_event() {
+        let events = extract(&[
+            "<== EventGetCoursesV2(abc)\n",
+            "{\n",
+            "  \"Courses\": [\n",
+            "    { \"InternalEventName\": \"Quick Draft {MKM}\" }\n",
+            "  ]\n",
+            "}\n",
+        ]);
+        assert_eq!(
+            events,
+            vec![r#"{"Courses":[{"InternalEventName":"Quick Draft {MKM}"}]}"#]
+        );
+    }
+
+    #[test]
+    fn test_extract_multiple_events_per_line() {
+        let events = extract(&[r#"{"a": "x y"} noise "quoted noise" {"b": "{"}"#]);
+        assert_eq!(events, vec![r#"{"a":"x y"}"#, r#"{"b":"{"}"#]);
+    }
+
+    #[test]
+    fn test_extract_ignores_stray_closing_brace() {
+        let mut extractor = JsonExtractor::default();
+        assert!(extractor.process_line("} not json }").is_empty());
+        assert_eq!(extractor.bracket_depth, 0);
+        let events = extractor.process_line(r#"{"a": 1}"#);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1, r#"{"a":1}"#);
+        assert_eq!(extractor.bracket_depth, 0);
+        assert!(!extractor.in_string);
+    }
+
+    #[test]
+    fn test_extract_unicode_strings() {
+        let events = extract(&[r#"{"name": "Jürgen 🃏 {x}"}"#]);
+        assert_eq!(events, vec![r#"{"name":"Jürgen 🃏 {x}"}"#]);
+    }
+
+    #[test]
+    fn test_envelopes() {
+        let mut extractor = JsonExtractor::default();
+        let lines = [
+            "[UnityCrossThreadLogger]10/17/2026 8:15:02 PM\n",
+            "[UnityCrossThreadLogger]==> EventJoin {\"id\":\"abc\",\"request\":\"{}\"}\n",
+            "<== EventJoin(abc)\n",
+            "{\n",
+            "  \"CurrentModule\": \"Join\"\n",
+            "}\n",
+        ];
+        let events: Vec<_> = lines
+            .iter()
+            .flat_map(|line| extractor.process_line(line))
+            .collect();
+        assert_eq!(events.len(), 2);
+
+        let (request, _) = &events[0];
+        assert_eq!(request.logger.as_deref(), Some("UnityCrossThreadLogger"));
+        assert_eq!(request.direction, Direction::Request);
+        assert_eq!(request.method.as_deref(), Some("EventJoin"));
+        assert_eq!(request.line_number, 2);
+        assert_eq!(
+            request.byte_offset,
+            (lines[0].len() + lines[1].find('{').unwrap_or_default()) as u64
+        );
+        assert!(request.timestamp.is_some());
+
+        let (response, json_str) = &events[1];
+        assert_eq!(json_str, r#"{"CurrentModule":"Join"}"#);
+        assert_eq!(response.logger, None);
+        assert_eq!(response.direction, Direction::Response);
+        assert_eq!(response.method.as_deref(), Some("EventJoin"));
+        assert_eq!(response.request_id.as_deref(), Some("abc"));
+        assert_eq!(response.line_number, 4);
+        assert_eq!(
+            response.byte_offset,
+            lines[..3].iter().map(|line| line.len() as u64).sum::<u64>()
+        );
+        assert_eq!(response.timestamp, request.timestamp);
+    }
+
+    #[test]
+    fn test_parse_front_door_request() {
+        let mut extractor = JsonExtractor::default();
+        let events = extractor.process_line(
+            r#"[UnityCrossThreadLogger]==> EventJoin {"id":"abc","request":"{\"EventName\":\"Ladder\"}"}"#,
+        );
+        let (envelope, event) = &events[0];
+        let parse_output = parse_with_envelope(event, envelope);
+        assert!(matches!(parse_output, Ok(Some(ParseOutput::FrontDoor(_)))));
+    }
+
+    #[test]
+    fn test_parse_business_event_with_envelope() {
+        let mut extractor = JsonExtractor::default();
+        let events = extractor.process_line(
+            r#"[UnityCrossThreadLogger]==> LogBusinessEvents {"id":"abc","request":"{\"EventId\":\"Ladder\"}"}"#,
+        );
+        let (envelope, event) = &events[0];
+        let parse_output = parse_with_envelope(event, envelope);
+        assert!(matches!(
+            parse_output,
+            Ok(Some(ParseOutput::BusinessMessage(_)))
+        ));
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() -> Result<()> {
+        let log_path =
+            std::env::temp_dir().join(format!("ap_core_{}_resume.log", std::process::id()));
+        std::fs::write(
+            &log_path,
+            "[UnityCrossThreadLogger]10/17/2026 8:15:02 PM\n{\"a\": 1}\n{\"b\":\n",
+        )?;
+        let mut processor = PlayerLogProcessor::try_new(log_path.clone())?;
+        let first = next_envelope(&mut processor);
+        assert_eq!(first.as_ref().map(|e| e.line_number), Some(2));
+        // the second event is still being written
+        assert!(matches!(
+            processor.get_next_log_event(),
+            Err(ParseError::EndOfInput)
+        ));
+        let checkpoint = processor.checkpoint(processor.position().clone())?;
+        assert_eq!(checkpoint.position.line_number, 2);
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log_path)?;
+        file.write_all(b"2}\n{\"c\": 3}\n")?;
+        let mut resumed = PlayerLogProcessor::try_new(log_path.clone())?;
+        assert!(resumed.resume_from(&checkpoint)?);
+        let second = next_envelope(&mut resumed);
+        assert_eq!(second.as_ref().map(|e| e.line_number), Some(3));
+        assert_eq!(
+            second.and_then(|e| e.timestamp),
+            first.and_then(|e| e.timestamp)
+        );
+        let third = next_envelope(&mut resumed);
+        assert_eq!(third.map(|e| e.line_number), Some(5));
+        assert!(matches!(
+            resumed.get_next_log_event(),
+            Err(ParseError::EndOfInput)
+        ));
+
+        std::fs::write(&log_path, "{\"new\": 1}\n")?;
+        let mut restarted = PlayerLogProcessor::try_new(log_path.clone())?;
+        assert!(!restarted.resume_from(&checkpoint)?);
+        let first = next_envelope(&mut restarted);
+        assert_eq!(first.map(|e| e.line_number), Some(1));
+        std::fs::remove_file(log_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_previous_log_path() {
+        assert_eq!(
+            previous_log_path(&PathBuf::from("/logs/Player.log")),
+            PathBuf::from("/logs/Player-prev.log")
+        );
+    }
+
+    #[test]
+    fn test_reopens_rotated_log() -> Result<()> {
+        let dir = scratch_dir("rotated")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(&log_path, "{\"a\": 1}\n{\"b\": 2}\n")?;
+        let mut processor = PlayerLogProcessor::try_new(log_path.clone())?;
+        assert_eq!(next_line_number(&mut processor), Some(1));
+        assert_eq!(next_line_number(&mut processor), Some(2));
+        assert!(next_line_number(&mut processor).is_none());
+
+        std::fs::rename(&log_path, dir.join("Player-prev.log"))?;
+        std::fs::write(&log_path, "noise\n{\"c\": 3}\n")?;
+        assert_eq!(next_line_number(&mut processor), Some(2));
+        assert_eq!(processor.position().line_number, 2);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopens_truncated_log() -> Result<()> {
+        let dir = scratch_dir("truncated")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(&log_path, "noise\nnoise\n{\"a\": 1}\n")?;
+        let mut processor = PlayerLogProcessor::try_new(log_path.clone())?;
+        assert_eq!(next_line_number(&mut processor), Some(3));
+
+        std::fs::write(&log_path, "{\"b\": 2}\n")?;
+        assert_eq!(next_line_number(&mut processor), Some(1));
+        assert!(next_line_number(&mut processor).is_none());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reads_previous_log_first() -> Result<()> {
+        let dir = scratch_dir("previous")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(dir.join("Player-prev.log"), "{\"a\": 1}\n")?;
+        std::fs::write(&log_path, "noise\n{\"b\": 2}\n")?;
+        let mut processor = PlayerLogProcessor::try_new_with_previous_log(log_path.clone())?;
+        assert_eq!(next_line_number(&mut processor), Some(1));
+        let checkpoint = processor.checkpoint(processor.position().clone())?;
+        assert_eq!(next_line_number(&mut processor), Some(2));
+        assert!(next_line_number(&mut processor).is_none());
+
+        // a checkpoint taken at the end of Player-prev.log resumes there
+        let mut resumed = PlayerLogProcessor::try_new_with_previous_log(log_path.clone())?;
+        assert!(resumed.resume_from(&checkpoint)?);
+        assert_eq!(next_line_number(&mut resumed), Some(2));
+
+        // a checkpoint taken in Player.log skips Player-prev.log entirely
+        let checkpoint = resumed.checkpoint(resumed.position().clone())?;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_path)?
+            .write_all(b"{\"c\": 3}\n")?;
+        let mut resumed = PlayerLogProcessor::try_new_with_previous_log(log_path)?;
+        assert!(resumed.resume_from(&checkpoint)?);
+        assert_eq!(next_line_number(&mut resumed), Some(3));
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_after_switching_logs() -> Result<()> {
+        let dir = scratch_dir("switched")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(dir.join("Player-prev.log"), "noise\nnoise\n{\"a\": 1}\n")?;
+        std::fs::write(&log_path, "{\"b\": 2}\n{\"c\": 3}\n")?;
+        let mut processor = PlayerLogProcessor::try_new_with_previous_log(log_path.clone())?;
+        assert_eq!(next_line_number(&mut processor), Some(3));
+        // e.g. where a match still in progress when MTGA restarted began
+        let previous_position = processor.position().clone();
+        assert_eq!(next_line_number(&mut processor), Some(1));
+        assert_ne!(
+            processor.position().generation,
+            previous_position.generation
+        );
+        assert!(processor.checkpoint(previous_position).is_err());
+
+        let checkpoint = processor.checkpoint(processor.position().clone())?;
+        assert_eq!(checkpoint.fingerprint, b"{\"b\": 2}\n");
+        let mut resumed = PlayerLogProcessor::try_new_with_previous_log(log_path)?;
+        assert!(resumed.resume_from(&checkpoint)?);
+        assert_eq!(next_line_number(&mut resumed), Some(2));
+        assert!(next_line_number(&mut resumed).is_none());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_following_event_source() -> Result<()> {
+        let dir = scratch_dir("following")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(&log_path, "{\"a\": 1}\n")?;
+        let mut source = FollowingEventSource::new(PlayerLogProcessor::try_new(log_path.clone())?)
+            .with_poll_interval(Duration::from_millis(50));
+        let stop_handle = source.stop_handle();
+        assert_eq!(next_line_number(&mut source), Some(1));
+
+        let writer = std::thread::spawn(move || -> Result<()> {
+            std::thread::sleep(Duration::from_millis(100));
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(&log_path)?
+                .write_all(b"{\"b\": 2}\n")?;
+            std::thread::sleep(Duration::from_millis(100));
+            stop_handle.stop();
+            Ok(())
+        });
+        assert_eq!(next_line_number(&mut source), Some(2));
+        assert!(matches!(
+            source.get_next_log_event(),
+            Err(ParseError::EndOfInput)
+        ));
+        writer.join().unwrap_or(Ok(()))?;
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_shape() {
+        assert!(matches!(parse(r#"{"unrelated": true}"#), Ok(None)));
+    }
+
+    #[test]
+    fn test_parse_error_has_path() {
+        let Err(e) = parse(r#"{"greToClientEvent":{"greToClientMessages":"oops"}}"#) else {
+            panic!("expected a deserialize error");
+        };
+        assert_eq!(e.path, "greToClientEvent.greToClientMessages");
+        assert_eq!(e.line(), 1);
+        assert!(e.column() > 0);
+    }
+
+    #[test]
+    fn test_keeps_going_past_bad_events() -> Result<()> {
+        let dir = scratch_dir("bad_events")?;
+        let log_path = dir.join("Player.log");
+        std::fs::write(
+            &log_path,
+            concat!(
+                "{\"greToClientEvent\":{\"greToClientMessages\":\"oops\"}}\n",
+                "{\"unrelated\": true}\n",
+                "[UnityCrossThreadLogger]==> EventJoin {\"id\":\"abc\",\"request\":\"{\\\"EventName\\\":\\\"Ladder\\\"}\"}\n",
+            ),
+        )?;
+        let mut processor = PlayerLogProcessor::try_new(log_path)?;
+        let mut errors = Vec::new();
+        let log_event = processor.get_next_valid_log_event(&mut |e| errors.push(e));
+        assert_eq!(log_event.map(|e| e.envelope.line_number), Some(3));
+        assert!(matches!(
+            errors[..],
+            [ParseError::Deserialize(..), ParseError::UnknownShape(_)]
+        ));
+        assert!(processor
+            .get_next_valid_log_event(&mut |e| errors.push(e))
+            .is_none());
+        assert_eq!(errors.len(), 2);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
     }
 }
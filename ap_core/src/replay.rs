@@ -1,28 +1,35 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::vec::IntoIter;
 
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Serializer};
+use serde_json::Value;
 use tracing::{debug, info, warn};
 
-use crate::cards::CardsDatabase;
-use crate::models::deck::Deck;
+use crate::cards::{CardsDatabase, LegalityTable};
+use crate::game_state::GameStateTracker;
+use crate::models::deck::{Deck, NamedDeck};
+use crate::models::deck_diff::DeckDiff;
+use crate::models::deck_validation::{DeckConstraints, DeckViolation};
+use crate::models::match_result::{MatchResult, MatchResultBuilder};
 use crate::models::mulligan::MulliganInfo;
 use crate::models::mulligan::MulliganInfoBuilder;
+use crate::models::rank::{MatchRanks, RankSnapshot};
+use crate::models::turn_snapshot::TurnSnapshot;
 use crate::mtga_events::business::BusinessEventRequest;
-use crate::mtga_events::client::{
-    ClientMessage, MulliganOption, MulliganRespWrapper, RequestTypeClientToMatchServiceMessage,
+use crate::mtga_events::client::{MulliganOption, RequestTypeClientToMatchServiceMessage};
+use crate::mtga_events::frontdoor::{
+    FrontDoorEvent, FrontDoorRequestPayload, FrontDoorResponsePayload,
 };
 use crate::mtga_events::gre::{
-    DeckMessage, GREToClientMessage, GameObjectType, GameStateMessage, MulliganReqWrapper,
-    RequestTypeGREToClientEvent,
+    GREToClientMessage, GameInfo, GameObjectType, GameStateMessage, RequestTypeGREToClientEvent,
 };
 use crate::mtga_events::mgrsc::{FinalMatchResult, RequestTypeMGRSCEvent, StateType};
-use crate::mtga_events::primitives::ZoneType;
-use crate::processor::ParseOutput;
-
-const DEFAULT_HAND_SIZE: i32 = 7;
+use crate::processor::{from_json_str, from_json_value, DeserializeError, LogEvent, ParseOutput};
 
 #[derive(Debug, Default)]
 pub struct MatchReplay {
@@ -31,6 +38,9 @@ pub struct MatchReplay {
     pub match_end_message: RequestTypeMGRSCEvent,
     pub client_server_messages: Vec<MatchReplayEvent>,
     pub business_messages: Vec<BusinessEventRequest>,
+    pub rank_snapshots: Vec<RankSnapshot>,
+    /// decks saved or submitted to an event since the previous match
+    pub named_decks: Vec<NamedDeck>,
 }
 
 #[derive(Debug, Clone)]
@@ -45,9 +55,11 @@ pub enum MatchReplayEventRef<'a> {
     Client(&'a RequestTypeClientToMatchServiceMessage),
     MGRSC(&'a RequestTypeMGRSCEvent),
     Business(&'a BusinessEventRequest),
+    Rank(&'a RankSnapshot),
+    NamedDeck(&'a NamedDeck),
 }
 
-impl<'a> Serialize for MatchReplayEventRef<'a> {
+impl Serialize for MatchReplayEventRef<'_> {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -57,6 +69,8 @@ impl<'a> Serialize for MatchReplayEventRef<'a> {
             Self::GRE(event) => event.serialize(serializer),
             Self::Client(event) => event.serialize(serializer),
             Self::Business(event) => event.serialize(serializer),
+            Self::Rank(event) => event.serialize(serializer),
+            Self::NamedDeck(event) => event.serialize(serializer),
         }
     }
 }
@@ -76,7 +90,7 @@ impl MatchReplay {
             .flat_map(|gre| &gre.gre_to_client_event.gre_to_client_messages)
     }
 
-    fn game_state_messages_iter(&self) -> impl Iterator<Item = &GameStateMessage> {
+    pub(crate) fn game_state_messages_iter(&self) -> impl Iterator<Item = &GameStateMessage> {
         self.gre_messages_iter()
             .filter_map(|gre_message| match gre_message {
                 GREToClientMessage::GameStateMessage(wrapper) => Some(&wrapper.game_state_message),
@@ -84,24 +98,15 @@ impl MatchReplay {
             })
     }
 
-    fn client_messages_iter(
-        &self,
-    ) -> impl Iterator<Item = &RequestTypeClientToMatchServiceMessage> {
-        self.client_server_messages
-            .iter()
-            .filter_map(|mre| match mre {
-                MatchReplayEvent::Client(message) => Some(message),
-                _ => None,
-            })
-    }
-
     /// # Errors
     ///
     /// Returns an error if the controller seat ID is not found
     pub(crate) fn get_controller_seat_id(&self) -> Result<i32> {
         for gre_message in self.gre_messages_iter() {
             if let GREToClientMessage::ConnectResp(wrapper) = gre_message {
-                return Ok(wrapper.meta.system_seat_ids[0]);
+                if let Some(seat_id) = wrapper.meta.system_seat_ids.first() {
+                    return Ok(*seat_id);
+                }
             }
         }
         Err(anyhow!("Controller seat ID not found"))
@@ -148,7 +153,7 @@ impl MatchReplay {
     /// # Errors
     ///
     /// Returns an error if the controller seat id is not found
-    fn get_opponent_color_identity(&self, cards_db: &CardsDatabase) -> Result<String> {
+    pub(crate) fn get_opponent_color_identity(&self, cards_db: &CardsDatabase) -> Result<String> {
         let opponent_cards = self.get_opponent_cards()?;
         let mut color_identity = BTreeSet::new();
         for card in opponent_cards {
@@ -168,7 +173,7 @@ impl MatchReplay {
     /// # Errors
     ///
     /// Returns an error if the match results are not found
-    pub fn get_match_results(&self) -> Result<FinalMatchResult> {
+    pub fn get_final_match_result(&self) -> Result<FinalMatchResult> {
         self.match_end_message
             .mgrsc_event
             .game_room_info
@@ -177,26 +182,31 @@ impl MatchReplay {
             .ok_or(anyhow!("Match results not found"))
     }
 
+    /// Result of every game, numbered like `games`, followed by the match result as game 0
+    ///
     /// # Errors
     ///
-    /// Returns an error if there is no `ConnectResp` in the GRE events
-    fn get_initial_decklist(&self) -> Result<DeckMessage> {
-        for gre_message in self.gre_messages_iter() {
-            if let GREToClientMessage::ConnectResp(wrapper) = gre_message {
-                return Ok(wrapper.connect_resp.deck_message.clone());
-            }
-        }
-        Err(anyhow!("Initial decklist not found"))
-    }
-
-    fn get_sideboarded_decklists(&self) -> Vec<DeckMessage> {
-        self.client_messages_iter()
-            .filter_map(|message| {
-                if let ClientMessage::SubmitDeckResp(submit_deck_resp) = &message.payload {
-                    Some(submit_deck_resp.submit_deck_resp.deck.clone())
-                } else {
-                    None
-                }
+    /// Returns an error if the match results are not found
+    pub fn get_match_results(&self) -> Result<Vec<MatchResult>> {
+        let final_match_result = self.get_final_match_result()?;
+        let game_results = self.games().into_iter().filter_map(|game| {
+            let result = game.result?;
+            Some((game.game_number, result))
+        });
+        let match_results = final_match_result
+            .result_list
+            .into_iter()
+            .filter(|result| result.scope != "MatchScope_Game")
+            .map(|result| (0, result));
+        game_results
+            .chain(match_results)
+            .map(|(game_number, result)| {
+                Ok(MatchResultBuilder::default()
+                    .match_id(self.match_id.clone())
+                    .game_number(game_number)
+                    .winning_team_id(result.winning_team_id)
+                    .result_scope(result.scope)
+                    .build()?)
             })
             .collect()
     }
@@ -205,166 +215,98 @@ impl MatchReplay {
     ///
     /// Returns an Error if the initial decklist is not found
     pub fn get_decklists(&self) -> Result<Vec<Deck>> {
-        let mut decklists = vec![self.get_initial_decklist()?];
-        decklists.append(&mut self.get_sideboarded_decklists());
-        Ok(decklists
-            .iter()
-            .map(|deck| -> Deck { deck.into() })
-            .enumerate()
-            .map(|(i, mut deck)| {
-                deck.game_number = i32::try_from(i).unwrap_or_else(|e| {
-                    warn!("Error converting usize to i32: {}", e);
-                    0
-                }) + 1;
-                deck
-            })
+        let mut decklists: Vec<Deck> = self
+            .games()
+            .into_iter()
+            .filter_map(|game| game.deck)
+            .collect();
+        if decklists.is_empty() {
+            return Err(anyhow!("Initial decklist not found"));
+        }
+        // sideboarded decks of later games keep the name of the deck the match started with
+        if let Some(named_deck) = self.find_named_deck(&decklists[0]) {
+            for deck in &mut decklists {
+                deck.name.clone_from(&named_deck.name);
+            }
+        }
+        Ok(decklists)
+    }
+
+    /// Sideboarding done before each game after the first, with the game it was done for
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the initial decklist is not found
+    pub fn get_deck_diffs(&self) -> Result<Vec<(i32, DeckDiff)>> {
+        Ok(self
+            .get_decklists()?
+            .windows(2)
+            .map(|decks| (decks[1].game_number, DeckDiff::new(&decks[0], &decks[1])))
             .collect())
     }
 
+    /// The most recent saved or submitted deck with the same cards as `deck`
+    pub fn find_named_deck(&self, deck: &Deck) -> Option<&NamedDeck> {
+        let fingerprint = deck.fingerprint();
+        self.named_decks
+            .iter()
+            .rev()
+            .find(|named_deck| named_deck.deck().fingerprint() == fingerprint)
+    }
+
     /// # Errors
     ///
-    /// Returns an error if the controller seat ID is not found among other things
-    #[allow(clippy::too_many_lines)]
+    /// Returns an error if the controller seat ID is not found,
+    /// or a game with mulligans has no play/draw decision
     pub fn get_mulligan_infos(&self, cards_db: &CardsDatabase) -> Result<Vec<MulliganInfo>> {
         let controller_id = self.get_controller_seat_id()?;
-
-        let mut game_number = 1;
-        let mut opening_hands = BTreeMap::<i32, Vec<Vec<i32>>>::new();
-        let mut mulligan_requests = BTreeMap::<i32, Vec<&MulliganReqWrapper>>::new();
-        let mut play_or_draw: BTreeMap<i32, String> = BTreeMap::new();
         let opponent_color_identity = self.get_opponent_color_identity(cards_db)?;
 
-        for gre in self.gre_messages_iter() {
-            match gre {
-                GREToClientMessage::GameStateMessage(wrapper) => {
-                    let gsm = &wrapper.game_state_message;
-
-                    if gsm.players.len() == 2
-                        && gsm.players.iter().all(|player| {
-                            player.pending_message_type
-                                == Some("ClientMessageType_MulliganResp".to_string())
-                        })
-                    {
-                        if let Some(turn_info) = &gsm.turn_info {
-                            if let Some(decision_player) = turn_info.decision_player {
-                                let pd = if decision_player == controller_id {
-                                    "Play"
-                                } else {
-                                    "Draw"
-                                };
-                                info!("game_number: {}, play_or_draw: {}", game_number, pd);
-                                play_or_draw.insert(game_number, pd.to_string());
-                            }
-                        }
-                    }
-
-                    if gsm.players.iter().any(|player| {
-                        player.controller_seat_id == controller_id
-                            && player.pending_message_type
-                                == Some("ClientMessageType_MulliganResp".to_string())
-                    }) {
-                        let controller_hand_zone_id = gsm
-                            .zones
-                            .iter()
-                            .find(|zone| {
-                                zone.type_field == ZoneType::Hand
-                                    && zone.owner_seat_id == Some(controller_id)
-                            })
-                            .ok_or(anyhow!("Controller hand zone not found"))?
-                            .zone_id;
-                        let game_objects_in_hand: Vec<i32> = gsm
-                            .game_objects
-                            .iter()
-                            .filter(|go| {
-                                let Some(zone_id) = go.zone_id else {
-                                    return false;
-                                };
-                                zone_id == controller_hand_zone_id
-                                    && go.type_field == GameObjectType::Card
-                            })
-                            .map(|go| go.grp_id)
-                            .collect();
-                        opening_hands
-                            .entry(game_number)
-                            .or_default()
-                            .push(game_objects_in_hand);
-                    }
-                }
-                GREToClientMessage::MulliganReq(wrapper) => {
-                    mulligan_requests
-                        .entry(game_number)
-                        .or_default()
-                        .push(wrapper);
-                }
-                GREToClientMessage::IntermissionReq(_) => {
-                    game_number += 1;
-                }
-                _ => {}
+        let mut mulligan_infos = Vec::new();
+        for game in self.games() {
+            if game.mulligans.is_empty() {
+                continue;
             }
-        }
-
-        let mulligan_responses: BTreeMap<i32, &MulliganRespWrapper> = self
-            .client_messages_iter()
-            .filter_map(|client_message| match &client_message.payload {
-                ClientMessage::MulliganResp(wrapper) => {
-                    Some((wrapper.meta.game_state_id?, wrapper))
+            let play_draw = match game.starting_player {
+                Some(seat_id) if seat_id == controller_id => "Play",
+                Some(_) => "Draw",
+                None => {
+                    return Err(anyhow!(
+                        "No play/draw decision found for game {}",
+                        game.game_number
+                    ))
                 }
-                _ => None,
-            })
-            .collect();
-
-        let mut mulligan_infos = Vec::new();
-        for (game_number, hands) in opening_hands {
-            let mut mulligan_requests_iter = mulligan_requests
-                .get(&game_number)
-                .ok_or(anyhow!(
-                    "No mulligan requests found for game {}",
-                    game_number
-                ))?
-                .iter();
-            let play_draw = play_or_draw.get(&game_number).ok_or(anyhow!(
-                "No play/draw decision found for game {}",
-                game_number
-            ))?;
-            for hand in hands {
-                let hand_string = hand
+            };
+            info!(
+                "game_number: {}, play_or_draw: {}",
+                game.game_number, play_draw
+            );
+            let opp_identity = if game.game_number == 1 {
+                "Unknown"
+            } else {
+                &opponent_color_identity
+            };
+            for mulligan in game.mulligans {
+                let hand_string = mulligan
+                    .hand
                     .iter()
                     .map(std::string::ToString::to_string)
                     .collect::<Vec<String>>()
                     .join(",");
-                let Some(mulligan_request) = mulligan_requests_iter.next() else {
-                    warn!("No mulligan request found for game {}", game_number);
-                    continue;
-                };
-                let Some(game_state_id) = mulligan_request.meta.game_state_id else {
-                    warn!("No game state ID found for mulligan request");
-                    continue;
-                };
-                let number_to_keep =
-                    DEFAULT_HAND_SIZE - mulligan_request.mulligan_req.mulligan_count;
-                let decision = match mulligan_responses.get(&game_state_id) {
-                    Some(mulligan_response) => match mulligan_response.mulligan_resp.decision {
-                        MulliganOption::AcceptHand => "Keep",
-                        MulliganOption::Mulligan => "Mulligan",
-                    },
+                let decision = match mulligan.decision {
+                    Some(MulliganOption::AcceptHand) => "Keep",
+                    Some(MulliganOption::Mulligan) => "Mulligan",
                     None => "Match Ended",
-                }
-                .to_string();
-                let opp_identity = if game_number == 1 {
-                    "Unknown"
-                } else {
-                    &opponent_color_identity
-                }
-                .to_string();
+                };
 
                 let mulligan = MulliganInfoBuilder::default()
                     .match_id(self.match_id.clone())
-                    .game_number(game_number)
-                    .number_to_keep(number_to_keep)
+                    .game_number(game.game_number)
+                    .number_to_keep(mulligan.number_to_keep)
                     .hand(hand_string)
-                    .play_draw(play_draw.clone())
-                    .opponent_identity(opp_identity)
-                    .decision(decision)
+                    .play_draw(play_draw.to_string())
+                    .opponent_identity(opp_identity.to_string())
+                    .decision(decision.to_string())
                     .build()?;
 
                 mulligan_infos.push(mulligan);
@@ -378,6 +320,19 @@ impl MatchReplay {
         self.business_messages.iter().find_map(|bm| bm.event_time)
     }
 
+    /// When the match server reported the match over, or else the time of the last game message
+    pub fn match_end_time(&self) -> Option<DateTime<Utc>> {
+        self.match_end_message.timestamp_utc().or_else(|| {
+            self.client_server_messages
+                .iter()
+                .rev()
+                .find_map(|event| match event {
+                    MatchReplayEvent::GRE(gre_event) => gre_event.timestamp_utc(),
+                    _ => None,
+                })
+        })
+    }
+
     /// Gets the format for this match if found (e.g. "`Traditional_Explorer_Ranked`")
     /// MTGA usually underscore-spaces format names
     pub fn match_format(&self) -> Option<String> {
@@ -387,9 +342,170 @@ impl MatchReplay {
             .and_then(|message| message.event_id.clone())
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = MatchReplayEventRef> {
+    /// Closest rank snapshots before the start and after the end of this match, among those
+    /// seen while building it. MTGA usually only reports the new rank once back in the lobby,
+    /// so `after` is often missing here; `MatchInsightDB::get_match_ranks` looks across all
+    /// stored snapshots
+    pub fn match_ranks(&self) -> MatchRanks {
+        let match_start = self.match_start_time().unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let match_end = self.match_end_time().unwrap_or(match_start);
+        MatchRanks::around(&self.rank_snapshots, match_start, match_end)
+    }
+
+    /// The settings of the first game: format, deck size limits and the like
+    pub fn get_game_info(&self) -> Option<&GameInfo> {
+        self.game_state_messages_iter()
+            .find_map(|gsm| gsm.game_info.as_ref())
+    }
+
+    /// Every deck played in the match checked against the match's deck constraints, and
+    /// against format legality when there is a legality table
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the initial decklist or the game info is not found
+    pub fn validate_decks(
+        &self,
+        cards_db: &CardsDatabase,
+        legalities: Option<&LegalityTable>,
+    ) -> Result<Vec<(i32, Vec<DeckViolation>)>> {
+        let game_info = self
+            .get_game_info()
+            .ok_or_else(|| anyhow!("Game info not found"))?;
+        let constraints = DeckConstraints::new(game_info, self.match_format().as_deref());
+        Ok(self
+            .get_decklists()?
+            .iter()
+            .map(|deck| {
+                (
+                    deck.game_number,
+                    constraints.validate(deck, cards_db, legalities),
+                )
+            })
+            .collect())
+    }
+
+    /// The board at the end of every phase of every turn, game by game
+    pub fn turn_snapshots(&self) -> Vec<TurnSnapshot> {
+        let mut tracker = GameStateTracker::new();
+        let mut snapshots: Vec<TurnSnapshot> = Vec::new();
+        for gsm in self.game_state_messages_iter() {
+            let snapshot = match tracker.apply(gsm) {
+                Ok(state) => TurnSnapshot::new(state),
+                Err(e) => {
+                    warn!("Error applying game state {}: {e}", gsm.game_state_id);
+                    continue;
+                }
+            };
+            let Some(snapshot) = snapshot else {
+                continue;
+            };
+            match snapshots.last_mut() {
+                Some(last) if last.same_phase(&snapshot) => *last = snapshot,
+                _ => snapshots.push(snapshot),
+            }
+        }
+        snapshots
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = MatchReplayEventRef<'_>> {
         self.into_iter()
     }
+
+    /// Reads a replay written by `DirectoryStorageBackend`, one event per line
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, a line does not decode,
+    /// or the match start or end message is missing
+    pub fn from_jsonl(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_reader(BufReader::new(File::open(path)?))
+            .map_err(|e| anyhow!("{}: {e}", path.display()))
+    }
+
+    /// Like `from_jsonl`, from any reader
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a line cannot be read or does not decode,
+    /// or the match start or end message is missing
+    pub fn from_reader(reader: impl BufRead) -> Result<Self> {
+        let mut match_start_message = None;
+        let mut client_server_messages = Vec::new();
+        let mut business_messages = Vec::new();
+        let mut rank_snapshots = Vec::new();
+        let mut named_decks = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event =
+                MatchReplayLine::parse(&line).map_err(|e| anyhow!("line {}: {e}", i + 1))?;
+            match event {
+                MatchReplayLine::Event(MatchReplayEvent::MGRSC(mgrsc_event))
+                    if match_start_message.is_none() =>
+                {
+                    match_start_message = Some(mgrsc_event);
+                }
+                MatchReplayLine::Event(event) => client_server_messages.push(event),
+                MatchReplayLine::Business(business_message) => {
+                    business_messages.push(business_message);
+                }
+                MatchReplayLine::Rank(rank_snapshot) => rank_snapshots.push(rank_snapshot),
+                MatchReplayLine::NamedDeck(named_deck) => named_decks.push(named_deck),
+            }
+        }
+        let match_start_message =
+            match_start_message.ok_or(MatchReplayBuilderError::MissingMatchStartMessage)?;
+        // the end message is written right after the last game event
+        let Some(MatchReplayEvent::MGRSC(match_end_message)) = client_server_messages.pop() else {
+            return Err(MatchReplayBuilderError::MissingMatchEndMessage.into());
+        };
+        Ok(Self {
+            match_id: match_start_message
+                .mgrsc_event
+                .game_room_info
+                .game_room_config
+                .match_id
+                .clone(),
+            match_start_message,
+            match_end_message,
+            client_server_messages,
+            business_messages,
+            rank_snapshots,
+            named_decks,
+        })
+    }
+}
+
+/// A line of a replay file, told apart by its top level keys
+enum MatchReplayLine {
+    Event(MatchReplayEvent),
+    Business(BusinessEventRequest),
+    Rank(RankSnapshot),
+    NamedDeck(NamedDeck),
+}
+
+impl MatchReplayLine {
+    fn parse(line: &str) -> std::result::Result<Self, DeserializeError> {
+        let value: Value = from_json_str(line)?;
+        let has_key = |key| value.get(key).is_some();
+        Ok(if has_key("greToClientEvent") {
+            Self::Event(MatchReplayEvent::GRE(from_json_value(value)?))
+        } else if has_key("clientToMatchServiceMessageType") {
+            Self::Event(MatchReplayEvent::Client(from_json_value(value)?))
+        } else if has_key("matchGameRoomStateChangedEvent") {
+            Self::Event(MatchReplayEvent::MGRSC(from_json_value(value)?))
+        } else if has_key("deck_id") {
+            Self::NamedDeck(from_json_value(value)?)
+        } else if has_key("observed_at") {
+            Self::Rank(from_json_value(value)?)
+        } else {
+            Self::Business(from_json_value(value)?)
+        })
+    }
 }
 
 impl<'a> IntoIterator for &'a MatchReplay {
@@ -411,6 +527,12 @@ impl<'a> IntoIterator for &'a MatchReplay {
         self.business_messages.iter().for_each(|bm| {
             events.push(MatchReplayEventRef::Business(bm));
         });
+        self.rank_snapshots.iter().for_each(|rank_snapshot| {
+            events.push(MatchReplayEventRef::Rank(rank_snapshot));
+        });
+        self.named_decks.iter().for_each(|named_deck| {
+            events.push(MatchReplayEventRef::NamedDeck(named_deck));
+        });
         events.into_iter()
     }
 }
@@ -422,6 +544,10 @@ pub struct MatchReplayBuilder {
     pub match_end_message: Option<RequestTypeMGRSCEvent>,
     pub client_server_messages: Vec<MatchReplayEvent>,
     pub business_messages: Vec<BusinessEventRequest>,
+    pub rank_snapshots: Vec<RankSnapshot>,
+    pub named_decks: Vec<NamedDeck>,
+    /// latest time carried by a match message, for front door events logged without one
+    pub last_seen_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug)]
@@ -452,28 +578,106 @@ impl MatchReplayBuilder {
         Self::default()
     }
 
+    /// True if nothing has been collected yet, so no events would be lost
+    /// by restarting processing from this point
+    pub fn is_idle(&self) -> bool {
+        self.match_id.is_none()
+            && self.match_start_message.is_none()
+            && self.client_server_messages.is_empty()
+            && self.business_messages.is_empty()
+            && self.rank_snapshots.is_empty()
+            && self.named_decks.is_empty()
+    }
+
+    /// Front door events carry no time of their own, so they are timed by the latest
+    /// match message and dropped when there is none yet
     pub fn ingest_event(&mut self, event: ParseOutput) -> bool {
         match event {
-            ParseOutput::GREMessage(gre_message) => self
-                .client_server_messages
-                .push(MatchReplayEvent::GRE(gre_message)),
+            ParseOutput::GREMessage(gre_message) => {
+                self.see(gre_message.timestamp_utc());
+                self.client_server_messages
+                    .push(MatchReplayEvent::GRE(gre_message));
+            }
             ParseOutput::ClientMessage(client_message) => self
                 .client_server_messages
                 .push(MatchReplayEvent::Client(client_message)),
             ParseOutput::MGRSCMessage(mgrsc_event) => {
+                self.see(mgrsc_event.timestamp_utc());
                 return self.ingest_mgrc_event(mgrsc_event);
             }
             ParseOutput::BusinessMessage(business_message) => {
                 if business_message.is_relevant() {
                     debug!("Business message: {:?}", business_message);
+                    self.see(business_message.request.event_time);
                     self.business_messages.push(business_message.request);
                 }
             }
-            ParseOutput::NoEvent => {}
+            ParseOutput::FrontDoor(front_door_event) => {
+                self.ingest_front_door_event(&front_door_event, self.last_seen_at);
+            }
         }
         false
     }
 
+    /// Same as `ingest_event`, but prefers the log header timestamp for events
+    /// that don't carry their own
+    pub fn ingest_log_event(&mut self, log_event: LogEvent) -> bool {
+        match log_event.output {
+            ParseOutput::FrontDoor(front_door_event) => {
+                let observed_at = log_event.envelope.timestamp_utc().or(self.last_seen_at);
+                self.ingest_front_door_event(&front_door_event, observed_at);
+                false
+            }
+            output => self.ingest_event(output),
+        }
+    }
+
+    fn see(&mut self, timestamp: Option<DateTime<Utc>>) {
+        if timestamp.is_some() {
+            self.last_seen_at = self.last_seen_at.max(timestamp);
+        }
+    }
+
+    fn ingest_front_door_event(
+        &mut self,
+        front_door_event: &FrontDoorEvent,
+        observed_at: Option<DateTime<Utc>>,
+    ) {
+        let Some(observed_at) = observed_at else {
+            debug!("Dropping untimed front door event: {:?}", front_door_event);
+            return;
+        };
+        match front_door_event {
+            FrontDoorEvent::Response(response) => {
+                if let FrontDoorResponsePayload::RankGetCombinedRankInfo(rank_info) =
+                    &response.payload
+                {
+                    debug!("Rank info: {:?}", rank_info);
+                    self.rank_snapshots
+                        .push(RankSnapshot::new(rank_info, observed_at));
+                }
+            }
+            FrontDoorEvent::Request(request) => match &request.payload {
+                FrontDoorRequestPayload::EventSetDeck(set_deck) => {
+                    self.named_decks.push(NamedDeck::new(
+                        &set_deck.summary,
+                        &set_deck.deck,
+                        observed_at,
+                    ));
+                }
+                FrontDoorRequestPayload::DeckUpsertDeck(upsert_deck) => {
+                    self.named_decks.push(NamedDeck::new(
+                        &upsert_deck.summary,
+                        &upsert_deck.deck,
+                        observed_at,
+                    ));
+                }
+                _ => {}
+            },
+            FrontDoorEvent::Notification(_) => {}
+        }
+    }
+
     pub fn ingest_mgrc_event(&mut self, mgrsc_event: RequestTypeMGRSCEvent) -> bool {
         let state_type = mgrsc_event.mgrsc_event.game_room_info.state_type.clone();
         let match_id = mgrsc_event
@@ -497,18 +701,26 @@ impl MatchReplayBuilder {
         false
     }
 
+    /// Builds the match and starts over, keeping `last_seen_at` to time the front door
+    /// events logged between matches
+    ///
     /// # Errors
     ///
     /// Returns an error if the builder is missing key information
     /// except it doesn't right now, so don't worry about it
-    pub fn build(self) -> Result<MatchReplay> {
-        let match_id = self
+    pub fn build(&mut self) -> Result<MatchReplay> {
+        let next = Self {
+            last_seen_at: self.last_seen_at,
+            ..Self::default()
+        };
+        let builder = std::mem::replace(self, next);
+        let match_id = builder
             .match_id
             .ok_or(MatchReplayBuilderError::MissingMatchId)?;
-        let match_start_message = self
+        let match_start_message = builder
             .match_start_message
             .ok_or(MatchReplayBuilderError::MissingMatchStartMessage)?;
-        let match_end_message = self
+        let match_end_message = builder
             .match_end_message
             .ok_or(MatchReplayBuilderError::MissingMatchEndMessage)?;
 
@@ -516,9 +728,82 @@ impl MatchReplayBuilder {
             match_id,
             match_start_message,
             match_end_message,
-            client_server_messages: self.client_server_messages,
-            business_messages: self.business_messages,
+            client_server_messages: builder.client_server_messages,
+            business_messages: builder.business_messages,
+            rank_snapshots: builder.rank_snapshots,
+            named_decks: builder.named_decks,
         };
         Ok(match_replay)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_envelope::{Direction, LogEnvelope};
+
+    fn rank_response() -> Result<ParseOutput> {
+        let envelope = LogEnvelope {
+            direction: Direction::Response,
+            method: Some("RankGetCombinedRankInfo".to_string()),
+            ..LogEnvelope::default()
+        };
+        let event = r#"{"constructedSeasonOrdinal":90,"constructedClass":"Gold","constructedLevel":2,"constructedStep":3,"constructedMatchesWon":10,"constructedMatchesLost":5,"constructedMatchesDrawn":0}"#;
+        let front_door_event = FrontDoorEvent::from_envelope(&envelope, event)?
+            .ok_or_else(|| anyhow!("not a front door event"))?;
+        Ok(ParseOutput::FrontDoor(Box::new(front_door_event)))
+    }
+
+    #[test]
+    fn test_front_door_events_timed_by_match_messages() -> Result<()> {
+        let mut builder = MatchReplayBuilder::new();
+        builder.ingest_event(rank_response()?);
+        assert!(builder.rank_snapshots.is_empty());
+
+        builder.ingest_event(ParseOutput::MGRSCMessage(RequestTypeMGRSCEvent {
+            timestamp: "638501616000000000".to_string(),
+            ..RequestTypeMGRSCEvent::default()
+        }));
+        builder.ingest_event(rank_response()?);
+        let observed_at: Vec<_> = builder
+            .rank_snapshots
+            .iter()
+            .map(|rank_snapshot| rank_snapshot.observed_at.to_rfc3339())
+            .collect();
+        assert_eq!(observed_at, vec!["2024-05-01T12:00:00+00:00"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rank_after_match_timed_by_match_end() -> Result<()> {
+        let mgrsc = |state_type, timestamp: &str| {
+            let mut mgrsc_event = RequestTypeMGRSCEvent {
+                timestamp: timestamp.to_string(),
+                ..RequestTypeMGRSCEvent::default()
+            };
+            let game_room_info = &mut mgrsc_event.mgrsc_event.game_room_info;
+            game_room_info.game_room_config.match_id = "m1".to_string();
+            game_room_info.state_type = state_type;
+            ParseOutput::MGRSCMessage(mgrsc_event)
+        };
+        let mut builder = MatchReplayBuilder::new();
+        builder.ingest_event(mgrsc(StateType::Playing, "638501616000000000"));
+        assert!(builder.ingest_event(mgrsc(StateType::MatchCompleted, "638501616600000000")));
+        assert_eq!(builder.build()?.match_id, "m1");
+        assert!(builder.is_idle());
+
+        // the lobby asks for the new rank right after the match, logged without a header time
+        let rank_update = LogEvent {
+            envelope: LogEnvelope::default(),
+            output: rank_response()?,
+        };
+        builder.ingest_log_event(rank_update);
+        let observed_at: Vec<_> = builder
+            .rank_snapshots
+            .iter()
+            .map(|rank_snapshot| rank_snapshot.observed_at.to_rfc3339())
+            .collect();
+        assert_eq!(observed_at, vec!["2024-05-01T12:01:00+00:00"]);
+        Ok(())
+    }
+}
@@ -1,9 +1,10 @@
+use crate::draft::DraftReplay;
 use crate::replay::MatchReplay;
 use anyhow::Result;
 use serde::Serialize;
 use std::fs::File;
 use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 pub trait ArenaMatchStorageBackend {
@@ -13,6 +14,18 @@ pub trait ArenaMatchStorageBackend {
     fn write(&mut self, match_replay: &MatchReplay) -> anyhow::Result<()>;
 }
 
+pub trait ArenaDraftStorageBackend {
+    /// # Errors
+    ///
+    /// Will return an error if the draft replay cannot be written to the storage backend
+    fn write_draft(&mut self, draft_replay: &DraftReplay) -> anyhow::Result<()>;
+}
+
+/// Backends that can store both matches and drafts
+pub trait ArenaStorageBackend: ArenaMatchStorageBackend + ArenaDraftStorageBackend {}
+
+impl<T> ArenaStorageBackend for T where T: ArenaMatchStorageBackend + ArenaDraftStorageBackend {}
+
 pub struct DirectoryStorageBackend {
     path: PathBuf,
 }
@@ -47,6 +60,158 @@ impl ArenaMatchStorageBackend for DirectoryStorageBackend {
             write_line(&mut writer, &match_item)?;
         }
         info!("Match replay written to file");
+
+        let turns_path = self
+            .path
+            .join(format!("{}.turns.json", match_replay.match_id));
+        let mut writer = BufWriter::new(File::create(turns_path)?);
+        serde_json::to_writer(&mut writer, &match_replay.turn_snapshots())?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl ArenaDraftStorageBackend for DirectoryStorageBackend {
+    fn write_draft(&mut self, draft_replay: &DraftReplay) -> anyhow::Result<()> {
+        let path = self
+            .path
+            .join(format!("{}.draft.json", draft_replay.draft_id));
+        info!(
+            "Writing draft replay to file: {}",
+            path.clone().to_str().unwrap_or("Path not found")
+        );
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        write_line(&mut writer, draft_replay)?;
+        info!("Draft replay written to file");
+        Ok(())
+    }
+}
+
+/// Reads back the match replays written by `DirectoryStorageBackend`, in file name order.
+/// Drafts and turn snapshots in the same directory are skipped
+pub struct DirectoryReplaySource {
+    paths: std::vec::IntoIter<PathBuf>,
+}
+
+impl DirectoryReplaySource {
+    /// # Errors
+    ///
+    /// Will return an error if the directory cannot be read
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            let is_match_replay = path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+                && path
+                    .file_stem()
+                    .and_then(|file_stem| file_stem.to_str())
+                    .is_some_and(|file_stem| !file_stem.contains('.'));
+            if is_match_replay && path.is_file() {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(Self {
+            paths: paths.into_iter(),
+        })
+    }
+}
+
+impl Iterator for DirectoryReplaySource {
+    type Item = (PathBuf, Result<MatchReplay>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.paths.next()?;
+        let match_replay = MatchReplay::from_jsonl(&path);
+        Some((path, match_replay))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.paths.size_hint()
+    }
+}
+
+impl ExactSizeIterator for DirectoryReplaySource {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::models::deck::NamedDeck;
+    use crate::models::rank::RankSnapshot;
+    use crate::mtga_events::gre::{GREToClientEvent, RequestTypeGREToClientEvent};
+    use crate::mtga_events::mgrsc::{RequestTypeMGRSCEvent, StateType};
+    use crate::replay::MatchReplayEvent;
+
+    fn match_replay() -> Result<MatchReplay> {
+        let mut match_start_message = RequestTypeMGRSCEvent::default();
+        match_start_message
+            .mgrsc_event
+            .game_room_info
+            .game_room_config
+            .match_id = "match-1".to_string();
+        let mut match_end_message = match_start_message.clone();
+        match_end_message.mgrsc_event.game_room_info.state_type = StateType::MatchCompleted;
+        Ok(MatchReplay {
+            match_id: "match-1".to_string(),
+            match_start_message,
+            match_end_message,
+            client_server_messages: vec![MatchReplayEvent::GRE(RequestTypeGREToClientEvent {
+                gre_to_client_event: GREToClientEvent {
+                    gre_to_client_messages: vec![],
+                },
+                timestamp: "638000000000000000".to_string(),
+                ..RequestTypeGREToClientEvent::default()
+            })],
+            business_messages: vec![serde_json::from_str(
+                r#"{"EventId":"Ladder","EventTime":"2024-05-01T12:00:00Z","MatchId":"match-1"}"#,
+            )?],
+            rank_snapshots: vec![RankSnapshot::default()],
+            named_decks: vec![NamedDeck {
+                deck_id: "deck-1".to_string(),
+                name: "Mono Red".to_string(),
+                mainboard: vec![1, 1, 2],
+                sideboard: vec![3],
+                observed_at: DateTime::<Utc>::UNIX_EPOCH,
+            }],
+        })
+    }
+
+    fn lines(match_replay: &MatchReplay) -> Result<Vec<String>> {
+        Ok(match_replay
+            .iter()
+            .map(|event| serde_json::to_string(&event))
+            .collect::<serde_json::Result<_>>()?)
+    }
+
+    #[test]
+    fn test_directory_round_trip() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("ap_core_{}_replays", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let written = match_replay()?;
+        DirectoryStorageBackend::new(dir.clone()).write(&written)?;
+
+        let mut source = DirectoryReplaySource::new(&dir)?;
+        assert_eq!(source.len(), 1);
+        let Some((path, read)) = source.next() else {
+            panic!("no replay found");
+        };
+        assert_eq!(path, dir.join("match-1.json"));
+        let read = read?;
+        assert_eq!(read.match_id, written.match_id);
+        assert_eq!(lines(&read)?, lines(&written)?);
+        assert_eq!(read.named_decks, written.named_decks);
+        std::fs::remove_dir_all(dir)?;
         Ok(())
     }
+
+    #[test]
+    fn test_missing_end_message() {
+        let line = r#"{"matchGameRoomStateChangedEvent":{"gameRoomInfo":{"gameRoomConfig":{"matchId":"m"},"stateType":"MatchGameRoomStateType_Playing"}},"timestamp":"","transactionId":""}"#;
+        assert!(MatchReplay::from_reader(line.as_bytes()).is_err());
+    }
 }
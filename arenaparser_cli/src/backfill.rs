@@ -0,0 +1,169 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use tracing::{error, info};
+
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::processor::{ArenaEventSource, PlayerLogProcessor};
+use ap_core::replay::MatchReplay;
+use ap_core::storage_backends::{ArenaMatchStorageBackend, DirectoryReplaySource};
+
+/// Counts what a backfill wrote, writing nothing on a dry run
+#[derive(Default)]
+struct Backfill {
+    db: Option<MatchInsightDB>,
+    /// matches written, or read on a dry run. A match saved as a replay file is usually
+    /// also in a Player.log copy
+    match_ids: BTreeSet<String>,
+    written: usize,
+    duplicates: usize,
+    failed: usize,
+}
+
+impl Backfill {
+    fn write(&mut self, source: &Path, match_replay: Result<MatchReplay>) {
+        let written = match_replay.and_then(|match_replay| {
+            if self.match_ids.contains(&match_replay.match_id) {
+                info!(
+                    "{}: match {} already read, skipping",
+                    source.display(),
+                    match_replay.match_id
+                );
+                return Ok(false);
+            }
+            info!("{}: match {}", source.display(), match_replay.match_id);
+            if let Some(db) = &mut self.db {
+                db.write(&match_replay)?;
+            }
+            self.match_ids.insert(match_replay.match_id);
+            Ok(true)
+        });
+        match written {
+            Ok(true) => self.written += 1,
+            Ok(false) => self.duplicates += 1,
+            Err(e) => {
+                error!("{}: {e}", source.display());
+                self.failed += 1;
+            }
+        }
+    }
+}
+
+/// Rebuilds matches from saved replay files and Player.log copies in `inputs`
+/// and writes them to `db`, or only reads them when there is no db (a dry run)
+///
+/// # Errors
+///
+/// will return an error if an input directory cannot be read
+pub fn backfill(inputs: &[PathBuf], db: Option<MatchInsightDB>) -> Result<()> {
+    let dry_run = db.is_none();
+    let mut backfill = Backfill {
+        db,
+        ..Backfill::default()
+    };
+
+    for input in inputs {
+        let replays = DirectoryReplaySource::new(input)?;
+        let total = replays.len();
+        for (i, (path, match_replay)) in replays.enumerate() {
+            info!("[{}/{total}] {}", i + 1, path.display());
+            backfill.write(&path, match_replay);
+        }
+
+        let mut logs = Vec::new();
+        for entry in std::fs::read_dir(input)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "log") {
+                logs.push(path);
+            }
+        }
+        logs.sort();
+        for log in logs {
+            info!("Reading matches from {}", log.display());
+            let processor = match PlayerLogProcessor::try_new(log.clone()) {
+                Ok(processor) => processor,
+                Err(e) => {
+                    error!("{}: {e}", log.display());
+                    backfill.failed += 1;
+                    continue;
+                }
+            };
+            for match_replay in processor.match_replays() {
+                backfill.write(&log, match_replay);
+            }
+        }
+    }
+
+    if dry_run {
+        info!(
+            "Dry run: {} matches would be written, {} duplicates skipped, {} could not be read",
+            backfill.written, backfill.duplicates, backfill.failed
+        );
+    } else {
+        info!(
+            "{} matches written, {} duplicates skipped, {} failed",
+            backfill.written, backfill.duplicates, backfill.failed
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use anyhow::anyhow;
+    use ap_core::cards::CardsDatabase;
+    use rusqlite::Connection;
+
+    use super::*;
+
+    fn match_replay(match_id: &str) -> Result<MatchReplay> {
+        Ok(MatchReplay {
+            match_id: match_id.to_string(),
+            ..MatchReplay::default()
+        })
+    }
+
+    #[test]
+    fn test_match_in_replay_file_and_log() {
+        let mut backfill = Backfill::default();
+        backfill.write(Path::new("m1.json"), match_replay("m1"));
+        backfill.write(Path::new("m2.json"), match_replay("m2"));
+        backfill.write(Path::new("Player.log"), match_replay("m1"));
+        backfill.write(Path::new("Player.log"), match_replay("m3"));
+        backfill.write(Path::new("Player-prev.log"), match_replay("m2"));
+
+        assert_eq!(backfill.written, 3);
+        assert_eq!(backfill.duplicates, 2);
+        assert_eq!(backfill.failed, 0);
+    }
+
+    #[test]
+    fn test_match_written_after_failed_copy() -> Result<()> {
+        let mut backfill = Backfill::default();
+        backfill.write(Path::new("m1.json"), Err(anyhow!("broken replay file")));
+        backfill.write(Path::new("Player.log"), match_replay("m1"));
+        assert_eq!(backfill.written, 1);
+        assert_eq!(backfill.duplicates, 0);
+        assert_eq!(backfill.failed, 1);
+
+        // without tables every write fails, so no copy is a duplicate of the first
+        let mut backfill = Backfill {
+            db: Some(MatchInsightDB::new(
+                Connection::open_in_memory()?,
+                CardsDatabase {
+                    db: BTreeMap::new(),
+                },
+            )),
+            ..Backfill::default()
+        };
+        backfill.write(Path::new("m1.json"), match_replay("m1"));
+        backfill.write(Path::new("Player.log"), match_replay("m1"));
+        assert_eq!(backfill.written, 0);
+        assert_eq!(backfill.duplicates, 0);
+        assert_eq!(backfill.failed, 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::Write;
+
+use ap_core::cards::{CardDbEntry, CardsDatabase};
+
+/// Cards whose grp id is `query`, or whose name contains it, ignoring case
+pub fn find_cards<'a>(cards_db: &'a CardsDatabase, query: &str) -> Vec<&'a CardDbEntry> {
+    if let Ok(grp_id) = query.parse::<i32>() {
+        return cards_db.get(&grp_id).into_iter().collect();
+    }
+    let query = query.to_lowercase();
+    let mut cards: Vec<&CardDbEntry> = cards_db
+        .db
+        .values()
+        .filter(|card| card.name.to_lowercase().contains(&query))
+        .collect();
+    cards.sort_by_key(|card| card.id);
+    cards
+}
+
+/// Prints the cards matching `query`, one per line
+pub fn cards(cards_db: &CardsDatabase, query: &str) {
+    let mut text = String::new();
+    for card in find_cards(cards_db, query) {
+        let _ = writeln!(
+            text,
+            "{:>6}  {}  {}  {}  ({})",
+            card.id,
+            card.name,
+            card.mana_cost.as_deref().unwrap_or_default(),
+            card.type_line,
+            card.set
+        );
+    }
+    if text.is_empty() {
+        eprintln!("no cards match {query}");
+    }
+    print!("{text}");
+}
@@ -0,0 +1,241 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+
+use ap_core::cards::{CardsDatabase, LegalityTable};
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::models::deck::quantities;
+use ap_core::models::deck_diff::DeckDiff;
+use ap_core::models::deck_profile::{DeckProfile, MAX_CURVE_BUCKET};
+use ap_core::models::win_rate::WinRateFilter;
+use ap_core::replay::MatchReplay;
+
+/// "4 Lightning Bolt" lines, sorted by card name
+pub fn decklist_lines(cards: &[i32], cards_db: &CardsDatabase) -> Vec<String> {
+    let mut lines: Vec<(String, u16)> = quantities(cards)
+        .into_iter()
+        .map(|(grp_id, quantity)| (cards_db.get_pretty_name_defaulted(&grp_id), quantity))
+        .collect();
+    lines.sort();
+    lines
+        .into_iter()
+        .map(|(name, quantity)| format!("{quantity} {name}"))
+        .collect()
+}
+
+/// Prints every deck played in game 1 of a match with its match record, most played first.
+/// Edits of a deck are counted together and listed as its versions. With `arena`, prints
+/// the latest version of each deck as MTGA's clipboard text instead
+///
+/// # Errors
+///
+/// will return an error if the database cannot be queried
+pub fn decks(
+    db: &mut MatchInsightDB,
+    cards_db: &CardsDatabase,
+    filter: &WinRateFilter,
+    arena: bool,
+) -> Result<()> {
+    let mut text = String::new();
+    for lineage in db.get_deck_lineages(filter)? {
+        if arena {
+            if let Some(latest) = lineage.latest() {
+                let _ = writeln!(text, "About\nName {}\n", lineage.display_name());
+                let _ = writeln!(text, "{}", latest.deck().to_arena_text(cards_db));
+            }
+            continue;
+        }
+        let win_rate = &lineage.win_rate;
+        let _ = writeln!(
+            text,
+            "{}: {} ({:.1}%)",
+            lineage.display_name(),
+            win_rate.record(),
+            win_rate.win_rate * 100.0
+        );
+        if lineage.versions.len() > 1 {
+            let _ = writeln!(text, "  {} versions", lineage.versions.len());
+        }
+        let Some(latest) = lineage.latest() else {
+            continue;
+        };
+        let _ = writeln!(text, "  fingerprint {}", latest.fingerprint);
+        for line in decklist_lines(&latest.mainboard, cards_db) {
+            let _ = writeln!(text, "  {line}");
+        }
+        text.push('\n');
+    }
+    print!("{text}");
+    Ok(())
+}
+
+/// Card counts, the mana curve and colored pips against sources
+pub fn profile_lines(profile: &DeckProfile) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{} cards: {} lands ({} modal), {} creatures, {} noncreature spells",
+        profile.cards,
+        profile.lands,
+        profile.modal_lands,
+        profile.creatures,
+        profile.noncreature_spells
+    )];
+    let curve = profile
+        .mana_curve
+        .iter()
+        .map(|(mana_value, count)| {
+            let plus = if *mana_value == MAX_CURVE_BUCKET {
+                "+"
+            } else {
+                ""
+            };
+            format!("{mana_value}{plus}:{count}")
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    lines.push(format!(
+        "Curve {curve} (average {:.2})",
+        profile.average_mana_value
+    ));
+    if !profile.colors.is_empty() {
+        let colors = profile
+            .colors
+            .iter()
+            .map(|color| {
+                format!(
+                    "{} {} pips/{} sources",
+                    color.color, color.pips, color.sources
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!("Colors {colors}"));
+    }
+    if profile.unknown_cards > 0 {
+        lines.push(format!(
+            "{} cards not in the cards database",
+            profile.unknown_cards
+        ));
+    }
+    lines
+}
+
+/// "+1 Duress, -1 Shock", or "no changes"
+pub fn deck_diff_line(deck_diff: &DeckDiff, cards_db: &CardsDatabase) -> String {
+    if deck_diff.is_empty() {
+        return "no changes".to_string();
+    }
+    let cards = |sign: char, cards: &std::collections::BTreeMap<i32, u32>| {
+        cards
+            .iter()
+            .map(move |(grp_id, quantity)| {
+                format!(
+                    "{sign}{quantity} {}",
+                    cards_db.get_pretty_name_defaulted(grp_id)
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    [
+        cards('+', &deck_diff.cards_in),
+        cards('-', &deck_diff.cards_out),
+    ]
+    .concat()
+    .join(", ")
+}
+
+/// Prints how games 2 and 3 were sideboarded with the deck `fingerprint` and the game record
+/// of each plan, most common plan first for each opponent color identity
+///
+/// # Errors
+///
+/// will return an error if the database cannot be queried
+pub fn sideboard(
+    db: &mut MatchInsightDB,
+    cards_db: &CardsDatabase,
+    fingerprint: &str,
+    filter: &WinRateFilter,
+) -> Result<()> {
+    let mut text = String::new();
+    let mut opponent_identity = None;
+    for plan in db.get_sideboard_plans(fingerprint, filter)? {
+        if opponent_identity.as_ref() != Some(&plan.opponent_identity) {
+            let _ = writeln!(text, "Against {}", plan.opponent_identity);
+            opponent_identity = Some(plan.opponent_identity.clone());
+        }
+        let _ = writeln!(
+            text,
+            "  {}-{}  {}",
+            plan.wins,
+            plan.losses,
+            deck_diff_line(&plan.diff, cards_db)
+        );
+    }
+    print!("{text}");
+    Ok(())
+}
+
+/// Prints what is wrong with each deck of a match, if anything
+///
+/// # Errors
+///
+/// will return an error if the match has no decklist or game info
+pub fn validate(
+    match_replay: &MatchReplay,
+    cards_db: &CardsDatabase,
+    legalities: Option<&LegalityTable>,
+) -> Result<()> {
+    let mut text = String::new();
+    for (game_number, violations) in match_replay.validate_decks(cards_db, legalities)? {
+        if violations.is_empty() {
+            let _ = writeln!(text, "Game {game_number}: ok");
+            continue;
+        }
+        let _ = writeln!(text, "Game {game_number}:");
+        for violation in violations {
+            let _ = writeln!(text, "  {violation}");
+        }
+    }
+    print!("{text}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ap_core::models::deck::Deck;
+
+    use super::*;
+
+    #[test]
+    fn test_deck_diff_line() {
+        let cards_db = CardsDatabase {
+            db: BTreeMap::new(),
+        };
+        let deck_diff = DeckDiff {
+            cards_in: [(4, 2)].into(),
+            cards_out: [(3, 1), (5, 1)].into(),
+        };
+        assert_eq!(deck_diff_line(&deck_diff, &cards_db), "+2 4, -1 3, -1 5");
+        assert_eq!(
+            deck_diff_line(&DeckDiff::default(), &cards_db),
+            "no changes"
+        );
+    }
+
+    #[test]
+    fn test_profile_lines() {
+        let deck = Deck::new(String::new(), 1, vec![1, 2], vec![]);
+        let cards_db = CardsDatabase {
+            db: BTreeMap::new(),
+        };
+        assert_eq!(
+            profile_lines(&DeckProfile::new(&deck, &cards_db)),
+            vec![
+                "2 cards: 0 lands (0 modal), 0 creatures, 0 noncreature spells",
+                "Curve 0:0 1:0 2:0 3:0 4:0 5:0 6:0 7+:0 (average 0.00)",
+                "2 cards not in the cards database",
+            ]
+        );
+    }
+}
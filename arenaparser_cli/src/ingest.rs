@@ -0,0 +1,195 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing::{debug, error};
+
+use ap_core::checkpoint::{CheckpointStore, FileCheckpointStore, LogPosition};
+use ap_core::draft::{DraftLobby, DraftReplayBuilder};
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::processor::{
+    ArenaEventSource, FollowingEventSource, LogEvent, ParseError, PlayerLogProcessor,
+};
+use ap_core::replay::MatchReplayBuilder;
+use ap_core::storage_backends::{ArenaStorageBackend, DirectoryStorageBackend};
+
+#[derive(Debug, clap::Args)]
+pub struct IngestArgs {
+    #[arg(short, long, help = "Location of Player.log file")]
+    player_log: PathBuf,
+    #[arg(short, long, help = "directory to write replay output files")]
+    output_dir: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "file to remember how far Player.log has been processed, so the next run picks up from there"
+    )]
+    checkpoint: Option<PathBuf>,
+    #[arg(
+        long, action = clap::ArgAction::SetTrue, help = "read Player-prev.log before Player.log, so matches interrupted by an MTGA restart are not lost"
+    )]
+    previous_log: bool,
+}
+
+fn report_parse_error(parse_error: ParseError) {
+    match &parse_error {
+        // most of the log is json we have no use for
+        ParseError::UnknownShape(_) | ParseError::EndOfInput => {}
+        ParseError::Deserialize(event, _) => {
+            error!("{parse_error}");
+            debug!("Event: {}", event.json_str);
+        }
+        ParseError::Io(_) => error!("{parse_error}"),
+    }
+}
+
+/// Feeds log events to the match and draft builders, writes whatever they finish
+/// to the storage backends, and keeps the checkpoint up to date
+struct ReplayWriter {
+    match_replay_builder: MatchReplayBuilder,
+    draft_replay_builder: DraftReplayBuilder,
+    storage_backends: Vec<Box<dyn ArenaStorageBackend>>,
+    checkpoint_store: Option<FileCheckpointStore>,
+    saved_position: LogPosition,
+    safe_position: LogPosition,
+    /// the draft lobby state as of `safe_position`
+    safe_lobby: DraftLobby,
+}
+
+impl ReplayWriter {
+    fn new(
+        storage_backends: Vec<Box<dyn ArenaStorageBackend>>,
+        checkpoint_store: Option<FileCheckpointStore>,
+        position: &LogPosition,
+        draft_lobby: DraftLobby,
+    ) -> Self {
+        Self {
+            match_replay_builder: MatchReplayBuilder::new(),
+            draft_replay_builder: DraftReplayBuilder::with_lobby(draft_lobby.clone()),
+            storage_backends,
+            checkpoint_store,
+            saved_position: position.clone(),
+            safe_position: position.clone(),
+            safe_lobby: draft_lobby,
+        }
+    }
+
+    fn run<S: ArenaEventSource>(
+        &mut self,
+        event_source: &mut S,
+        processor: impl Fn(&S) -> &PlayerLogProcessor,
+    ) {
+        while let Some(log_event) = event_source.get_next_valid_log_event(&mut report_parse_error) {
+            self.ingest(log_event, processor(event_source));
+            if processor(event_source).is_caught_up() {
+                self.save_checkpoint(processor(event_source));
+            }
+        }
+    }
+
+    fn ingest(&mut self, log_event: LogEvent, processor: &PlayerLogProcessor) {
+        if self.draft_replay_builder.ingest_log_event(&log_event) {
+            match self.draft_replay_builder.build() {
+                Ok(draft_replay) => {
+                    for backend in &mut self.storage_backends {
+                        if let Err(e) = backend.write_draft(&draft_replay) {
+                            error!("Error writing draft to backend: {e}");
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("Error building draft replay: {err}");
+                }
+            }
+        }
+        if self.match_replay_builder.ingest_log_event(log_event) {
+            match self.match_replay_builder.build() {
+                Ok(match_replay) => {
+                    for backend in &mut self.storage_backends {
+                        if let Err(e) = backend.write(&match_replay) {
+                            error!("Error writing replay to backend: {e}");
+                        }
+                    }
+                }
+                Err(err) => {
+                    error!("Error building match replay: {err}");
+                }
+            }
+        }
+        // only checkpoint between matches and drafts, so none are left half read
+        if self.match_replay_builder.is_idle() && self.draft_replay_builder.is_idle() {
+            self.safe_position.clone_from(processor.position());
+            self.safe_lobby
+                .clone_from(self.draft_replay_builder.lobby());
+        }
+    }
+
+    fn save_checkpoint(&mut self, processor: &PlayerLogProcessor) {
+        let Some(checkpoint_store) = &mut self.checkpoint_store else {
+            return;
+        };
+        if self.safe_position == self.saved_position {
+            return;
+        }
+        // a match or draft that began before the processor switched logs has left
+        // no safe position in the log now being read yet
+        if self.safe_position.generation != processor.position().generation {
+            return;
+        }
+        let saved = processor
+            .checkpoint(self.safe_position.clone())
+            .and_then(|mut checkpoint| {
+                checkpoint.draft_lobby.clone_from(&self.safe_lobby);
+                checkpoint_store.save(&checkpoint)
+            });
+        match saved {
+            Ok(()) => self.saved_position.clone_from(&self.safe_position),
+            Err(e) => error!("Error saving checkpoint: {e}"),
+        }
+    }
+}
+
+/// Reads Player.log and writes every match and draft in it to the output directory and database.
+/// With `follow`, keeps waiting for new events until interrupted
+///
+/// # Errors
+///
+/// will return an error if the log or checkpoint cannot be read, or the output directory created
+pub fn ingest(args: IngestArgs, db: Option<MatchInsightDB>, follow: bool) -> Result<()> {
+    let mut processor = if args.previous_log {
+        PlayerLogProcessor::try_new_with_previous_log(args.player_log)?
+    } else {
+        PlayerLogProcessor::try_new(args.player_log)?
+    };
+    let mut checkpoint_store = args.checkpoint.map(FileCheckpointStore::new);
+    let mut draft_lobby = DraftLobby::default();
+    if let Some(checkpoint_store) = &mut checkpoint_store {
+        if let Some(checkpoint) = checkpoint_store.load()? {
+            processor.resume_from(&checkpoint)?;
+            draft_lobby = checkpoint.draft_lobby;
+        }
+    }
+
+    let mut storage_backends: Vec<Box<dyn ArenaStorageBackend>> = Vec::new();
+    if let Some(output_dir) = args.output_dir {
+        std::fs::create_dir_all(&output_dir)?;
+        storage_backends.push(Box::new(DirectoryStorageBackend::new(output_dir)));
+    }
+    if let Some(db) = db {
+        storage_backends.push(Box::new(db));
+    }
+
+    let mut replay_writer = ReplayWriter::new(
+        storage_backends,
+        checkpoint_store,
+        processor.position(),
+        draft_lobby,
+    );
+    if follow {
+        let mut event_source = FollowingEventSource::new(processor);
+        let stop_handle = event_source.stop_handle();
+        ctrlc::set_handler(move || stop_handle.stop())?;
+        replay_writer.run(&mut event_source, FollowingEventSource::processor);
+    } else {
+        replay_writer.run(&mut processor, |processor| processor);
+    }
+    Ok(())
+}
@@ -1,103 +1,225 @@
-use std::path::PathBuf;
-use std::time::Duration;
+mod backfill;
+mod cards;
+mod decks;
+mod ingest;
+mod matches;
+mod stats;
 
-use anyhow::Result;
-use clap::Parser;
-use crossbeam::channel::{select, unbounded, Receiver};
-use tracing::error;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-use ap_core::match_insights::MatchInsightDB;
-use ap_core::processor::{ArenaEventSource, PlayerLogProcessor};
-use ap_core::replay::MatchReplayBuilder;
-use ap_core::storage_backends::{ArenaMatchStorageBackend, DirectoryStorageBackend};
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
 
-const PLAYER_LOG_POLLING_INTERVAL: u64 = 1;
+use ap_core::cards::{CardsDatabase, LegalityTable};
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::replay::MatchReplay;
 
 #[derive(Debug, Parser)]
 #[command(about = "Tries to scrape useful data from mtga detailed logs")]
 struct Args {
-    #[arg(short, long, help = "Location of Player.log file")]
-    player_log: PathBuf,
-    #[arg(short, long, help = "directory to write replay output files")]
-    output_dir: Option<PathBuf>,
-    #[arg(short, long, help = "database to write match data to")]
+    #[command(subcommand)]
+    command: Command,
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "database of match data to write to or read from"
+    )]
     db: Option<PathBuf>,
-    #[arg(short, long, help = "database of cards to reference")]
-    cards_db: Option<PathBuf>,
-    #[arg(long, action = clap::ArgAction::SetTrue, help = "enable debug logging")]
-    debug: bool,
     #[arg(
-        short, long, action = clap::ArgAction::SetTrue, help = "wait for new events on Player.log, useful if you are actively playing MTGA"
+        short,
+        long,
+        global = true,
+        default_value = "data/merged.json",
+        help = "database of cards to reference"
     )]
-    follow: bool,
+    cards_db: PathBuf,
+    #[arg(long, global = true, action = clap::ArgAction::SetTrue, help = "enable debug logging")]
+    debug: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Read Player.log once and write the matches and drafts in it
+    Ingest(ingest::IngestArgs),
+    /// Keep reading Player.log as MTGA writes to it, useful if you are actively playing MTGA
+    Watch(ingest::IngestArgs),
+    /// Rebuild matches from saved replay files and Player.log copies and write them to the database
+    Backfill {
+        #[arg(
+            required = true,
+            help = "directories holding <match_id>.json replay files and/or *.log copies of Player.log"
+        )]
+        inputs: Vec<PathBuf>,
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "only report the matches that would be written")]
+        dry_run: bool,
+    },
+    /// Summarize the matches in the database: records by format, deck, play/draw and mulligans
+    Stats {
+        #[arg(
+            long,
+            value_enum,
+            default_value = "table",
+            help = "how to print the stats"
+        )]
+        format: stats::OutputFormat,
+        #[command(flatten)]
+        filter: stats::FilterArgs,
+    },
+    /// Write every match in the database with its result
+    Export {
+        #[arg(
+            long,
+            value_enum,
+            default_value = "json",
+            help = "how to write the matches"
+        )]
+        format: matches::ExportFormat,
+        #[arg(short, long, help = "file to write to instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Print everything stored about a match
+    Show { match_id: String },
+    /// List the decks played with their records
+    Decks {
+        #[command(flatten)]
+        filter: stats::FilterArgs,
+        #[arg(long, action = clap::ArgAction::SetTrue, help = "print the latest version of each deck in the format MTGA imports")]
+        arena: bool,
+    },
+    /// Show how a deck was sideboarded, per opponent color identity
+    Sideboard {
+        #[arg(help = "fingerprint of the deck played in game 1, as listed by `decks`")]
+        fingerprint: String,
+        #[command(flatten)]
+        filter: stats::FilterArgs,
+    },
+    /// Look up cards by grp id or name
+    Cards { query: String },
+    /// Check the decks of a match written to the output directory against its deck size
+    /// and copy limits, and format legality with --legalities
+    Validate {
+        #[arg(help = "match replay file, e.g. <match_id>.json")]
+        match_file: PathBuf,
+        #[arg(
+            long,
+            help = "card legalities per format, keyed by grp id like the cards database"
+        )]
+        legalities: Option<PathBuf>,
+    },
+    /// Print a play-by-play of a match written to the output directory
+    Narrate {
+        #[arg(help = "match replay file, e.g. <match_id>.json")]
+        match_file: PathBuf,
+    },
 }
 
-fn ctrl_c_channel() -> Result<Receiver<()>> {
-    let (ctrl_c_tx, ctrl_c_rx) = unbounded();
-    ctrlc::set_handler(move || {
-        ctrl_c_tx.send(()).unwrap_or(());
-    })?;
-    Ok(ctrl_c_rx)
+/// Opens the database, creating it if needed
+fn open_db(db_path: &Path, cards_db: CardsDatabase) -> Result<MatchInsightDB> {
+    let mut db = MatchInsightDB::new(rusqlite::Connection::open(db_path)?, cards_db);
+    db.init()?;
+    Ok(db)
+}
+
+/// Opens the database for commands that only read from it
+fn existing_db(db_path: Option<&Path>, cards_db: CardsDatabase) -> Result<MatchInsightDB> {
+    let db_path = db_path.ok_or_else(|| anyhow!("--db is required"))?;
+    if !db_path.exists() {
+        return Err(anyhow!("database {} does not exist", db_path.display()));
+    }
+    open_db(db_path, cards_db)
+}
+
+/// Writing to a database is optional when ingesting, replay files may be all that is wanted
+fn ingest_db(db_path: Option<&Path>, cards_db_path: &Path) -> Result<Option<MatchInsightDB>> {
+    db_path
+        .map(|db_path| open_db(db_path, CardsDatabase::new(cards_db_path)?))
+        .transpose()
+}
+
+/// For queries that never need card data
+fn no_cards() -> CardsDatabase {
+    CardsDatabase {
+        db: BTreeMap::new(),
+    }
 }
 
 fn main() -> Result<()> {
-    let args = Args::try_parse()?;
+    let args = Args::parse();
+    // logs go to stderr so they never end up in printed stats or narrations
     tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
         .with_max_level(if args.debug {
             tracing::Level::DEBUG
         } else {
             tracing::Level::INFO
         })
         .init();
+    let db_path = args.db.as_deref();
 
-    let mut processor = PlayerLogProcessor::try_new(args.player_log)?;
-    let mut match_replay_builder = MatchReplayBuilder::new();
-    let mut storage_backends: Vec<Box<dyn ArenaMatchStorageBackend>> = Vec::new();
-    let cards_db =
-        ap_core::cards::CardsDatabase::new(args.cards_db.unwrap_or("data/merged.json".into()))?;
-
-    let ctrl_c_rx = ctrl_c_channel()?;
-    if let Some(output_dir) = args.output_dir {
-        std::fs::create_dir_all(&output_dir)?;
-        storage_backends.push(Box::new(DirectoryStorageBackend::new(output_dir)));
-    }
-
-    if let Some(db_path) = args.db {
-        let conn = rusqlite::Connection::open(db_path)?;
-        let mut db = MatchInsightDB::new(conn, cards_db);
-        db.init()?;
-        storage_backends.push(Box::new(db));
-    }
-
-    loop {
-        select! {
-            recv(ctrl_c_rx) -> _ => {
-                break;
-            }
-            default(Duration::from_secs(PLAYER_LOG_POLLING_INTERVAL)) => {
-                while let Ok(parse_output) = processor.get_next_event() {
-                    if match_replay_builder.ingest_event(parse_output) {
-                        match match_replay_builder.build() {
-                            Ok(match_replay) => {
-                                for backend in &mut storage_backends {
-                                    if let Err(e) =backend.write(&match_replay){
-                                        error!("Error writing replay to backend: {e}");
-                                    }
-                                }
-                            },
-                            Err(err) => {
-                                error!("Error building match replay: {err}");
-                            }
-                        }
-                        match_replay_builder = MatchReplayBuilder::new();
-                    }
-                }
-                if !args.follow {
-                    break;
-                }
-            }
+    match args.command {
+        Command::Ingest(ingest_args) => {
+            let db = ingest_db(db_path, &args.cards_db)?;
+            ingest::ingest(ingest_args, db, false)
+        }
+        Command::Watch(ingest_args) => {
+            let db = ingest_db(db_path, &args.cards_db)?;
+            ingest::ingest(ingest_args, db, true)
+        }
+        Command::Backfill { inputs, dry_run } => {
+            let db = if dry_run {
+                None
+            } else {
+                let db_path = db_path.ok_or_else(|| anyhow!("--db is required"))?;
+                Some(open_db(db_path, CardsDatabase::new(&args.cards_db)?)?)
+            };
+            backfill::backfill(&inputs, db)
+        }
+        Command::Stats { format, filter } => {
+            let mut db = existing_db(db_path, no_cards())?;
+            stats::stats(&mut db, &filter.into(), format)
+        }
+        Command::Export { format, output } => {
+            let mut db = existing_db(db_path, no_cards())?;
+            matches::export(&mut db, format, output)
+        }
+        Command::Show { match_id } => {
+            let cards_db = CardsDatabase::new(&args.cards_db)?;
+            let mut db = existing_db(db_path, no_cards())?;
+            matches::show(&mut db, &cards_db, &match_id)
+        }
+        Command::Decks { filter, arena } => {
+            let cards_db = CardsDatabase::new(&args.cards_db)?;
+            let mut db = existing_db(db_path, no_cards())?;
+            decks::decks(&mut db, &cards_db, &filter.into(), arena)
+        }
+        Command::Sideboard {
+            fingerprint,
+            filter,
+        } => {
+            let cards_db = CardsDatabase::new(&args.cards_db)?;
+            let mut db = existing_db(db_path, no_cards())?;
+            decks::sideboard(&mut db, &cards_db, &fingerprint, &filter.into())
+        }
+        Command::Cards { query } => {
+            cards::cards(&CardsDatabase::new(&args.cards_db)?, &query);
+            Ok(())
+        }
+        Command::Validate {
+            match_file,
+            legalities,
+        } => {
+            let cards_db = CardsDatabase::new(&args.cards_db)?;
+            let legalities = legalities.map(LegalityTable::new).transpose()?;
+            let match_replay = MatchReplay::from_jsonl(match_file)?;
+            decks::validate(&match_replay, &cards_db, legalities.as_ref())
+        }
+        Command::Narrate { match_file } => {
+            let cards_db = CardsDatabase::new(&args.cards_db)?;
+            let match_replay = MatchReplay::from_jsonl(match_file)?;
+            print!("{}", match_replay.narrate(&cards_db)?);
+            Ok(())
         }
     }
-
-    Ok(())
 }
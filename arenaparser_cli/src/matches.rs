@@ -0,0 +1,271 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use ap_core::cards::CardsDatabase;
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::models::deck_profile::DeckProfile;
+use ap_core::models::match_result::{MatchResult, NO_WINNING_TEAM};
+use ap_core::models::mtga_match::MTGAMatch;
+use ap_core::models::rank::Rank;
+
+use crate::decks::{deck_diff_line, decklist_lines, profile_lines};
+use crate::stats::csv_field;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// one JSON object per line
+    Json,
+    Csv,
+}
+
+/// One exported match. Team ids are the same as seat ids in two player matches
+#[derive(Debug, Serialize)]
+struct MatchRecord {
+    id: String,
+    created_at: String,
+    format: Option<String>,
+    controller_player_name: String,
+    opponent_player_name: String,
+    opponent_identity: Option<String>,
+    /// None for matches without a winner, unfinished or drawn
+    won: Option<bool>,
+    game_wins: usize,
+    game_losses: usize,
+    game_draws: usize,
+}
+
+impl MatchRecord {
+    fn new(db: &mut MatchInsightDB, mtga_match: MTGAMatch) -> Result<Self> {
+        let game_results = db.get_match_results(&mtga_match.id)?;
+        let (game_wins, game_losses, game_draws) =
+            game_record(&game_results, mtga_match.controller_seat_id);
+        let won = db
+            .get_match_winner(&mtga_match.id)?
+            .filter(|winning_team_id| *winning_team_id != NO_WINNING_TEAM)
+            .map(|winning_team_id| winning_team_id == mtga_match.controller_seat_id);
+        Ok(Self {
+            id: mtga_match.id,
+            created_at: mtga_match.created_at.to_rfc3339(),
+            format: mtga_match.format,
+            controller_player_name: mtga_match.controller_player_name,
+            opponent_player_name: mtga_match.opponent_player_name,
+            opponent_identity: mtga_match.opponent_identity,
+            won,
+            game_wins,
+            game_losses,
+            game_draws,
+        })
+    }
+
+    fn csv_row(&self) -> String {
+        let won = self.won.map(|won| won.to_string()).unwrap_or_default();
+        [
+            csv_field(&self.id),
+            self.created_at.clone(),
+            csv_field(self.format.as_deref().unwrap_or_default()),
+            csv_field(&self.controller_player_name),
+            csv_field(&self.opponent_player_name),
+            csv_field(self.opponent_identity.as_deref().unwrap_or_default()),
+            won,
+            self.game_wins.to_string(),
+            self.game_losses.to_string(),
+            self.game_draws.to_string(),
+        ]
+        .join(",")
+    }
+}
+
+/// Writes every match in the database, oldest first, to `output` or stdout
+///
+/// # Errors
+///
+/// will return an error if the database cannot be queried or the output cannot be written
+pub fn export(
+    db: &mut MatchInsightDB,
+    export_format: ExportFormat,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    if let ExportFormat::Csv = export_format {
+        writeln!(
+            out,
+            "id,created_at,format,controller_player_name,opponent_player_name,opponent_identity,won,game_wins,game_losses,game_draws"
+        )?;
+    }
+    for mtga_match in db.get_matches()? {
+        let record = MatchRecord::new(db, mtga_match)?;
+        match export_format {
+            ExportFormat::Json => writeln!(out, "{}", serde_json::to_string(&record)?)?,
+            ExportFormat::Csv => writeln!(out, "{}", record.csv_row())?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn outcome(winning_team_id: i32, seat_id: i32) -> &'static str {
+    if winning_team_id == NO_WINNING_TEAM {
+        "drew"
+    } else if winning_team_id == seat_id {
+        "won"
+    } else {
+        "lost"
+    }
+}
+
+/// Games won, lost and drawn by `seat_id`. Team ids are the same as seat ids in two player matches
+fn game_record(game_results: &[MatchResult], seat_id: i32) -> (usize, usize, usize) {
+    let count = |outcome_of: &str| {
+        game_results
+            .iter()
+            .filter(|result| outcome(result.winning_team_id, seat_id) == outcome_of)
+            .count()
+    };
+    (count("won"), count("lost"), count("drew"))
+}
+
+fn rank_change(before: Option<&Rank>, after: Option<&Rank>) -> String {
+    let rank = |rank: Option<&Rank>| rank.map_or("?".to_string(), ToString::to_string);
+    format!("{} -> {}", rank(before), rank(after))
+}
+
+/// Prints everything stored about a match
+///
+/// # Errors
+///
+/// will return an error if the match is not in the database or the database cannot be queried
+pub fn show(db: &mut MatchInsightDB, cards_db: &CardsDatabase, match_id: &str) -> Result<()> {
+    let mtga_match = db
+        .get_match(match_id)?
+        .ok_or_else(|| anyhow!("match {match_id} not found"))?;
+    let seat_id = mtga_match.controller_seat_id;
+    let mut text = String::new();
+
+    let _ = writeln!(text, "Match {}", mtga_match.id);
+    let _ = writeln!(
+        text,
+        "  {} vs {}",
+        mtga_match.controller_player_name, mtga_match.opponent_player_name
+    );
+    let _ = writeln!(
+        text,
+        "  Started {}, {}",
+        mtga_match.created_at.format("%Y-%m-%d %H:%M UTC"),
+        mtga_match.format.as_deref().unwrap_or("unknown format")
+    );
+    if let Some(opponent_identity) = &mtga_match.opponent_identity {
+        let _ = writeln!(text, "  Opponent colors: {opponent_identity}");
+    }
+    let game_results = db.get_match_results(match_id)?;
+    if let Some(winning_team_id) = db.get_match_winner(match_id)? {
+        let (game_wins, game_losses, game_draws) = game_record(&game_results, seat_id);
+        let draws = if game_draws > 0 {
+            format!("-{game_draws}")
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            text,
+            "  Result: {} {game_wins}-{game_losses}{draws}",
+            outcome(winning_team_id, seat_id),
+        );
+    }
+
+    let _ = writeln!(text, "\nGames");
+    for result in &game_results {
+        let _ = writeln!(
+            text,
+            "  Game {}: {}",
+            result.game_number,
+            outcome(result.winning_team_id, seat_id)
+        );
+    }
+
+    for deck in db.get_decklists(match_id)? {
+        let _ = writeln!(text, "\n{} (game {})", deck.name, deck.game_number);
+        for line in decklist_lines(&deck.mainboard, cards_db) {
+            let _ = writeln!(text, "  {line}");
+        }
+        if !deck.sideboard.is_empty() {
+            let _ = writeln!(text, "Sideboard");
+            for line in decklist_lines(&deck.sideboard, cards_db) {
+                let _ = writeln!(text, "  {line}");
+            }
+        }
+        if deck.game_number == 1 {
+            let _ = writeln!(text, "Profile");
+            for line in profile_lines(&DeckProfile::new(&deck, cards_db)) {
+                let _ = writeln!(text, "  {line}");
+            }
+        }
+    }
+
+    let deck_diffs = db.get_deck_diffs(match_id)?;
+    if !deck_diffs.is_empty() {
+        let _ = writeln!(text, "\nSideboarding");
+    }
+    for (game_number, deck_diff) in deck_diffs {
+        let _ = writeln!(
+            text,
+            "  Game {game_number}: {}",
+            deck_diff_line(&deck_diff, cards_db)
+        );
+    }
+
+    let mulligans = db.get_mulligans(match_id)?;
+    if !mulligans.is_empty() {
+        let _ = writeln!(text, "\nMulligans");
+    }
+    for mulligan in mulligans {
+        let hand = mulligan
+            .hand
+            .split(',')
+            .filter(|grp_id| !grp_id.is_empty())
+            .map(|grp_id| cards_db.get_pretty_name_defaulted(grp_id))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(
+            text,
+            "  Game {} ({}), {} to keep: {} - {}",
+            mulligan.game_number,
+            mulligan.play_draw,
+            mulligan.number_to_keep,
+            mulligan.decision,
+            hand
+        );
+    }
+
+    let ranks = db.get_match_ranks(match_id)?;
+    if ranks.before.is_some() || ranks.after.is_some() {
+        let before = ranks.before.as_ref();
+        let after = ranks.after.as_ref();
+        let _ = writeln!(text, "\nRank");
+        let _ = writeln!(
+            text,
+            "  Constructed: {}",
+            rank_change(
+                before.map(|snapshot| &snapshot.constructed),
+                after.map(|snapshot| &snapshot.constructed)
+            )
+        );
+        let _ = writeln!(
+            text,
+            "  Limited: {}",
+            rank_change(
+                before.map(|snapshot| &snapshot.limited),
+                after.map(|snapshot| &snapshot.limited)
+            )
+        );
+    }
+
+    print!("{text}");
+    Ok(())
+}
@@ -0,0 +1,269 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use ap_core::match_insights::MatchInsightDB;
+use ap_core::models::win_rate::{Streaks, WinRate, WinRateFilter};
+
+/// Deck names can be long, so labels get cut short in tables
+const MAX_LABEL_WIDTH: usize = 40;
+
+/// Which matches to summarize
+#[derive(Debug, clap::Args)]
+pub struct FilterArgs {
+    #[arg(long, value_parser = parse_time, help = "only matches started on or after this date, e.g. 2024-05-01")]
+    since: Option<DateTime<Utc>>,
+    #[arg(long, value_parser = parse_time, help = "only matches started before this date")]
+    until: Option<DateTime<Utc>>,
+    #[arg(
+        long,
+        help = "only matches of this event, e.g. Traditional_Explorer_Ranked"
+    )]
+    format_filter: Option<String>,
+}
+
+impl From<FilterArgs> for WinRateFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            since: args.since,
+            until: args.until,
+            format: args.format_filter,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Everything `arenaparser stats` prints
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub overall: WinRate,
+    pub by_format: Vec<WinRate>,
+    pub by_deck: Vec<WinRate>,
+    /// game win rates
+    pub play_draw: Vec<WinRate>,
+    /// game win rates, by how many mulligans were taken
+    pub mulligans: Vec<WinRate>,
+    pub streaks: Streaks,
+}
+
+impl Stats {
+    /// # Errors
+    ///
+    /// will return an error if the database cannot be queried
+    pub fn query(db: &mut MatchInsightDB, filter: &WinRateFilter) -> Result<Self> {
+        Ok(Self {
+            overall: db.get_win_rate(filter)?,
+            by_format: db.get_win_rates_by_format(filter)?,
+            by_deck: db.get_win_rates_by_deck(filter)?,
+            play_draw: db.get_win_rates_by_play_draw(filter)?,
+            mulligans: db.get_win_rates_by_mulligans(filter)?,
+            streaks: db.get_streaks(filter)?,
+        })
+    }
+
+    fn sections(&self) -> [(&'static str, &[WinRate]); 5] {
+        [
+            ("overall", std::slice::from_ref(&self.overall)),
+            ("format", &self.by_format),
+            ("deck", &self.by_deck),
+            ("play_draw", &self.play_draw),
+            ("mulligans", &self.mulligans),
+        ]
+    }
+
+    /// # Errors
+    ///
+    /// will return an error if the stats cannot be serialized
+    pub fn render(&self, output_format: OutputFormat) -> Result<String> {
+        match output_format {
+            OutputFormat::Table => Ok(self.table()),
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            OutputFormat::Csv => Ok(self.csv()),
+        }
+    }
+
+    fn table(&self) -> String {
+        let mut text = String::new();
+        let titles = [
+            "Overall (matches)",
+            "Format (matches)",
+            "Deck (matches)",
+            "Play/Draw (games)",
+            "Mulligans (games)",
+        ];
+        for ((_, win_rates), title) in self.sections().into_iter().zip(titles) {
+            let _ = writeln!(text, "{title}");
+            let games: u32 = win_rates.iter().map(WinRate::total).sum();
+            for win_rate in win_rates {
+                let _ = writeln!(
+                    text,
+                    "  {:<MAX_LABEL_WIDTH$}  {:>11}  {:>5.1}%  ({:.1}-{:.1}%)  {:>5.1}% of total",
+                    truncate(&win_rate.label),
+                    win_rate.record(),
+                    win_rate.win_rate * 100.0,
+                    win_rate.lower_bound * 100.0,
+                    win_rate.upper_bound * 100.0,
+                    share(win_rate.total(), games),
+                );
+            }
+            text.push('\n');
+        }
+        let current = match self.streaks.current {
+            Some(streak) if streak.won => format!("{} wins", streak.length),
+            Some(streak) => format!("{} losses", streak.length),
+            None => "none".to_string(),
+        };
+        let _ = writeln!(text, "Streaks");
+        let _ = writeln!(text, "  current       {current}");
+        let _ = writeln!(text, "  longest win   {}", self.streaks.longest_win);
+        let _ = writeln!(text, "  longest loss  {}", self.streaks.longest_loss);
+        text
+    }
+
+    /// One row per win rate, with streaks as rows of only wins or only losses
+    fn csv(&self) -> String {
+        let mut text =
+            "section,label,wins,losses,draws,win_rate,lower_bound,upper_bound\n".to_string();
+        for (section, win_rates) in self.sections() {
+            for win_rate in win_rates {
+                let _ = writeln!(
+                    text,
+                    "{section},{},{},{},{},{:.4},{:.4},{:.4}",
+                    csv_field(&win_rate.label),
+                    win_rate.wins,
+                    win_rate.losses,
+                    win_rate.draws,
+                    win_rate.win_rate,
+                    win_rate.lower_bound,
+                    win_rate.upper_bound,
+                );
+            }
+        }
+        let (current_wins, current_losses) = match self.streaks.current {
+            Some(streak) if streak.won => (streak.length, 0),
+            Some(streak) => (0, streak.length),
+            None => (0, 0),
+        };
+        let _ = writeln!(text, "streaks,current,{current_wins},{current_losses},,,,");
+        let _ = writeln!(
+            text,
+            "streaks,longest_win,{},0,,,,",
+            self.streaks.longest_win
+        );
+        let _ = writeln!(
+            text,
+            "streaks,longest_loss,0,{},,,,",
+            self.streaks.longest_loss
+        );
+        text
+    }
+}
+
+fn truncate(label: &str) -> String {
+    if label.chars().count() <= MAX_LABEL_WIDTH {
+        return label.to_string();
+    }
+    let mut truncated: String = label.chars().take(MAX_LABEL_WIDTH - 3).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+fn share(count: u32, total: u32) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    f64::from(count) / f64::from(total) * 100.0
+}
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Accepts a date (midnight UTC) or an RFC 3339 timestamp
+///
+/// # Errors
+///
+/// will return an error if the argument is neither
+fn parse_time(arg: &str) -> Result<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(arg, "%Y-%m-%d") {
+        return Ok(date.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+    DateTime::parse_from_rfc3339(arg)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| anyhow!("expected a date like 2024-05-01 or a timestamp, got {arg}"))
+}
+
+/// # Errors
+///
+/// will return an error if the database cannot be queried
+pub fn stats(
+    db: &mut MatchInsightDB,
+    filter: &WinRateFilter,
+    output_format: OutputFormat,
+) -> Result<()> {
+    let stats = Stats::query(db, filter)?;
+    print!("{}", stats.render(output_format)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> Stats {
+        Stats {
+            overall: WinRate::new("All".to_string(), 3, 1, 0),
+            by_format: vec![WinRate::new("Ladder".to_string(), 3, 1, 0)],
+            by_deck: vec![WinRate::new("Mono Red, Burn".to_string(), 3, 1, 0)],
+            play_draw: vec![
+                WinRate::new("Play".to_string(), 4, 1, 0),
+                WinRate::new("Draw".to_string(), 1, 2, 0),
+            ],
+            mulligans: vec![WinRate::new("0".to_string(), 5, 3, 0)],
+            streaks: Streaks::new([false, true, true, true]),
+        }
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = stats().csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[1], "overall,All,3,1,0,0.7500,0.3006,0.9544");
+        assert_eq!(
+            lines[3],
+            "deck,\"Mono Red, Burn\",3,1,0,0.7500,0.3006,0.9544"
+        );
+        assert_eq!(lines[7], "streaks,current,3,0,,,,");
+    }
+
+    #[test]
+    fn test_table() {
+        let table = stats().table();
+        assert!(table.contains("Play/Draw (games)"));
+        assert!(table.contains("current       3 wins"));
+    }
+
+    #[test]
+    fn test_parse_time() -> Result<()> {
+        assert_eq!(
+            parse_time("2024-05-01")?,
+            parse_time("2024-05-01T00:00:00Z")?
+        );
+        assert!(parse_time("yesterday").is_err());
+        Ok(())
+    }
+}